@@ -0,0 +1,606 @@
+/*
+Buffer Manager API:
+  public BufferMgr(FileMgr fm, LogMgr lm, int numbuffs);
+  public Buffer pin(BlockId blk);
+  public void unpin(Buffer buff);
+  public int available();
+  public void flushAll(int txnum);
+
+Buffer API:
+  public Page contents();
+  public BlockId block();
+  public void setModified(int txnum, int lsn);
+  public boolean isPinned();
+  public int modifyingTx();
+ */
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    BlockMetadata, FileManager, Page, StormDbError,
+    error::Result,
+    log_manager::LogManager,
+    replacement_policy::{ReplacementPolicy, ReplacementStrategy},
+};
+
+/// A single frame of the buffer pool. Holds the contents of one block along with the bookkeeping needed to know when it can be replaced
+/// and what has to happen before it is written back.
+pub struct Buffer {
+    file_manager: Arc<FileManager>,
+    log_manager: Arc<Mutex<LogManager>>,
+    contents: Page,
+    block: Option<BlockMetadata>,
+    pins: u32,
+    // None means the buffer is clean. The book uses -1 for both of these, Option reads better to me.
+    modifying_transaction: Option<u32>,
+    lsn: Option<u32>,
+}
+
+impl Buffer {
+    pub fn new(file_manager: Arc<FileManager>, log_manager: Arc<Mutex<LogManager>>) -> Self {
+        let contents = Page::builder()
+            .with_block_size(file_manager.block_size())
+            .with_buffer()
+            .build();
+
+        Self {
+            file_manager,
+            log_manager,
+            contents,
+            block: None,
+            pins: 0,
+            modifying_transaction: None,
+            lsn: None,
+        }
+    }
+
+    /// Returns the page holding the contents of the block.
+    pub fn contents(&self) -> &Page {
+        &self.contents
+    }
+
+    /// Returns the page holding the contents of the block. Remember to call `set_modified` after changing it, otherwise the change never makes it to disk.
+    pub fn contents_mut(&mut self) -> &mut Page {
+        &mut self.contents
+    }
+
+    /// Returns the block currently assigned to this buffer, if any.
+    pub fn block(&self) -> Option<&BlockMetadata> {
+        self.block.as_ref()
+    }
+
+    /// Marks the buffer as modified by the given transaction. The lsn is the one of the log record describing the change,
+    /// None if the change wasn't logged.
+    pub fn set_modified(&mut self, transaction_number: u32, lsn: Option<u32>) {
+        self.modifying_transaction = Some(transaction_number);
+        // A change without a log record shouldn't lower the lsn we need flushed before writing this buffer back.
+        if lsn.is_some() {
+            self.lsn = lsn;
+        }
+    }
+
+    /// Returns whether any client currently has the buffer pinned.
+    pub fn is_pinned(&self) -> bool {
+        self.pins > 0
+    }
+
+    /// Returns the transaction that last modified the buffer, None if the buffer is clean.
+    pub fn modifying_transaction(&self) -> Option<u32> {
+        self.modifying_transaction
+    }
+
+    /// Returns the lsn of the latest log record for a change to this buffer, if any.
+    pub fn lsn(&self) -> Option<u32> {
+        self.lsn
+    }
+
+    /// Flushes the current contents (if dirty) and reads the given block into the buffer.
+    /// A block past the end of the file comes in as zeros, it gets created on disk the first time the buffer is flushed.
+    /// If the read fails the buffer is left without a block, so nobody gets handed the old block's page as if it were still valid.
+    pub(crate) fn assign_to_block(&mut self, block: BlockMetadata) -> Result<()> {
+        self.flush()?;
+        self.block = None;
+        self.file_manager
+            .read_zero_filled(&block, &mut self.contents)?;
+        self.block = Some(block);
+        self.pins = 0;
+        Ok(())
+    }
+
+    /// Writes the buffer back to disk if it is dirty. Write-ahead logging: the log is flushed before the block itself is written.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if self.modifying_transaction.is_none() {
+            return Ok(());
+        }
+
+        if let Some(lsn) = self.lsn {
+            self.log_manager
+                .lock()
+                .expect("log manager mutex poisoned")
+                .flush(lsn)?;
+        }
+
+        if let Some(block) = &self.block {
+            self.file_manager.write(block, &mut self.contents)?;
+        }
+        self.modifying_transaction = None;
+        Ok(())
+    }
+
+    pub(crate) fn pin(&mut self) {
+        self.pins += 1;
+    }
+
+    /// Errors if the buffer isn't pinned, unpinning it once too often would otherwise wrap the count around.
+    pub(crate) fn unpin(&mut self) -> Result<()> {
+        if !self.is_pinned() {
+            return Err(StormDbError::BlockNotPinned(format!(
+                "Unpinning a buffer that isn't pinned, it holds {}",
+                self.block
+                    .as_ref()
+                    .map_or("no block".to_string(), |block| block.to_string())
+            )));
+        }
+        self.pins -= 1;
+        Ok(())
+    }
+}
+
+/// Manages a fixed pool of buffers. Clients pin a block to get the id of the buffer holding it and unpin it once they are done.
+/// Buffers are handed out as ids instead of references so that several of them can be pinned at the same time without fighting the borrow checker.
+pub struct BufferManager {
+    file_manager: Arc<FileManager>,
+    buffer_pool: Vec<Buffer>,
+    available: usize,
+    replacement_policy: Box<dyn ReplacementPolicy>,
+}
+
+impl BufferManager {
+    /// Creates a buffer manager with `num_buffers` frames using the naive replacement strategy.
+    pub fn new(
+        file_manager: Arc<FileManager>,
+        log_manager: Arc<Mutex<LogManager>>,
+        num_buffers: usize,
+    ) -> Self {
+        Self::with_strategy(
+            file_manager,
+            log_manager,
+            num_buffers,
+            ReplacementStrategy::Naive,
+        )
+    }
+
+    /// Creates a buffer manager with `num_buffers` frames that picks victims according to the given strategy.
+    pub fn with_strategy(
+        file_manager: Arc<FileManager>,
+        log_manager: Arc<Mutex<LogManager>>,
+        num_buffers: usize,
+        strategy: ReplacementStrategy,
+    ) -> Self {
+        let buffer_pool = (0..num_buffers)
+            .map(|_| Buffer::new(file_manager.clone(), log_manager.clone()))
+            .collect();
+
+        Self {
+            file_manager,
+            buffer_pool,
+            available: num_buffers,
+            replacement_policy: strategy.policy(num_buffers),
+        }
+    }
+
+    /// Returns the number of unpinned buffers.
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    /// Flushes every buffer modified by the given transaction and syncs the files, as far as the sync policy goes.
+    pub fn flush_all(&mut self, transaction_number: u32) -> Result<()> {
+        for buffer in self.buffer_pool.iter_mut() {
+            if buffer.modifying_transaction() == Some(transaction_number) {
+                buffer.flush()?;
+            }
+        }
+        self.file_manager.sync_all()
+    }
+
+    /// Flushes every modified buffer, whichever transaction modified it, and syncs the files. Checkpoints need this.
+    pub fn flush_dirty(&mut self) -> Result<()> {
+        for buffer in self.buffer_pool.iter_mut() {
+            buffer.flush()?;
+        }
+        self.file_manager.sync_all()
+    }
+
+    /// Returns the buffer with the given id. An id that isn't in the pool is a `StormDbError::IndexOutOfBound`.
+    pub fn buffer(&self, buffer_id: usize) -> Result<&Buffer> {
+        let max_id = self.buffer_pool.len().saturating_sub(1);
+        self.buffer_pool
+            .get(buffer_id)
+            .ok_or(StormDbError::IndexOutOfBound(buffer_id, max_id))
+    }
+
+    /// Returns the buffer with the given id. An id that isn't in the pool is a `StormDbError::IndexOutOfBound`.
+    pub fn buffer_mut(&mut self, buffer_id: usize) -> Result<&mut Buffer> {
+        let max_id = self.buffer_pool.len().saturating_sub(1);
+        self.buffer_pool
+            .get_mut(buffer_id)
+            .ok_or(StormDbError::IndexOutOfBound(buffer_id, max_id))
+    }
+
+    /// Pins the block to a buffer and returns the id of that buffer. If the block isn't in the pool already an unpinned buffer is
+    /// replaced, and if there isn't one either we return `StormDbError::BufferAbort`.
+    // The book waits for a buffer to get unpinned here. We'd be waiting with the BufferManager mutex held, so nobody could unpin one,
+    // we bail instead and leave it to the caller to try again.
+    pub fn pin(&mut self, block: &BlockMetadata) -> Result<usize> {
+        let buffer_id = match self.find_existing_buffer(block) {
+            Some(buffer_id) => {
+                self.file_manager.stats().record_buffer_hit();
+                self.replacement_policy.record_pin(buffer_id, false);
+                buffer_id
+            }
+            None => {
+                let buffer_id = self.choose_unpinned_buffer().ok_or_else(|| {
+                    StormDbError::BufferAbort(format!("No buffer available to pin {}", block))
+                })?;
+                self.buffer_pool[buffer_id].assign_to_block(block.clone())?;
+                self.file_manager.stats().record_buffer_miss();
+                self.replacement_policy.record_pin(buffer_id, true);
+                buffer_id
+            }
+        };
+
+        let buffer = &mut self.buffer_pool[buffer_id];
+        if !buffer.is_pinned() {
+            self.available -= 1;
+        }
+        buffer.pin();
+        Ok(buffer_id)
+    }
+
+    /// Unpins the buffer with the given id. Unpinning a buffer that isn't pinned is a `StormDbError::BlockNotPinned` and changes nothing,
+    /// same as an id that isn't in the pool.
+    pub fn unpin(&mut self, buffer_id: usize) -> Result<()> {
+        let buffer = self.buffer_mut(buffer_id)?;
+        buffer.unpin()?;
+        if !buffer.is_pinned() {
+            self.available += 1;
+        }
+        self.replacement_policy.record_unpin(buffer_id);
+        Ok(())
+    }
+
+    fn find_existing_buffer(&self, block: &BlockMetadata) -> Option<usize> {
+        self.buffer_pool
+            .iter()
+            .position(|buffer| buffer.block() == Some(block))
+    }
+
+    fn choose_unpinned_buffer(&mut self) -> Option<usize> {
+        let buffer_pool = &self.buffer_pool;
+        self.replacement_policy
+            .choose_victim(&|buffer_id| buffer_pool[buffer_id].is_pinned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 256;
+
+    fn setup(tmp_dir: &TempDir, num_buffers: usize) -> (Arc<FileManager>, BufferManager) {
+        setup_with_strategy(tmp_dir, num_buffers, ReplacementStrategy::Naive)
+    }
+
+    fn setup_with_strategy(
+        tmp_dir: &TempDir,
+        num_buffers: usize,
+        strategy: ReplacementStrategy,
+    ) -> (Arc<FileManager>, BufferManager) {
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let log_manager = Arc::new(Mutex::new(
+            LogManager::builder("log.wal".to_string(), file_manager.clone())
+                .build()
+                .expect("failed to build log manager"),
+        ));
+        let buffer_manager =
+            BufferManager::with_strategy(file_manager.clone(), log_manager, num_buffers, strategy);
+        (file_manager, buffer_manager)
+    }
+
+    #[test]
+    fn test_pin_same_block_returns_same_buffer() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup(&tmp_dir, 3);
+        let block = BlockMetadata::new("test.tbl", 1);
+
+        let first = buffer_manager.pin(&block).expect("failed to pin");
+        let second = buffer_manager.pin(&block).expect("failed to pin");
+        assert_eq!(first, second);
+        assert_eq!(buffer_manager.available(), 2);
+
+        buffer_manager.unpin(first).expect("failed to unpin");
+        assert_eq!(buffer_manager.available(), 2);
+        buffer_manager.unpin(second).expect("failed to unpin");
+        assert_eq!(buffer_manager.available(), 3);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_double_unpin_fails() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup(&tmp_dir, 3);
+
+        let buffer_id = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        buffer_manager.unpin(buffer_id).expect("failed to unpin");
+        assert!(matches!(
+            buffer_manager.unpin(buffer_id),
+            Err(StormDbError::BlockNotPinned(_))
+        ));
+        // Never pinned at all.
+        assert!(matches!(
+            buffer_manager.unpin(2),
+            Err(StormDbError::BlockNotPinned(_))
+        ));
+        assert_eq!(buffer_manager.available(), 3);
+
+        // The pin counts are still right, the buffer can be pinned and unpinned as usual.
+        let buffer_id = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        assert_eq!(buffer_manager.available(), 2);
+        buffer_manager.unpin(buffer_id).expect("failed to unpin");
+        assert_eq!(buffer_manager.available(), 3);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_pin_fails_when_all_buffers_pinned() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup(&tmp_dir, 2);
+
+        buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 0))
+            .expect("failed to pin");
+        let second = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        assert_eq!(buffer_manager.available(), 0);
+
+        let result = buffer_manager.pin(&BlockMetadata::new("test.tbl", 2));
+        assert!(matches!(result, Err(StormDbError::BufferAbort(_))));
+
+        // Once something is unpinned its buffer can be reused.
+        buffer_manager.unpin(second).expect("failed to unpin");
+        let third = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 2))
+            .expect("failed to pin");
+        assert_eq!(third, second);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_bad_buffer_id() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup(&tmp_dir, 2);
+
+        assert!(matches!(
+            buffer_manager.buffer(2),
+            Err(StormDbError::IndexOutOfBound(2, 1))
+        ));
+        assert!(matches!(
+            buffer_manager.buffer_mut(2),
+            Err(StormDbError::IndexOutOfBound(2, 1))
+        ));
+        assert!(matches!(
+            buffer_manager.unpin(2),
+            Err(StormDbError::IndexOutOfBound(2, 1))
+        ));
+        assert_eq!(buffer_manager.available(), 2);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_dirty_buffer_written_back_on_replacement() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (file_manager, mut buffer_manager) = setup(&tmp_dir, 1);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let buffer_id = buffer_manager.pin(&block).expect("failed to pin");
+        let buffer = buffer_manager
+            .buffer_mut(buffer_id)
+            .expect("failed to get buffer");
+        buffer
+            .contents_mut()
+            .write_int(20, 1234)
+            .expect("failed to write int");
+        buffer.set_modified(1, Some(1));
+        assert_eq!(buffer.modifying_transaction(), Some(1));
+        buffer_manager.unpin(buffer_id).expect("failed to unpin");
+
+        // Only one frame, so pinning another block forces the dirty one out.
+        let other = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        assert_eq!(
+            buffer_manager
+                .buffer(other)
+                .expect("failed to get buffer")
+                .modifying_transaction(),
+            None
+        );
+
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        file_manager
+            .read(&block, &mut page)
+            .expect("failed to read block");
+        assert_eq!(page.read_int(20).expect("failed to read int"), 1234);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_failed_read_does_not_keep_old_block() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (file_manager, mut buffer_manager) = setup(&tmp_dir, 1);
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        for (block_number, value) in [(0, 1234), (1, 5678)] {
+            page.write_int(20, value).expect("failed to write int");
+            file_manager
+                .write(&BlockMetadata::new("test.tbl", block_number), &mut page)
+                .expect("failed to write block");
+        }
+        // Block 1 gets cut off halfway.
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_dir.path().join("test.tbl"))
+            .expect("failed to open file")
+            .set_len((BLOCK_SIZE + BLOCK_SIZE / 2) as u64)
+            .expect("failed to cut file");
+
+        let block = BlockMetadata::new("test.tbl", 0);
+        let buffer_id = buffer_manager.pin(&block).expect("failed to pin");
+        buffer_manager.unpin(buffer_id).expect("failed to unpin");
+        assert!(matches!(
+            buffer_manager.pin(&BlockMetadata::new("test.tbl", 1)),
+            Err(StormDbError::ShortRead { .. })
+        ));
+        assert_eq!(
+            buffer_manager
+                .buffer(buffer_id)
+                .expect("failed to get buffer")
+                .block(),
+            None
+        );
+
+        let buffer_id = buffer_manager.pin(&block).expect("failed to pin");
+        assert_eq!(
+            buffer_manager
+                .buffer(buffer_id)
+                .expect("failed to get buffer")
+                .contents()
+                .read_int(20)
+                .expect("failed to read int"),
+            1234
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_flush_all_only_flushes_given_transaction() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup(&tmp_dir, 3);
+
+        let first = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 0))
+            .expect("failed to pin");
+        let second = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        buffer_manager
+            .buffer_mut(first)
+            .expect("failed to get buffer")
+            .set_modified(1, None);
+        buffer_manager
+            .buffer_mut(second)
+            .expect("failed to get buffer")
+            .set_modified(2, None);
+
+        buffer_manager.flush_all(1).expect("failed to flush");
+        assert_eq!(
+            buffer_manager
+                .buffer(first)
+                .expect("failed to get buffer")
+                .modifying_transaction(),
+            None
+        );
+        assert_eq!(
+            buffer_manager
+                .buffer(second)
+                .expect("failed to get buffer")
+                .modifying_transaction(),
+            Some(2)
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    // Naive always grabs the first unpinned buffer, so block 1 keeps kicking block 0 out and vice versa.
+    #[case(ReplacementStrategy::Naive, 0, 5)]
+    #[case(ReplacementStrategy::Lru, 3, 2)]
+    #[case(ReplacementStrategy::Clock, 3, 2)]
+    #[case(ReplacementStrategy::LruK(2), 3, 2)]
+    #[case(ReplacementStrategy::TwoQueue, 3, 2)]
+    fn test_hits_and_misses_recorded(
+        #[case] strategy: ReplacementStrategy,
+        #[case] expected_hits: u64,
+        #[case] expected_misses: u64,
+    ) {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (file_manager, mut buffer_manager) = setup_with_strategy(&tmp_dir, 2, strategy);
+
+        // Two blocks in a pool of two, a sensible policy only misses on the first pin of each.
+        for block_number in [0, 1, 0, 1, 0] {
+            let buffer_id = buffer_manager
+                .pin(&BlockMetadata::new("test.tbl", block_number))
+                .expect("failed to pin");
+            buffer_manager.unpin(buffer_id).expect("failed to unpin");
+        }
+
+        let file_manager = file_manager;
+        assert_eq!(file_manager.stats().buffer_hits(), expected_hits);
+        assert_eq!(file_manager.stats().buffer_misses(), expected_misses);
+        assert_eq!(
+            file_manager.stats().buffer_hit_ratio(),
+            expected_hits as f64 / 5.0
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_lru_replaces_least_recently_pinned_block() {
+        let tmp_dir = TempDir::new("test_buffer_manager").expect("failed to create temp dir");
+        let (_, mut buffer_manager) = setup_with_strategy(&tmp_dir, 2, ReplacementStrategy::Lru);
+
+        let first = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 0))
+            .expect("failed to pin");
+        let second = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 1))
+            .expect("failed to pin");
+        buffer_manager.unpin(first).expect("failed to unpin");
+        buffer_manager.unpin(second).expect("failed to unpin");
+        // Touch block 0 again so block 1 becomes the least recently used one.
+        let first = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 0))
+            .expect("failed to pin");
+        buffer_manager.unpin(first).expect("failed to unpin");
+
+        let third = buffer_manager
+            .pin(&BlockMetadata::new("test.tbl", 2))
+            .expect("failed to pin");
+        assert_eq!(third, second);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
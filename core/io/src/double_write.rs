@@ -0,0 +1,165 @@
+/*
+Double-write buffer, same idea as InnoDB's. A crash in the middle of a block write can leave the block half old and half new, and
+neither undo nor redo records can fix that since they only describe a couple of bytes inside the block.
+So before a block gets written in place, a full copy of it goes to the double-write file and is synced. If the in-place write tears,
+the copy is still there and gets put back when the FileManager opens the directory again. If the copy itself tears, the in-place write
+never started and the old block is fine.
+
+Record layout:
+    block number (u64) | file name length (u32) | file name | block bytes | CRC32C of everything before it (u32)
+*/
+
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+use crate::{BlockMetadata, checksum::crc32c, error::Result};
+
+const BLOCK_NUMBER_SIZE: usize = 8;
+const NAME_LENGTH_SIZE: usize = 4;
+const CRC_SIZE: usize = 4;
+
+pub(crate) struct DoubleWrite {
+    file: File,
+    block_size: usize,
+    // Where the next record goes.
+    end: u64,
+    records: usize,
+}
+
+impl DoubleWrite {
+    pub(crate) fn open(path: &Path, block_size: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let end = file.metadata()?.len();
+        Ok(Self {
+            file,
+            block_size,
+            end,
+            records: 0,
+        })
+    }
+
+    pub(crate) fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Returns every complete record in the file, oldest first. Stops at the first one that's cut off or doesn't match its CRC, that's
+    /// the one we crashed while writing.
+    pub(crate) fn read_records(&self) -> Result<Vec<(BlockMetadata, Vec<u8>)>> {
+        let mut bytes = vec![0u8; self.end as usize];
+        self.file.read_exact_at(&mut bytes, 0)?;
+
+        let mut records = Vec::new();
+        let mut rest = bytes.as_slice();
+        while let Some((record, length)) = self.decode(rest) {
+            records.push(record);
+            rest = &rest[length..];
+        }
+        Ok(records)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<((BlockMetadata, Vec<u8>), usize)> {
+        let header = BLOCK_NUMBER_SIZE + NAME_LENGTH_SIZE;
+        if bytes.len() < header {
+            return None;
+        }
+        let block_number = u64::from_be_bytes(bytes[..BLOCK_NUMBER_SIZE].try_into().ok()?);
+        let name_length =
+            u32::from_be_bytes(bytes[BLOCK_NUMBER_SIZE..header].try_into().ok()?) as usize;
+        let body = header + name_length + self.block_size;
+        if bytes.len() < body + CRC_SIZE {
+            return None;
+        }
+        let crc = u32::from_be_bytes(bytes[body..body + CRC_SIZE].try_into().ok()?);
+        if crc != crc32c(&bytes[..body]) {
+            return None;
+        }
+
+        let file_name = std::str::from_utf8(&bytes[header..header + name_length]).ok()?;
+        let block = BlockMetadata::new(file_name, block_number as usize);
+        let block_bytes = bytes[header + name_length..body].to_vec();
+        Some(((block, block_bytes), body + CRC_SIZE))
+    }
+
+    /// Appends a copy of the block and syncs it. Only after this returns may the block be written in place.
+    pub(crate) fn stage(&mut self, block: &BlockMetadata, bytes: &[u8]) -> Result<()> {
+        let file_name = block.file_name();
+        let mut record = Vec::with_capacity(
+            BLOCK_NUMBER_SIZE + NAME_LENGTH_SIZE + file_name.len() + bytes.len() + CRC_SIZE,
+        );
+        record.extend_from_slice(&(block.block_number() as u64).to_be_bytes());
+        record.extend_from_slice(&(file_name.len() as u32).to_be_bytes());
+        record.extend_from_slice(file_name.as_bytes());
+        record.extend_from_slice(bytes);
+        let crc = crc32c(&record);
+        record.extend_from_slice(&crc.to_be_bytes());
+
+        self.file.write_all_at(&record, self.end)?;
+        self.file.sync_data()?;
+        self.end += record.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Throws every record away. Only safe once all the blocks they cover have been synced in place.
+    pub(crate) fn clear(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        self.end = 0;
+        self.records = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 16;
+
+    #[test]
+    fn test_torn_record_is_ignored() {
+        let tmp_dir = TempDir::new("test_double_write").expect("failed to create temp dir");
+        let path = tmp_dir.path().join("doublewrite.dw");
+        let mut double_write = DoubleWrite::open(&path, BLOCK_SIZE).expect("failed to open");
+        double_write
+            .stage(&BlockMetadata::new("a.tbl", 3), &[1u8; BLOCK_SIZE])
+            .expect("failed to stage");
+        double_write
+            .stage(&BlockMetadata::new("b.tbl", 0), &[2u8; BLOCK_SIZE])
+            .expect("failed to stage");
+        assert_eq!(double_write.records(), 2);
+
+        // The second record loses its last bytes, like a crash halfway through staging it.
+        let length = std::fs::metadata(&path)
+            .expect("failed to get metadata")
+            .len();
+        double_write
+            .file
+            .set_len(length - 3)
+            .expect("failed to cut file");
+        let mut reopened = DoubleWrite::open(&path, BLOCK_SIZE).expect("failed to open");
+        assert_eq!(
+            reopened.read_records().expect("failed to read records"),
+            vec![(BlockMetadata::new("a.tbl", 3), vec![1u8; BLOCK_SIZE])]
+        );
+
+        reopened.clear().expect("failed to clear");
+        assert!(
+            reopened
+                .read_records()
+                .expect("failed to read records")
+                .is_empty()
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
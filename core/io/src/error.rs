@@ -1,6 +1,8 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+use crate::BlockMetadata;
+
 /// Custom Result type for StormDB operations
 pub type Result<T> = std::result::Result<T, StormDbError>;
 
@@ -8,11 +10,48 @@ pub type Result<T> = std::result::Result<T, StormDbError>;
 pub enum StormDbError {
     OutOfBound(String),
     IndexOutOfBound(usize, usize),
-    Corrupt(String),
+    // Bytes that don't decode into what they're supposed to be, or a block that fails its checksum (torn write or bit rot). The block is
+    // only known for the checksum, decoding varints, strings and records happens on bytes nobody knows the block of.
+    Corrupt {
+        block: Option<BlockMetadata>,
+        message: String,
+    },
     InvalidUtf8,
     // Doesn't seem like it'd be easy to use ngl. Wrapping a std error in my own one. But this makes the code a bit simpler so I'll roll with it for now.
     IOError(std::io::Error),
     InvalidBool,
+    // Every buffer in the pool is pinned, so there's nothing to replace.
+    BufferAbort(String),
+    // A transaction tried to read or write a block it never pinned, or a buffer got unpinned more often than it was pinned.
+    BlockNotPinned(String),
+    // Gave up waiting for a lock on a block, the transaction should roll back.
+    LockAbort(String),
+    // Snapshot isolation, somebody else committed a write to the same value first.
+    WriteConflict(String),
+    // Fewer bytes than a whole block came back, the block is cut off (or past EOF for a strict read).
+    ShortRead {
+        block: BlockMetadata,
+        got: usize,
+    },
+    // The disk stopped taking bytes partway through a block.
+    ShortWrite {
+        block: BlockMetadata,
+        written: usize,
+    },
+    // The superblock says the database was written with settings (block size, format version, ...) it's now being opened without.
+    IncompatibleDatabase(String),
+    // Freeing a block that doesn't exist or is already free.
+    InvalidFree(String),
+    // Asked for something by name (a field, a table) that isn't there, or for the current record when there isn't one.
+    NotFound(String),
+    // Creating a table, view or index under a name that's taken.
+    AlreadyExists(String),
+    // SQL that doesn't parse, the position is where the offending token starts, both counted from 1.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 }
 
 impl Error for StormDbError {}
@@ -23,11 +62,41 @@ impl Display for StormDbError {
             Self::IndexOutOfBound(idx, max_idx) => {
                 write!(f, "Index out of bounds {}. Max index: {}", idx, max_idx)
             }
-            StormDbError::Corrupt(message) => write!(f, "{}", message),
+            StormDbError::Corrupt {
+                block: Some(block),
+                message,
+            } => write!(f, "Corrupt block {}, {}", block, message),
+            StormDbError::Corrupt {
+                block: None,
+                message,
+            } => write!(f, "{}", message),
             StormDbError::InvalidUtf8 => write!(f, "Invalid UTF8"),
             StormDbError::IOError(error) => write!(f, "{}", error),
             StormDbError::InvalidBool => write!(f, "Invalid Boolean."),
             StormDbError::OutOfBound(msg) => write!(f, "{}", msg),
+            StormDbError::BufferAbort(msg) => write!(f, "{}", msg),
+            StormDbError::BlockNotPinned(msg) => write!(f, "{}", msg),
+            StormDbError::LockAbort(msg) => write!(f, "{}", msg),
+            StormDbError::WriteConflict(msg) => write!(f, "{}", msg),
+            StormDbError::IncompatibleDatabase(msg) => write!(f, "{}", msg),
+            StormDbError::InvalidFree(msg) => write!(f, "{}", msg),
+            StormDbError::NotFound(msg) => write!(f, "{}", msg),
+            StormDbError::AlreadyExists(msg) => write!(f, "{}", msg),
+            StormDbError::ShortRead { block, got } => {
+                write!(f, "Short read of {}, only got {} bytes", block, got)
+            }
+            StormDbError::ShortWrite { block, written } => {
+                write!(f, "Short write of {}, only wrote {} bytes", block, written)
+            }
+            StormDbError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Syntax error at line {}, column {}: {}",
+                line, column, message
+            ),
         }
     }
 }
@@ -46,10 +115,61 @@ impl PartialEq for StormDbError {
             (StormDbError::IndexOutOfBound(a1, a2), StormDbError::IndexOutOfBound(b1, b2)) => {
                 a1 == b1 && a2 == b2
             }
-            (StormDbError::Corrupt(a), StormDbError::Corrupt(b)) => a == b,
+            (
+                StormDbError::Corrupt {
+                    block: a,
+                    message: a_message,
+                },
+                StormDbError::Corrupt {
+                    block: b,
+                    message: b_message,
+                },
+            ) => a == b && a_message == b_message,
             (StormDbError::InvalidUtf8, StormDbError::InvalidUtf8) => true,
             (StormDbError::IOError(a), StormDbError::IOError(b)) => a.kind() == b.kind(),
             (StormDbError::InvalidBool, StormDbError::InvalidBool) => true,
+            (StormDbError::BufferAbort(a), StormDbError::BufferAbort(b)) => a == b,
+            (StormDbError::BlockNotPinned(a), StormDbError::BlockNotPinned(b)) => a == b,
+            (StormDbError::LockAbort(a), StormDbError::LockAbort(b)) => a == b,
+            (StormDbError::WriteConflict(a), StormDbError::WriteConflict(b)) => a == b,
+            (StormDbError::IncompatibleDatabase(a), StormDbError::IncompatibleDatabase(b)) => {
+                a == b
+            }
+            (StormDbError::InvalidFree(a), StormDbError::InvalidFree(b)) => a == b,
+            (StormDbError::NotFound(a), StormDbError::NotFound(b)) => a == b,
+            (StormDbError::AlreadyExists(a), StormDbError::AlreadyExists(b)) => a == b,
+            (
+                StormDbError::ShortRead {
+                    block: a,
+                    got: a_got,
+                },
+                StormDbError::ShortRead {
+                    block: b,
+                    got: b_got,
+                },
+            ) => a == b && a_got == b_got,
+            (
+                StormDbError::ShortWrite {
+                    block: a,
+                    written: a_written,
+                },
+                StormDbError::ShortWrite {
+                    block: b,
+                    written: b_written,
+                },
+            ) => a == b && a_written == b_written,
+            (
+                StormDbError::Syntax {
+                    line: a_line,
+                    column: a_column,
+                    message: a,
+                },
+                StormDbError::Syntax {
+                    line: b_line,
+                    column: b_column,
+                    message: b,
+                },
+            ) => a_line == b_line && a_column == b_column && a == b,
             _ => false,
         }
     }
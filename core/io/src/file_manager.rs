@@ -12,24 +12,145 @@ API will be like:
 use std::{
     collections::HashMap,
     fs::{self, File, OpenOptions},
-    io::{Read, Seek, Write},
+    io,
+    os::unix::fs::{FileExt, OpenOptionsExt},
     path::PathBuf,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use crate::{block_metadata::BlockMetadata, error::Result, page::Page};
+use crate::{
+    StormDbError, block_metadata::BlockMetadata, checksum::crc32c, double_write::DoubleWrite,
+    error::Result, page::Page, superblock::Superblock,
+};
+
+mod free_space;
+
+// Size of the checksum trailer stored after every block when checksums are on.
+const CHECKSUM_SIZE: usize = 4;
+const DOUBLE_WRITE_FILE: &str = "doublewrite.dw";
+// Once the double-write file holds this many blocks everything gets synced in place and it starts over, so it doesn't grow forever
+// when nobody calls sync_all (or the sync policy keeps skipping it).
+const DOUBLE_WRITE_CAPACITY: usize = 64;
+
+/// When the FileManager makes writes durable. Whoever needs writes to survive a crash calls `FileManager::sync` (the log manager on
+/// every flush, the buffer manager after flushing buffers for a commit or checkpoint) and the policy decides what that does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Never syncs, durability is up to the OS.
+    None,
+    /// Every sync call with something unsynced in the file goes to disk.
+    #[default]
+    EveryFlush,
+    /// Sync calls only go to disk once `interval` has passed since the last one or `bytes` have been written since, so a crash can
+    /// lose what was written inside that window.
+    GroupCommit { interval: Duration, bytes: usize },
+    /// Files are opened with O_DSYNC, every write is durable by the time it returns and sync calls don't have to do anything.
+    DSync,
+}
 
+// Every method takes &self so a single FileManager can be shared between threads behind an Arc.
+// With checksums on every block gets a CRC32C trailer on disk. Pages stay block_size, the trailer sits right after the block in the
+// file, so nothing above the FileManager has to know about it. A block slot is then block_size + 4 bytes in the file.
+// With double-write on, every block write first goes to the double-write file (see double_write.rs). Writers hold its lock through
+// the in-place write as well, so whoever clears it knows nobody is halfway through a block.
+// Block reads and writes are positional (pread/pwrite), there's no shared cursor so they don't need a lock at all. Only things that
+// change the length of a file take its resize lock. The map of open files is only locked long enough to look a file up.
 pub struct FileManager {
     db_directory: PathBuf,
     block_size: usize,
     is_new: bool,
-    open_files: HashMap<String, File>,
+    superblock: Superblock,
+    open_files: Mutex<HashMap<String, Arc<OpenFile>>>,
+    sync_policy: SyncPolicy,
+    checksums: bool,
+    double_write: Option<Mutex<DoubleWrite>>,
     stats: IOStats,
 }
 
+struct OpenFile {
+    file: File,
+    // Held from reading the length to writing the new block, otherwise two appends could hand out the same block number.
+    resize: Mutex<()>,
+    unsynced_bytes: AtomicUsize,
+    // Also what makes two syncs of the same file wait for each other.
+    last_sync: Mutex<Instant>,
+}
+
+impl OpenFile {
+    fn lock_resize(&self) -> MutexGuard<'_, ()> {
+        self.resize.lock().expect("file resize mutex poisoned")
+    }
+
+    // read_exact_at without the error at EOF, returns how many bytes were actually there.
+    fn read_fully_at(&self, mut buffer: &mut [u8], mut offset: u64) -> io::Result<usize> {
+        let mut bytes_read = 0;
+        while !buffer.is_empty() {
+            match self.file.read_at(buffer, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    bytes_read += n;
+                    offset += n as u64;
+                    buffer = &mut buffer[n..];
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(bytes_read)
+    }
+
+    // write_all_at, except it says how far it got when the disk stops taking bytes.
+    fn write_fully_at(&self, mut buffer: &[u8], mut offset: u64) -> io::Result<usize> {
+        let mut bytes_written = 0;
+        while !buffer.is_empty() {
+            match self.file.write_at(buffer, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    bytes_written += n;
+                    offset += n as u64;
+                    buffer = &buffer[n..];
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(bytes_written)
+    }
+}
+
 impl FileManager {
     // I think I'll go with Result here, there's a chance opening the directory fails or file creation fails, panicing doesn't seem like the right thing to do.
-    /// Retruns a new FileManager struct.
+    /// Retruns a new FileManager struct that syncs on every flush.
     pub fn new(db_directory: PathBuf, block_size: usize) -> Result<Self> {
+        Self::builder(db_directory, block_size).build()
+    }
+
+    /// Returns a new FileManager that makes writes durable according to the given policy.
+    pub fn with_sync_policy(
+        db_directory: PathBuf,
+        block_size: usize,
+        sync_policy: SyncPolicy,
+    ) -> Result<Self> {
+        Self::builder(db_directory, block_size)
+            .with_sync_policy(sync_policy)
+            .build()
+    }
+
+    pub fn builder(db_directory: PathBuf, block_size: usize) -> FileManagerBuilder {
+        FileManagerBuilder::new(db_directory, block_size)
+    }
+
+    fn open(
+        db_directory: PathBuf,
+        block_size: usize,
+        sync_policy: SyncPolicy,
+        checksums: bool,
+        double_write: bool,
+    ) -> Result<Self> {
         let is_new = !db_directory.exists();
         if is_new {
             // If we fail to create the directory panicing maybe makes sense.
@@ -41,24 +162,105 @@ impl FileManager {
         let db_files = std::fs::read_dir(&db_directory)?;
 
         // Remove all temp files on startup
-        for file in db_files {
-            if let Ok(file) = file {
-                // TODO: Handle this one as well.
-                if !file.file_name().into_string().unwrap().starts_with("temp") {
-                    continue;
-                } else {
-                    std::fs::remove_file(file.path()).expect("failed to remove file");
-                }
+        let mut has_data_files = false;
+        for file in db_files.flatten() {
+            // TODO: Handle this one as well.
+            if !file.file_name().into_string().unwrap().starts_with("temp") {
+                has_data_files = true;
+            } else {
+                std::fs::remove_file(file.path()).expect("failed to remove file");
             }
         }
 
-        Ok(FileManager {
+        // Only a new (or empty) directory gets a fresh superblock. Files without one could have been written with any block size,
+        // stamping ours on them would just hide that.
+        let superblock = match Superblock::read(&db_directory)? {
+            Some(superblock) => {
+                superblock.validate(block_size, checksums)?;
+                superblock
+            }
+            None if has_data_files => {
+                return Err(StormDbError::IncompatibleDatabase(format!(
+                    "{} has files but no superblock, not a StormDB directory or one from before superblocks",
+                    db_directory.display()
+                )));
+            }
+            None => {
+                let superblock = Superblock::new(block_size, checksums);
+                superblock.write(&db_directory)?;
+                superblock
+            }
+        };
+
+        let double_write = if double_write {
+            Some(Mutex::new(DoubleWrite::open(
+                &db_directory.join(DOUBLE_WRITE_FILE),
+                block_size,
+            )?))
+        } else {
+            None
+        };
+
+        let file_manager = FileManager {
             db_directory,
             block_size,
             is_new,
-            open_files: HashMap::new(),
+            superblock,
+            open_files: Mutex::new(HashMap::new()),
+            sync_policy,
+            checksums,
+            double_write,
             stats: IOStats::new(),
-        })
+        };
+        file_manager.restore_torn_blocks()?;
+        Ok(file_manager)
+    }
+
+    // Puts back every block left in the double-write file. They're the newest copies of their blocks, so writing them again is
+    // harmless for the ones that made it in place and repairs the ones that tore. Has to run before anyone reads anything, log
+    // recovery included.
+    fn restore_torn_blocks(&self) -> Result<()> {
+        let Some(double_write) = &self.double_write else {
+            return Ok(());
+        };
+        let mut double_write = double_write.lock().expect("double write mutex poisoned");
+        let records = double_write.read_records()?;
+        for (block, bytes) in &records {
+            let file = self.get_file(&block.file_name())?;
+            self.write_in_place(&file, block, bytes)?;
+        }
+        self.stats
+            .blocks_restored
+            .fetch_add(records.len() as u64, Ordering::Relaxed);
+        self.checkpoint_double_write(&mut double_write)
+    }
+
+    // Syncs every open file no matter the policy and then empties the double-write file.
+    fn checkpoint_double_write(&self, double_write: &mut DoubleWrite) -> Result<()> {
+        let files: Vec<Arc<OpenFile>> = self
+            .open_files
+            .lock()
+            .expect("open files mutex poisoned")
+            .values()
+            .cloned()
+            .collect();
+        for file in files {
+            self.force_sync(&file)?;
+        }
+        double_write.clear()
+    }
+
+    // Syncs the file if anything was written to it since the last sync, whatever the policy says.
+    fn force_sync(&self, file: &OpenFile) -> Result<()> {
+        let _last_sync = file.last_sync.lock().expect("last sync mutex poisoned");
+        let unsynced_bytes = file.unsynced_bytes.load(Ordering::Relaxed);
+        if unsynced_bytes > 0 {
+            file.file.sync_data()?;
+            file.unsynced_bytes
+                .fetch_sub(unsynced_bytes, Ordering::Relaxed);
+            self.stats.syncs.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
     }
 
     /// Returns whether the connection was new or not.
@@ -66,126 +268,1041 @@ impl FileManager {
         self.is_new
     }
 
+    /// Returns what the superblock of the directory says about it.
+    pub fn superblock(&self) -> &Superblock {
+        &self.superblock
+    }
+
     /// Returns the block size of the DB instance.
     pub fn block_size(&self) -> usize {
         self.block_size
     }
 
+    pub fn sync_policy(&self) -> SyncPolicy {
+        self.sync_policy
+    }
+
+    /// Returns whether block writes go through the double-write file first.
+    pub fn double_write(&self) -> bool {
+        self.double_write.is_some()
+    }
+
+    /// Returns whether blocks carry a checksum on disk.
+    pub fn checksums(&self) -> bool {
+        self.checksums
+    }
+
     /// Get's the file with the specified name from the open files if present. Otherwise opens the file and adds it to the open files hash. If file does not exist one is created.
-    fn get_file(&mut self, file_name: &str) -> Result<File> {
-        if let Some(file) = self.open_files.get(file_name) {
-            // clone returns a reference. I was stuck on that for embarrassingly long time.
-            Ok(file.try_clone()?)
+    fn get_file(&self, file_name: &str) -> Result<Arc<OpenFile>> {
+        let mut open_files = self.open_files.lock().expect("open files mutex poisoned");
+        if let Some(file) = open_files.get(file_name) {
+            Ok(file.clone())
         } else {
             let db_table = self.db_directory.join(file_name);
-            let file = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(db_table)?;
-            self.open_files
-                .insert(file_name.to_string(), file.try_clone()?);
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create(true).truncate(false);
+            if self.sync_policy == SyncPolicy::DSync {
+                options.custom_flags(libc::O_DSYNC);
+            }
+            let file = Arc::new(OpenFile {
+                file: options.open(db_table)?,
+                resize: Mutex::new(()),
+                unsynced_bytes: AtomicUsize::new(0),
+                last_sync: Mutex::new(Instant::now()),
+            });
+            open_files.insert(file_name.to_string(), file.clone());
             Ok(file)
         }
     }
 
-    /// Reads block into given page.
-    pub fn read(&mut self, block: &BlockMetadata, page: &mut Page) -> Result<()> {
-        let mut file = self.get_file(&block.file_name())?;
-        file.seek(std::io::SeekFrom::Start(
-            (block.block_number() * page.block_size) as u64,
-        ))?;
+    // How many bytes a block takes up in the file, trailer included.
+    fn slot_size(&self) -> usize {
+        if self.checksums {
+            self.block_size + CHECKSUM_SIZE
+        } else {
+            self.block_size
+        }
+    }
 
-        file.read(page.byte_buffer.as_mut_slice())?;
+    fn offset(&self, block: &BlockMetadata) -> u64 {
+        (block.block_number() * self.slot_size()) as u64
+    }
 
-        Ok(())
+    /// Reads block into given page. The whole block has to be in the file, anything less is a `StormDbError::ShortRead`.
+    /// With checksums on, a block whose checksum doesn't match is a `StormDbError::Corrupt` carrying the block.
+    pub fn read(&self, block: &BlockMetadata, page: &mut Page) -> Result<()> {
+        self.read_block(block, page, false)
+    }
+
+    /// Same as `read`, except a block that is entirely past the end of the file reads as all zeros. A block that's only partly there
+    /// is still a `StormDbError::ShortRead`.
+    pub fn read_zero_filled(&self, block: &BlockMetadata, page: &mut Page) -> Result<()> {
+        self.read_block(block, page, true)
+    }
+
+    fn read_block(&self, block: &BlockMetadata, page: &mut Page, zero_fill: bool) -> Result<()> {
+        let file = self.get_file(&block.file_name())?;
+        if self.checksums {
+            return self.read_checked_block(&file, block, page, zero_fill);
+        }
+        // Read aside and copy over once it's a whole block, a short read must not leave the page half old and half new.
+        let mut bytes = vec![0u8; self.block_size];
+        let bytes_read = file.read_fully_at(&mut bytes, self.offset(block))?;
+        self.stats.blocks_read.fetch_add(1, Ordering::Relaxed);
+
+        if bytes_read == bytes.len() {
+            page.byte_buffer.copy_from_slice(&bytes);
+            Ok(())
+        } else if bytes_read == 0 && zero_fill {
+            // Pages get reused for different blocks (buffer pool frames for one), so this has to be zeros and not the previous block's bytes.
+            page.byte_buffer.fill(0);
+            Ok(())
+        } else {
+            Err(StormDbError::ShortRead {
+                block: block.clone(),
+                got: bytes_read,
+            })
+        }
     }
 
-    /// Writes block to the file.
-    pub fn write(&mut self, block: &BlockMetadata, page: &mut Page) -> Result<()> {
-        let mut file = self.get_file(&block.file_name())?;
-        file.seek(std::io::SeekFrom::Start(
-            (block.block_number() * page.block_size) as u64,
-        ))?;
+    fn read_checked_block(
+        &self,
+        file: &OpenFile,
+        block: &BlockMetadata,
+        page: &mut Page,
+        zero_fill: bool,
+    ) -> Result<()> {
+        let mut slot = vec![0u8; self.slot_size()];
+        let bytes_read = file.read_fully_at(&mut slot, self.offset(block))?;
+        self.stats.blocks_read.fetch_add(1, Ordering::Relaxed);
 
-        file.write(page.byte_buffer.as_mut_slice())?;
+        if bytes_read == 0 && zero_fill {
+            page.byte_buffer.fill(0);
+            return Ok(());
+        } else if bytes_read < slot.len() {
+            return Err(StormDbError::ShortRead {
+                block: block.clone(),
+                got: bytes_read,
+            });
+        }
+
+        let (bytes, trailer) = slot.split_at(self.block_size);
+        let expected = u32::from_be_bytes(trailer.try_into().expect("trailer is 4 bytes"));
+        let actual = crc32c(bytes);
+        // All zeros, trailer included, is a block nobody wrote yet (a hole left by writing further into the file), not a broken one.
+        let never_written = expected == 0 && bytes.iter().all(|byte| *byte == 0);
+        if expected != actual && !never_written {
+            return Err(StormDbError::Corrupt {
+                block: Some(block.clone()),
+                message: format!(
+                    "checksum is {:#010x} but the block hashes to {:#010x}",
+                    expected, actual
+                ),
+            });
+        }
+        page.byte_buffer.copy_from_slice(bytes);
         Ok(())
     }
 
+    /// Writes block to the file. Running out of disk halfway through is a `StormDbError::ShortWrite`.
+    pub fn write(&self, block: &BlockMetadata, page: &mut Page) -> Result<()> {
+        let file = self.get_file(&block.file_name())?;
+        self.write_block(&file, block, page.byte_buffer.as_slice())
+    }
+
     /// Appends a new block the end of the file.
-    pub fn append(&mut self, file_name: &str) -> Result<BlockMetadata> {
-        let mut file = self.get_file(&file_name)?;
-        let file_metadata = file.metadata()?;
-        let block_number = file_metadata.len() as usize / self.block_size;
+    pub fn append(&self, file_name: &str) -> Result<BlockMetadata> {
+        let file = self.get_file(file_name)?;
+        let _resize = file.lock_resize();
+        self.append_locked(&file, file_name)
+    }
+
+    // Caller holds the file's resize lock.
+    fn append_locked(&self, file: &OpenFile, file_name: &str) -> Result<BlockMetadata> {
+        let block_number = self.block_count(file)?;
         let block = BlockMetadata::new(file_name, block_number);
         let bytes = vec![0u8; self.block_size];
 
-        file.seek(std::io::SeekFrom::End(0))?;
-        file.write(&bytes)?;
+        self.write_block(file, &block, &bytes)?;
         Ok(block)
     }
 
-    /// Returns the size of file in blocks, if the file exists, None otherwise.
-    pub fn length(&self, file_name: &str) -> Option<usize> {
-        let file = self.open_files.get(file_name);
-        if let Some(file) = file {
-            Some(
-                file.metadata()
-                    .expect("Error getting metadata for file.")
-                    .len() as usize,
-            )
+    fn block_count(&self, file: &OpenFile) -> Result<usize> {
+        Ok(file.file.metadata()?.len() as usize / self.slot_size())
+    }
+
+    fn write_block(&self, file: &OpenFile, block: &BlockMetadata, bytes: &[u8]) -> Result<()> {
+        let Some(double_write) = &self.double_write else {
+            return self.write_in_place(file, block, bytes);
+        };
+        let mut double_write = double_write.lock().expect("double write mutex poisoned");
+        if double_write.records() >= DOUBLE_WRITE_CAPACITY {
+            self.checkpoint_double_write(&mut double_write)?;
+        }
+        double_write.stage(block, bytes)?;
+        self.write_in_place(file, block, bytes)
+    }
+
+    fn write_in_place(&self, file: &OpenFile, block: &BlockMetadata, bytes: &[u8]) -> Result<()> {
+        // Stamped in the same write as the block so the two can't get out of step because of us.
+        let stamped;
+        let bytes = if self.checksums {
+            stamped = [bytes, &crc32c(bytes).to_be_bytes()].concat();
+            stamped.as_slice()
         } else {
-            None
+            bytes
+        };
+
+        let bytes_written = file.write_fully_at(bytes, self.offset(block))?;
+        file.unsynced_bytes
+            .fetch_add(bytes_written, Ordering::Relaxed);
+        if bytes_written < bytes.len() {
+            return Err(StormDbError::ShortWrite {
+                block: block.clone(),
+                written: bytes_written,
+            });
+        }
+        self.stats.blocks_written.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the size of file in blocks, if the file exists, None otherwise. Doesn't create the file.
+    pub fn length(&self, file_name: &str) -> Result<Option<usize>> {
+        let is_open = self
+            .open_files
+            .lock()
+            .expect("open files mutex poisoned")
+            .contains_key(file_name);
+        if !is_open && !self.db_directory.join(file_name).exists() {
+            return Ok(None);
         }
+        let file = self.get_file(file_name)?;
+        self.block_count(&file).map(Some)
     }
 
-    /// Get length of file in blocks.
-    pub fn last_block_index(&self, file_name: &str) -> Option<usize> {
-        let file = self.open_files.get(file_name);
-        if let Some(file) = file {
-            let file_length = file
-                .metadata()
-                .expect("Error getting metadata for file.")
-                .len() as usize;
+    /// Returns the index of the last block in the file, None if the file is empty. The file is opened (or created) if it isn't already.
+    pub fn last_block_index(&self, file_name: &str) -> Result<Option<usize>> {
+        let file = self.get_file(file_name)?;
+        let block_count = self.block_count(&file)?;
 
-            if file_length == 0 {
-                None
-            } else {
-                Some(file_length / self.block_size())
-            }
+        if block_count == 0 {
+            Ok(None)
         } else {
-            None
+            Ok(Some(block_count - 1))
         }
     }
+
+    /// Shrinks (or grows) the file to exactly `block_count` blocks.
+    pub(crate) fn truncate(&self, file_name: &str, block_count: usize) -> Result<()> {
+        let file = self.get_file(file_name)?;
+        let _resize = file.lock_resize();
+        self.empty_double_write()?;
+        self.set_block_count(&file, block_count)
+    }
+
+    /// Renames `replacement` over `file_name` once it's synced, so after a crash the file is either all old or all new.
+    pub(crate) fn replace(&self, replacement: &str, file_name: &str) -> Result<()> {
+        // Same as truncate, nothing staged for the old file may come back after the rename.
+        self.empty_double_write()?;
+        self.get_file(replacement)?.file.sync_all()?;
+
+        // Held through the rename so nobody opens the old file in between.
+        let mut open_files = self.open_files.lock().expect("open files mutex poisoned");
+        fs::rename(
+            self.db_directory.join(replacement),
+            self.db_directory.join(file_name),
+        )?;
+        // The rename only sticks once the directory is synced.
+        File::open(&self.db_directory)?.sync_all()?;
+        open_files.remove(replacement);
+        open_files.remove(file_name);
+        Ok(())
+    }
+
+    // Caller holds the file's resize lock and has emptied the double-write file, otherwise a restore after a crash could bring
+    // back blocks past the new end.
+    fn set_block_count(&self, file: &OpenFile, block_count: usize) -> Result<()> {
+        file.file.set_len((block_count * self.slot_size()) as u64)?;
+        Ok(())
+    }
+
+    fn empty_double_write(&self) -> Result<()> {
+        match &self.double_write {
+            Some(double_write) => {
+                let mut double_write = double_write.lock().expect("double write mutex poisoned");
+                self.checkpoint_double_write(&mut double_write)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the writes to the file durable, as far as the sync policy asks for it.
+    pub fn sync(&self, file_name: &str) -> Result<()> {
+        let file = self
+            .open_files
+            .lock()
+            .expect("open files mutex poisoned")
+            .get(file_name)
+            .cloned();
+        // Never opened, so nothing was written to it either.
+        match file {
+            Some(file) => self.sync_file(&file),
+            None => Ok(()),
+        }
+    }
+
+    /// `sync` for every open file.
+    /// With double-write on, the double-write file is emptied if that left nothing unsynced.
+    pub fn sync_all(&self) -> Result<()> {
+        // Keeps writers out until we know whether the double-write file can go.
+        let mut double_write = self
+            .double_write
+            .as_ref()
+            .map(|double_write| double_write.lock().expect("double write mutex poisoned"));
+        let files: Vec<Arc<OpenFile>> = self
+            .open_files
+            .lock()
+            .expect("open files mutex poisoned")
+            .values()
+            .cloned()
+            .collect();
+        for file in &files {
+            self.sync_file(file)?;
+        }
+
+        if let Some(double_write) = double_write.as_mut() {
+            let all_synced = files
+                .iter()
+                .all(|file| file.unsynced_bytes.load(Ordering::Relaxed) == 0);
+            if all_synced && double_write.records() > 0 {
+                double_write.clear()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn sync_file(&self, file: &OpenFile) -> Result<()> {
+        let mut last_sync = file.last_sync.lock().expect("last sync mutex poisoned");
+        let unsynced_bytes = file.unsynced_bytes.load(Ordering::Relaxed);
+        let due = unsynced_bytes > 0
+            && match self.sync_policy {
+                SyncPolicy::None | SyncPolicy::DSync => false,
+                SyncPolicy::EveryFlush => true,
+                SyncPolicy::GroupCommit { interval, bytes } => {
+                    unsynced_bytes >= bytes || last_sync.elapsed() >= interval
+                }
+            };
+        if !due {
+            return Ok(());
+        }
+
+        // Taken before syncing, whatever gets written while the sync runs counts for the next one.
+        file.unsynced_bytes
+            .fetch_sub(unsynced_bytes, Ordering::Relaxed);
+        if let Err(error) = file.file.sync_data() {
+            file.unsynced_bytes
+                .fetch_add(unsynced_bytes, Ordering::Relaxed);
+            return Err(error.into());
+        }
+        *last_sync = Instant::now();
+        self.stats.syncs.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the I/O counters collected by this FileManager.
+    pub fn stats(&self) -> &IOStats {
+        &self.stats
+    }
 }
 
+pub struct FileManagerBuilder {
+    db_directory: PathBuf,
+    block_size: usize,
+    sync_policy: SyncPolicy,
+    checksums: bool,
+    double_write: bool,
+}
+
+impl FileManagerBuilder {
+    pub fn new(db_directory: PathBuf, block_size: usize) -> Self {
+        Self {
+            db_directory,
+            block_size,
+            sync_policy: SyncPolicy::default(),
+            checksums: false,
+            double_write: false,
+        }
+    }
+
+    pub fn with_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    /// Stores a checksum with every block and checks it on every read. Off by default. The setting is recorded in the superblock,
+    /// opening the directory with the other one is an error.
+    pub fn with_checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Protects against torn block writes by writing every block to a double-write file (and syncing it) before writing it in place.
+    /// Blocks a crash left there are put back when the FileManager is built. Costs an extra write and sync per block.
+    pub fn with_double_write(mut self, double_write: bool) -> Self {
+        self.double_write = double_write;
+        self
+    }
+
+    pub fn build(self) -> Result<FileManager> {
+        FileManager::open(
+            self.db_directory,
+            self.block_size,
+            self.sync_policy,
+            self.checksums,
+            self.double_write,
+        )
+    }
+}
+
+// Atomics so the counters can go up through a shared FileManager. Relaxed is plenty, nothing else is synchronized through them.
+#[derive(Default)]
 pub struct IOStats {
-    blocks_read: u64,
-    blocks_written: u64,
+    blocks_read: AtomicU64,
+    blocks_written: AtomicU64,
+    buffer_hits: AtomicU64,
+    buffer_misses: AtomicU64,
+    syncs: AtomicU64,
+    log_flush_requests: AtomicU64,
+    group_flushes: AtomicU64,
+    blocks_restored: AtomicU64,
 }
 
 // TODO: Implement something in the commit and transaction logics that would keep these values up-to-date.
 impl IOStats {
     pub fn new() -> Self {
-        IOStats {
-            blocks_read: 0,
-            blocks_written: 0,
-        }
+        Self::default()
     }
 
     pub fn blocks_read(&self) -> u64 {
-        self.blocks_read
+        self.blocks_read.load(Ordering::Relaxed)
     }
 
     pub fn blocks_written(&self) -> u64 {
-        self.blocks_written
+        self.blocks_written.load(Ordering::Relaxed)
+    }
+
+    pub fn set_blocks_read(&self, blocks_read: u64) {
+        self.blocks_read.store(blocks_read, Ordering::Relaxed);
+    }
+
+    pub fn set_blocks_write(&self, blocks_written: u64) {
+        self.blocks_written.store(blocks_written, Ordering::Relaxed);
+    }
+
+    /// Number of times a file was actually synced to disk.
+    pub fn syncs(&self) -> u64 {
+        self.syncs.load(Ordering::Relaxed)
+    }
+
+    /// Number of times a committer asked for a group flush of the log.
+    pub fn log_flush_requests(&self) -> u64 {
+        self.log_flush_requests.load(Ordering::Relaxed)
+    }
+
+    /// Number of group flushes that actually wrote and synced the log, each one covers one or more requests.
+    pub fn group_flushes(&self) -> u64 {
+        self.group_flushes.load(Ordering::Relaxed)
+    }
+
+    /// Average number of requests covered by one group flush, 0 if there weren't any.
+    pub fn requests_per_group_flush(&self) -> f64 {
+        let group_flushes = self.group_flushes();
+        if group_flushes == 0 {
+            0.0
+        } else {
+            self.log_flush_requests() as f64 / group_flushes as f64
+        }
+    }
+
+    /// Number of blocks put back from the double-write file when the FileManager was opened.
+    pub fn blocks_restored(&self) -> u64 {
+        self.blocks_restored.load(Ordering::Relaxed)
+    }
+
+    /// Number of pins that found their block already in the buffer pool.
+    pub fn buffer_hits(&self) -> u64 {
+        self.buffer_hits.load(Ordering::Relaxed)
     }
 
-    pub fn set_blocks_read(&mut self, blocks_read: u64) {
-        self.blocks_read = blocks_read;
+    /// Number of pins that had to read their block from disk.
+    pub fn buffer_misses(&self) -> u64 {
+        self.buffer_misses.load(Ordering::Relaxed)
     }
 
-    pub fn set_blocks_write(&mut self, blocks_written: u64) {
-        self.blocks_written = blocks_written;
+    /// Fraction of pins that were hits, 0 if nothing was pinned yet.
+    pub fn buffer_hit_ratio(&self) -> f64 {
+        let buffer_hits = self.buffer_hits();
+        let total = buffer_hits + self.buffer_misses();
+        if total == 0 {
+            0.0
+        } else {
+            buffer_hits as f64 / total as f64
+        }
+    }
+
+    pub(crate) fn record_buffer_hit(&self) {
+        self.buffer_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_buffer_miss(&self) {
+        self.buffer_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_log_flush_request(&self) {
+        self.log_flush_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_group_flush(&self) {
+        self.group_flushes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::superblock::SUPERBLOCK_FILE;
+    use crate::{BufferManager, LogManager, Transaction};
+    use rstest::rstest;
+    use std::thread;
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 64;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_storage_stack_is_thread_safe() {
+        assert_send_sync::<FileManager>();
+        assert_send_sync::<Mutex<LogManager>>();
+        assert_send_sync::<Mutex<BufferManager>>();
+        assert_send_sync::<Mutex<Transaction>>();
+    }
+
+    #[test]
+    fn test_concurrent_block_io() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+
+        let writers: Vec<_> = (0..4)
+            .map(|thread_number| {
+                let file_manager = file_manager.clone();
+                thread::spawn(move || {
+                    // Two threads per file, each one on its own blocks.
+                    let file_name = format!("test{}.tbl", thread_number % 2);
+                    let mut page = Page::builder()
+                        .with_block_size(BLOCK_SIZE)
+                        .with_buffer()
+                        .build();
+                    for block_number in (thread_number / 2..20).step_by(2) {
+                        let block = BlockMetadata::new(&file_name, block_number);
+                        page.write_int(0, (thread_number * 100 + block_number) as i32)
+                            .expect("failed to write int");
+                        file_manager
+                            .write(&block, &mut page)
+                            .expect("failed to write block");
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().expect("writer panicked");
+        }
+
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        for thread_number in 0..4 {
+            let file_name = format!("test{}.tbl", thread_number % 2);
+            for block_number in (thread_number / 2..20).step_by(2) {
+                file_manager
+                    .read(&BlockMetadata::new(&file_name, block_number), &mut page)
+                    .expect("failed to read block");
+                assert_eq!(
+                    page.read_int(0).expect("failed to read int"),
+                    (thread_number * 100 + block_number) as i32
+                );
+            }
+        }
+        assert_eq!(file_manager.stats().blocks_written(), 40);
+        assert_eq!(file_manager.stats().blocks_read(), 40);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_concurrent_appends_get_distinct_blocks() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+
+        let appenders: Vec<_> = (0..4)
+            .map(|_| {
+                let file_manager = file_manager.clone();
+                thread::spawn(move || {
+                    (0..10)
+                        .map(|_| {
+                            file_manager
+                                .append("test.tbl")
+                                .expect("failed to append")
+                                .block_number()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut block_numbers: Vec<usize> = appenders
+            .into_iter()
+            .flat_map(|appender| appender.join().expect("appender panicked"))
+            .collect();
+        block_numbers.sort();
+
+        assert_eq!(block_numbers, (0..40).collect::<Vec<_>>());
+        assert_eq!(
+            file_manager
+                .last_block_index("test.tbl")
+                .expect("failed to get last block"),
+            Some(39)
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_concurrent_reads_of_one_file() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        for block_number in 0..16 {
+            page.write_int(0, block_number as i32)
+                .expect("failed to write int");
+            file_manager
+                .write(&BlockMetadata::new("test.tbl", block_number), &mut page)
+                .expect("failed to write block");
+        }
+
+        // With a shared cursor a seek from one thread would land another thread's read on the wrong block.
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let file_manager = file_manager.clone();
+                thread::spawn(move || {
+                    let mut page = Page::builder()
+                        .with_block_size(BLOCK_SIZE)
+                        .with_buffer()
+                        .build();
+                    for _ in 0..50 {
+                        for block_number in 0..16 {
+                            file_manager
+                                .read(&BlockMetadata::new("test.tbl", block_number), &mut page)
+                                .expect("failed to read block");
+                            assert_eq!(
+                                page.read_int(0).expect("failed to read int"),
+                                block_number as i32
+                            );
+                        }
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().expect("reader panicked");
+        }
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_read_past_eof() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        let block = BlockMetadata::new("test.tbl", 3);
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        page.write_int(0, 42).expect("failed to write int");
+
+        assert_eq!(
+            file_manager.read(&block, &mut page),
+            Err(StormDbError::ShortRead {
+                block: block.clone(),
+                got: 0
+            })
+        );
+
+        // Only when asked for, and then it's zeros and not whatever the page held before.
+        file_manager
+            .read_zero_filled(&block, &mut page)
+            .expect("failed to read block");
+        assert_eq!(page.read_int(0).expect("failed to read int"), 0);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    #[case::strict(false)]
+    #[case::zero_filled(true)]
+    fn test_read_of_cut_off_block(#[case] zero_fill: bool) {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        file_manager.append("test.tbl").expect("failed to append");
+        let block = file_manager.append("test.tbl").expect("failed to append");
+        // Half of the last block goes missing, like a torn append would leave it.
+        fs::OpenOptions::new()
+            .write(true)
+            .open(tmp_dir.path().join("test.tbl"))
+            .expect("failed to open file")
+            .set_len((BLOCK_SIZE + BLOCK_SIZE / 2) as u64)
+            .expect("failed to cut file");
+
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        page.byte_buffer.fill(0xAB);
+        let result = if zero_fill {
+            file_manager.read_zero_filled(&block, &mut page)
+        } else {
+            file_manager.read(&block, &mut page)
+        };
+        assert_eq!(
+            result,
+            Err(StormDbError::ShortRead {
+                block,
+                got: BLOCK_SIZE / 2
+            })
+        );
+        // What was in the page before is left alone.
+        assert!(page.bytes().iter().all(|byte| *byte == 0xAB));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    fn write_blocks(file_manager: &FileManager, file_name: &str, count: usize) {
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        for block_number in 0..count {
+            file_manager
+                .write(&BlockMetadata::new(file_name, block_number), &mut page)
+                .expect("failed to write block");
+        }
+    }
+
+    #[rstest]
+    #[case::none(SyncPolicy::None, 0)]
+    #[case::every_flush(SyncPolicy::EveryFlush, 3)]
+    #[case::group_commit_by_bytes(SyncPolicy::GroupCommit { interval: Duration::from_secs(3600), bytes: 2 * BLOCK_SIZE }, 1)]
+    #[case::group_commit_by_time(SyncPolicy::GroupCommit { interval: Duration::ZERO, bytes: usize::MAX }, 3)]
+    #[case::dsync(SyncPolicy::DSync, 0)]
+    fn test_sync_policy(#[case] sync_policy: SyncPolicy, #[case] expected_syncs: u64) {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager =
+            FileManager::with_sync_policy(tmp_dir.path().to_owned(), BLOCK_SIZE, sync_policy)
+                .expect("failed to create file manager");
+
+        // One block per flush, with bytes as the trigger only the second flush has enough to go to disk.
+        for _ in 0..3 {
+            write_blocks(&file_manager, "test.tbl", 1);
+            file_manager.sync("test.tbl").expect("failed to sync");
+        }
+        // Nothing new since, so nothing to do.
+        if sync_policy == SyncPolicy::EveryFlush {
+            file_manager.sync("test.tbl").expect("failed to sync");
+        }
+        assert_eq!(file_manager.stats().syncs(), expected_syncs);
+
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        file_manager
+            .read(&BlockMetadata::new("test.tbl", 0), &mut page)
+            .expect("failed to read block");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_sync_all_only_syncs_written_files() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+
+        write_blocks(&file_manager, "first.tbl", 2);
+        write_blocks(&file_manager, "second.tbl", 1);
+        file_manager
+            .last_block_index("third.tbl")
+            .expect("failed to open file");
+        file_manager.sync_all().expect("failed to sync");
+        assert_eq!(file_manager.stats().syncs(), 2);
+
+        // Never opened, nothing to sync and it doesn't get created either.
+        file_manager.sync("fourth.tbl").expect("failed to sync");
+        assert!(!tmp_dir.path().join("fourth.tbl").exists());
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    fn checksummed_file_manager(tmp_dir: &TempDir) -> FileManager {
+        FileManager::builder(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .with_checksums(true)
+            .build()
+            .expect("failed to create file manager")
+    }
+
+    #[test]
+    fn test_checksummed_blocks_round_trip() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = checksummed_file_manager(&tmp_dir);
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        page.write_int(0, 42).expect("failed to write int");
+        // Leaves blocks 0 and 1 as a hole.
+        file_manager
+            .write(&BlockMetadata::new("test.tbl", 2), &mut page)
+            .expect("failed to write block");
+
+        let file_length = fs::metadata(tmp_dir.path().join("test.tbl"))
+            .expect("failed to get metadata")
+            .len() as usize;
+        assert_eq!(file_length, 3 * (BLOCK_SIZE + CHECKSUM_SIZE));
+        assert_eq!(
+            file_manager
+                .last_block_index("test.tbl")
+                .expect("failed to get last block"),
+            Some(2)
+        );
+        assert_eq!(
+            file_manager
+                .length("test.tbl")
+                .expect("failed to get length"),
+            Some(3)
+        );
+        assert_eq!(
+            file_manager
+                .length("missing.tbl")
+                .expect("failed to get length"),
+            None
+        );
+        assert!(!tmp_dir.path().join("missing.tbl").exists());
+        assert_eq!(
+            file_manager.append("test.tbl").expect("failed to append"),
+            BlockMetadata::new("test.tbl", 3)
+        );
+
+        let mut read_page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        file_manager
+            .read(&BlockMetadata::new("test.tbl", 2), &mut read_page)
+            .expect("failed to read block");
+        assert_eq!(read_page.read_int(0).expect("failed to read int"), 42);
+        file_manager
+            .read(&BlockMetadata::new("test.tbl", 0), &mut read_page)
+            .expect("failed to read hole");
+        assert!(read_page.bytes().iter().all(|byte| *byte == 0));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    #[case::flipped_data_byte(3)]
+    #[case::flipped_checksum_byte(BLOCK_SIZE + 1)]
+    fn test_checksum_mismatch_is_corrupt(#[case] flipped_offset: usize) {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = checksummed_file_manager(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 1);
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+        page.write_string(0, "bit rot".to_string())
+            .expect("failed to write string");
+        file_manager
+            .write(&block, &mut page)
+            .expect("failed to write block");
+
+        // Flip one bit of the second block behind the FileManager's back.
+        let path = tmp_dir.path().join("test.tbl");
+        let mut bytes = fs::read(&path).expect("failed to read file");
+        bytes[BLOCK_SIZE + CHECKSUM_SIZE + flipped_offset] ^= 0x10;
+        fs::write(&path, bytes).expect("failed to write file");
+
+        let result = file_manager.read(&block, &mut page);
+        assert!(
+            matches!(&result, Err(StormDbError::Corrupt { block: Some(corrupt), .. }) if *corrupt == block),
+            "unexpected result {:?}",
+            result
+        );
+        // The untouched block next to it still reads fine.
+        file_manager
+            .read(&BlockMetadata::new("test.tbl", 0), &mut page)
+            .expect("failed to read block");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_double_write_restores_torn_block() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let open = || {
+            FileManager::builder(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .with_checksums(true)
+                .with_double_write(true)
+                .build()
+                .expect("failed to create file manager")
+        };
+        let double_write_length = || {
+            fs::metadata(tmp_dir.path().join(DOUBLE_WRITE_FILE))
+                .expect("failed to get metadata")
+                .len()
+        };
+        let block = BlockMetadata::new("test.tbl", 0);
+        let mut page = Page::builder()
+            .with_block_size(BLOCK_SIZE)
+            .with_buffer()
+            .build();
+
+        let file_manager = open();
+        page.write_string(0, "old".to_string())
+            .expect("failed to write string");
+        file_manager
+            .write(&block, &mut page)
+            .expect("failed to write block");
+        file_manager.sync_all().expect("failed to sync");
+        assert_eq!(double_write_length(), 0);
+
+        page.write_string(0, "new".to_string())
+            .expect("failed to write string");
+        file_manager
+            .write(&block, &mut page)
+            .expect("failed to write block");
+        assert!(double_write_length() > 0);
+        drop(file_manager);
+
+        // Crash halfway through the in-place write: the first half is new, the rest is garbage.
+        let path = tmp_dir.path().join("test.tbl");
+        let mut bytes = fs::read(&path).expect("failed to read file");
+        bytes[BLOCK_SIZE / 2..].fill(0xAB);
+        fs::write(&path, bytes).expect("failed to write file");
+
+        let file_manager = open();
+        assert_eq!(file_manager.stats().blocks_restored(), 1);
+        assert_eq!(double_write_length(), 0);
+        file_manager
+            .read(&block, &mut page)
+            .expect("failed to read block");
+        assert_eq!(page.read_string(0).expect("failed to read string"), "new");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_double_write_stays_bounded() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let file_manager = FileManager::builder(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .with_sync_policy(SyncPolicy::None)
+            .with_double_write(true)
+            .build()
+            .expect("failed to create file manager");
+
+        // Nobody ever syncs, so it's the capacity that empties the double-write file.
+        write_blocks(&file_manager, "test.tbl", DOUBLE_WRITE_CAPACITY + 1);
+        let records = file_manager
+            .double_write
+            .as_ref()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .records();
+        assert_eq!(records, 1);
+        assert_eq!(file_manager.stats().syncs(), 1);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    #[case::same_settings(BLOCK_SIZE, false, true)]
+    #[case::other_block_size(2 * BLOCK_SIZE, false, false)]
+    #[case::checksums_turned_on(BLOCK_SIZE, true, false)]
+    fn test_reopen_checks_superblock(
+        #[case] block_size: usize,
+        #[case] checksums: bool,
+        #[case] compatible: bool,
+    ) {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        let db_directory = tmp_dir.path().join("db");
+        let file_manager = FileManager::new(db_directory.clone(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        assert!(file_manager.is_new());
+        let superblock = file_manager.superblock().clone();
+        assert_eq!(superblock.block_size(), BLOCK_SIZE);
+        assert!(!superblock.checksums());
+        drop(file_manager);
+
+        let reopened = FileManager::builder(db_directory, block_size)
+            .with_checksums(checksums)
+            .build();
+        if compatible {
+            let reopened = reopened.expect("failed to reopen");
+            assert!(!reopened.is_new());
+            assert_eq!(reopened.superblock(), &superblock);
+        } else {
+            assert!(matches!(
+                reopened,
+                Err(StormDbError::IncompatibleDatabase(_))
+            ));
+        }
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_files_without_superblock_are_rejected() {
+        let tmp_dir = TempDir::new("test_file_manager").expect("failed to create temp dir");
+        // Already there, but empty. Same as a new one.
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        assert!(!file_manager.is_new());
+        file_manager.append("table.tbl").expect("failed to append");
+        drop(file_manager);
+
+        fs::remove_file(tmp_dir.path().join(SUPERBLOCK_FILE)).expect("failed to remove superblock");
+        assert!(matches!(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE),
+            Err(StormDbError::IncompatibleDatabase(_))
+        ));
+        // Nothing got stamped on it either.
+        assert!(!tmp_dir.path().join(SUPERBLOCK_FILE).exists());
+
+        tmp_dir.close().expect("failed to remove temp dir");
     }
 }
@@ -0,0 +1,370 @@
+/*
+Free space management. Every data file gets a bitmap file next to it (`<file>.free`), bit n set means block n of the data file is free.
+Keeping it out of the data file means block 0 is still block 0 for everybody that was already using these files.
+Bitmap blocks go through the normal block read/write path, so they get checksums and double-write like everything else.
+
+allocate hands out the lowest free block and only grows the file when there isn't one. Nothing here is logged, the bitmap is synced
+after every change whatever the sync policy is, so a crash can at worst leak a block, never hand the same one out twice.
+Whoever frees a block has to make sure nothing has it pinned anymore, the FileManager doesn't know about buffers.
+*/
+
+use super::FileManager;
+use crate::{BlockMetadata, StormDbError, error::Result, page::Page};
+
+const FREE_SPACE_SUFFIX: &str = ".free";
+
+impl FileManager {
+    /// Returns a zeroed block of the file, reusing a freed one if there is any and appending a new one otherwise.
+    pub fn allocate(&self, file_name: &str) -> Result<BlockMetadata> {
+        let file = self.get_file(file_name)?;
+        let _resize = file.lock_resize();
+        let block_count = self.block_count(&file)?;
+        let mut bitmap = self.new_bitmap_page();
+
+        for bitmap_block in self.bitmap_blocks(file_name, block_count) {
+            self.read_zero_filled(&bitmap_block, &mut bitmap)?;
+            let Some(bit) = bitmap.bytes().iter().enumerate().find_map(|(index, byte)| {
+                (*byte != 0).then(|| index * 8 + byte.trailing_zeros() as usize)
+            }) else {
+                continue;
+            };
+
+            let block = BlockMetadata::new(
+                file_name,
+                bitmap_block.block_number() * self.bits_per_block() + bit,
+            );
+            self.set_free_bit(&mut bitmap, &bitmap_block, block.block_number(), false)?;
+            self.write_block(&file, &block, &vec![0u8; self.block_size])?;
+            return Ok(block);
+        }
+        self.append_locked(&file, file_name)
+    }
+
+    /// Gives the block back so `allocate` can hand it out again. Freeing a block that is past the end of the file or already free is
+    /// a `StormDbError::InvalidFree`.
+    pub fn free(&self, block: &BlockMetadata) -> Result<()> {
+        let file_name = block.file_name();
+        let file = self.get_file(&file_name)?;
+        let _resize = file.lock_resize();
+        if block.block_number() >= self.block_count(&file)? {
+            return Err(StormDbError::InvalidFree(format!(
+                "Can't free {}, it's past the end of the file",
+                block
+            )));
+        }
+
+        let bitmap_block = self.bitmap_block(block);
+        let mut bitmap = self.new_bitmap_page();
+        self.read_zero_filled(&bitmap_block, &mut bitmap)?;
+        if self.is_free(&bitmap, block.block_number()) {
+            return Err(StormDbError::InvalidFree(format!(
+                "Can't free {}, it's already free",
+                block
+            )));
+        }
+        self.set_free_bit(&mut bitmap, &bitmap_block, block.block_number(), true)
+    }
+
+    /// Returns whether the block is marked free.
+    pub fn is_block_free(&self, block: &BlockMetadata) -> Result<bool> {
+        let bitmap_block = self.bitmap_block(block);
+        let mut bitmap = self.new_bitmap_page();
+        self.read_zero_filled(&bitmap_block, &mut bitmap)?;
+        Ok(self.is_free(&bitmap, block.block_number()))
+    }
+
+    /// Cuts the free blocks off the end of the file and returns how many went away.
+    pub fn truncate_free_blocks(&self, file_name: &str) -> Result<usize> {
+        let file = self.get_file(file_name)?;
+        // Resize lock first, otherwise a write staged between emptying the double-write file and taking the lock could be restored
+        // past the new end after a crash.
+        let _resize = file.lock_resize();
+        self.empty_double_write()?;
+        let block_count = self.block_count(&file)?;
+
+        let mut new_block_count = block_count;
+        let mut bitmap = self.new_bitmap_page();
+        let mut loaded = None;
+        while new_block_count > 0 {
+            let block = BlockMetadata::new(file_name, new_block_count - 1);
+            let bitmap_block = self.bitmap_block(&block);
+            if loaded.as_ref() != Some(&bitmap_block) {
+                self.read_zero_filled(&bitmap_block, &mut bitmap)?;
+                loaded = Some(bitmap_block);
+            }
+            if !self.is_free(&bitmap, block.block_number()) {
+                break;
+            }
+            new_block_count -= 1;
+        }
+        if new_block_count == block_count {
+            return Ok(0);
+        }
+
+        // Blocks past the end aren't free, they don't exist. Bits go first so a crash in between only leaks the tail.
+        self.clear_free_bits(file_name, new_block_count, block_count)?;
+        self.set_block_count(&file, new_block_count)?;
+        Ok(block_count - new_block_count)
+    }
+
+    fn clear_free_bits(&self, file_name: &str, from: usize, to: usize) -> Result<()> {
+        let mut bitmap = self.new_bitmap_page();
+        let mut block_number = from;
+        while block_number < to {
+            let bitmap_block = self.bitmap_block(&BlockMetadata::new(file_name, block_number));
+            self.read_zero_filled(&bitmap_block, &mut bitmap)?;
+            let bitmap_end = ((bitmap_block.block_number() + 1) * self.bits_per_block()).min(to);
+            for number in block_number..bitmap_end {
+                self.flip_bit(&mut bitmap, number, false);
+            }
+            self.write_bitmap(&bitmap_block, &bitmap)?;
+            block_number = bitmap_end;
+        }
+        Ok(())
+    }
+
+    fn bits_per_block(&self) -> usize {
+        self.block_size * 8
+    }
+
+    fn new_bitmap_page(&self) -> Page {
+        Page::builder()
+            .with_block_size(self.block_size)
+            .with_buffer()
+            .build()
+    }
+
+    // The bitmap blocks that cover the first block_count blocks of the file.
+    fn bitmap_blocks(
+        &self,
+        file_name: &str,
+        block_count: usize,
+    ) -> impl Iterator<Item = BlockMetadata> {
+        let bitmap_file = format!("{}{}", file_name, FREE_SPACE_SUFFIX);
+        (0..block_count.div_ceil(self.bits_per_block()))
+            .map(move |bitmap_block| BlockMetadata::new(&bitmap_file, bitmap_block))
+    }
+
+    // Returns the bitmap block that holds the block's bit.
+    fn bitmap_block(&self, block: &BlockMetadata) -> BlockMetadata {
+        let bitmap_file = format!("{}{}", block.file_name(), FREE_SPACE_SUFFIX);
+        BlockMetadata::new(&bitmap_file, block.block_number() / self.bits_per_block())
+    }
+
+    fn is_free(&self, bitmap: &Page, block_number: usize) -> bool {
+        let bit = block_number % self.bits_per_block();
+        bitmap.bytes()[bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    fn flip_bit(&self, bitmap: &mut Page, block_number: usize, free: bool) {
+        let bit = block_number % self.bits_per_block();
+        let byte = &mut bitmap.byte_buffer[bit / 8];
+        if free {
+            *byte |= 1 << (bit % 8);
+        } else {
+            *byte &= !(1 << (bit % 8));
+        }
+    }
+
+    fn set_free_bit(
+        &self,
+        bitmap: &mut Page,
+        bitmap_block: &BlockMetadata,
+        block_number: usize,
+        free: bool,
+    ) -> Result<()> {
+        self.flip_bit(bitmap, block_number, free);
+        self.write_bitmap(bitmap_block, bitmap)
+    }
+
+    fn write_bitmap(&self, bitmap_block: &BlockMetadata, bitmap: &Page) -> Result<()> {
+        let bitmap_file = self.get_file(&bitmap_block.file_name())?;
+        self.write_block(&bitmap_file, bitmap_block, bitmap.bytes())?;
+        // Not self.sync, the sync policy doesn't get a say here. An unsynced bit could hand out a block twice after a crash.
+        self.force_sync(&bitmap_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_manager::SyncPolicy;
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 64;
+
+    #[test]
+    fn test_allocate_reuses_freed_blocks() {
+        let tmp_dir = TempDir::new("test_free_space").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        let blocks: Vec<BlockMetadata> = (0..4)
+            .map(|_| {
+                file_manager
+                    .allocate("test.tbl")
+                    .expect("failed to allocate")
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            (0..4)
+                .map(|number| BlockMetadata::new("test.tbl", number))
+                .collect::<Vec<_>>()
+        );
+
+        let mut page = file_manager.new_bitmap_page();
+        page.write_int(0, 7).expect("failed to write int");
+        file_manager
+            .write(&blocks[2], &mut page)
+            .expect("failed to write block");
+        file_manager.free(&blocks[2]).expect("failed to free");
+        file_manager.free(&blocks[1]).expect("failed to free");
+        assert!(
+            file_manager
+                .is_block_free(&blocks[1])
+                .expect("failed to check")
+        );
+        assert!(
+            !file_manager
+                .is_block_free(&blocks[0])
+                .expect("failed to check")
+        );
+
+        // Lowest free block first, and it comes back zeroed.
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            blocks[1]
+        );
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            blocks[2]
+        );
+        file_manager
+            .read(&blocks[2], &mut page)
+            .expect("failed to read block");
+        assert_eq!(page.read_int(0).expect("failed to read int"), 0);
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            BlockMetadata::new("test.tbl", 4)
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_invalid_free() {
+        let tmp_dir = TempDir::new("test_free_space").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        let block = file_manager
+            .allocate("test.tbl")
+            .expect("failed to allocate");
+
+        file_manager.free(&block).expect("failed to free");
+        assert!(matches!(
+            file_manager.free(&block),
+            Err(StormDbError::InvalidFree(_))
+        ));
+        assert!(matches!(
+            file_manager.free(&BlockMetadata::new("test.tbl", 1)),
+            Err(StormDbError::InvalidFree(_))
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_bitmap_synced_without_sync_policy() {
+        let tmp_dir = TempDir::new("test_free_space").expect("failed to create temp dir");
+        let file_manager =
+            FileManager::with_sync_policy(tmp_dir.path().to_owned(), BLOCK_SIZE, SyncPolicy::None)
+                .expect("failed to create file manager");
+        let block = file_manager.append("test.tbl").expect("failed to append");
+        assert_eq!(file_manager.stats().syncs(), 0);
+
+        // Only the bitmap is synced, the data file is left to the policy.
+        file_manager.free(&block).expect("failed to free");
+        assert_eq!(file_manager.stats().syncs(), 1);
+        file_manager
+            .allocate("test.tbl")
+            .expect("failed to allocate");
+        assert_eq!(file_manager.stats().syncs(), 2);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_truncate_free_blocks() {
+        let tmp_dir = TempDir::new("test_free_space").expect("failed to create temp dir");
+        let file_manager = FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+            .expect("failed to create file manager");
+        // Enough blocks to need a second bitmap block.
+        let block_count = BLOCK_SIZE * 8 + 3;
+        for _ in 0..block_count {
+            file_manager.append("test.tbl").expect("failed to append");
+        }
+        for block_number in [
+            1,
+            block_count - 5,
+            block_count - 3,
+            block_count - 2,
+            block_count - 1,
+        ] {
+            file_manager
+                .free(&BlockMetadata::new("test.tbl", block_number))
+                .expect("failed to free");
+        }
+
+        // Stops at the first block that's in use, block_count - 5 and 1 stay free.
+        assert_eq!(
+            file_manager
+                .truncate_free_blocks("test.tbl")
+                .expect("failed to truncate"),
+            3
+        );
+        assert_eq!(
+            file_manager
+                .last_block_index("test.tbl")
+                .expect("failed to get last block"),
+            Some(block_count - 4)
+        );
+        assert_eq!(
+            file_manager
+                .truncate_free_blocks("test.tbl")
+                .expect("failed to truncate"),
+            0
+        );
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            BlockMetadata::new("test.tbl", 1)
+        );
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            BlockMetadata::new("test.tbl", block_count - 5)
+        );
+        // The cut off blocks are gone, not free.
+        assert_eq!(
+            file_manager
+                .allocate("test.tbl")
+                .expect("failed to allocate"),
+            BlockMetadata::new("test.tbl", block_count - 3)
+        );
+        assert!(
+            !file_manager
+                .is_block_free(&BlockMetadata::new("test.tbl", block_count - 2))
+                .expect("failed to check")
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
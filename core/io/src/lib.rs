@@ -18,16 +18,37 @@ The api as per the book will be:
 // TODO: I think everything should be pub create and not just pub directly.
 
 mod block_metadata;
+mod buffer_manager;
+mod checksum;
+mod double_write;
 mod error;
 mod file_manager;
 mod log_manager;
+mod metadata;
 mod page;
+mod record;
+mod replacement_policy;
+mod superblock;
+mod transaction;
 pub mod varint;
 
 pub use block_metadata::BlockMetadata;
+pub use buffer_manager::{Buffer, BufferManager};
 pub use error::{Result, StormDbError};
-pub use file_manager::FileManager;
+pub use file_manager::{FileManager, FileManagerBuilder, IOStats, SyncPolicy};
+pub use log_manager::{LogIterator, LogManager, LogManagerBuilder};
+pub use metadata::{IndexInfo, MAX_NAME, MAX_VIEW_DEF, MetadataManager, StatInfo};
 pub use page::{Page, PageBuilder};
+pub use record::{FieldInfo, FieldType, Layout, RecordPage, Rid, Schema, TableScan};
+pub use replacement_policy::{
+    ClockPolicy, LruKPolicy, LruPolicy, NaivePolicy, ReplacementPolicy, ReplacementStrategy,
+    TwoQueuePolicy,
+};
+pub use superblock::{FORMAT_VERSION, Superblock};
+pub use transaction::{
+    DeadlockPolicy, LockTable, LogRecord, RecoveryMode, Transaction, TransactionBuilder,
+    VersionStore, VictimSelection, checkpoint, truncate_log,
+};
 pub use varint::{
     get_varint_len, get_varint_reversed, read_varint, read_varint_reversed, write_varint,
     write_varint_sqlite,
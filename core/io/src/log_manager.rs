@@ -8,52 +8,55 @@ Log Manager API:
 #![allow(dead_code)]
 
 use std::{
-    cell::{RefCell, RefMut},
-    rc::Rc,
+    collections::BTreeSet,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
 };
 
 use crate::{
     BlockMetadata, FileManager, Page, PageBuilder, StormDbError, error::Result, get_varint_len,
 };
 
+// Every log block starts with a header: the boundary (offset of the latest record in the block) followed by the lsn of the latest record
+// appended up to and including this block. The lsn is what lets a reopened log carry on numbering where it left off.
+const LSN_OFFSET: usize = size_of::<u32>();
+const HEADER_SIZE: usize = 2 * size_of::<u32>();
+
 pub struct LogIterator {
-    file_manager: Rc<RefCell<FileManager>>,
+    file_manager: Arc<FileManager>,
     log_page: Page,
     block_id: BlockMetadata,
     current_offset: u32,
+    // Set once a read failed, the error is handed out once and the iterator stops there.
+    failed: bool,
 }
 
 // This one reads form the start of the last page and keeps going back.
 impl LogIterator {
-    pub fn new(file_manager: Rc<RefCell<FileManager>>, block: &BlockMetadata) -> Self {
-        let file_manager_borrowed = file_manager.borrow_mut();
-        let bytes = vec![0; file_manager_borrowed.block_size()];
+    pub fn new(file_manager: Arc<FileManager>, block: &BlockMetadata) -> Result<Self> {
+        let bytes = vec![0; file_manager.block_size()];
         let mut page = Page::builder()
-            .with_block_size(file_manager_borrowed.block_size())
+            .with_block_size(file_manager.block_size())
             .with_log_buffer(bytes)
             .build();
 
-        let boundary = Self::move_to_block(file_manager_borrowed, block, &mut page);
+        let boundary = Self::move_to_block(&file_manager, block, &mut page)?;
 
-        Self {
+        Ok(Self {
             file_manager,
             log_page: page,
             block_id: block.clone(),
             current_offset: boundary,
-        }
+            failed: false,
+        })
     }
 
     fn move_to_block(
-        mut file_manager: RefMut<FileManager>,
+        file_manager: &FileManager,
         block: &BlockMetadata,
         log_page: &mut Page,
-    ) -> u32 {
-        file_manager
-            .read(block, log_page)
-            .expect("Error reading block to log page.");
-        log_page
-            .read_u32(0)
-            .expect("Error reading boundary from log page.")
+    ) -> Result<u32> {
+        file_manager.read(block, log_page)?;
+        log_page.read_u32(0)
     }
 }
 
@@ -74,14 +77,10 @@ impl Iterator for LogIterator2 {
             self.block_id =
                 BlockMetadata::new(&self.block_id.file_name(), self.block_id.block_number() - 1);
 
-            Self::move_to_block(
-                self.file_manager.borrow_mut(),
-                &self.block_id,
-                &mut self.log_page,
-            );
+            Self::move_to_block(&self.file_manager, &self.block_id, &mut self.log_page);
 
             // Set current_offset to the end of the block (block_size - 1)
-            self.current_offset = (self.file_manager.borrow().block_size() - 1) as u32;
+            self.current_offset = (self.file_manager.block_size() - 1) as u32;
         }
 
         // Read the record from the end backwards
@@ -100,14 +99,30 @@ impl Iterator for LogIterator2 {
 }
 
 impl Iterator for LogIterator {
-    type Item = Vec<u8>;
+    type Item = Result<Vec<u8>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl LogIterator {
+    fn next_record(&mut self) -> Result<Option<Vec<u8>>> {
         // If the current block does not have any more records we'd have to check if there is a block before it.
-        if self.current_offset >= self.file_manager.borrow_mut().block_size() as u32 {
+        // A loop and not an if, the previous block can be empty too (block 0 after the log gets truncated for one).
+        while self.current_offset >= self.file_manager.block_size() as u32 {
             // If we're on the last block and we're out of records then we're done for good.
             if self.block_id.block_number() == 0 {
-                return None;
+                return Ok(None);
             } else {
                 // Otherwise load the previous block into the page. And ensure that the current_offset is also set correctly.
                 self.block_id = BlockMetadata::new(
@@ -115,32 +130,26 @@ impl Iterator for LogIterator {
                     self.block_id.block_number() - 1,
                 );
 
-                Self::move_to_block(
-                    self.file_manager.borrow_mut(),
-                    &self.block_id,
-                    &mut self.log_page,
-                );
-
-                self.current_offset = self
-                    .log_page
-                    .read_u32(0)
-                    .expect("Error reading boundary for newly loaded block.");
+                self.current_offset =
+                    Self::move_to_block(&self.file_manager, &self.block_id, &mut self.log_page)?;
             }
         }
 
-        let record_bytes = self
-            .log_page
-            .read_bytes(self.current_offset as usize)
-            .expect("Error reading record bytes for iterator");
+        let record_bytes = self.log_page.read_bytes(self.current_offset as usize)?;
 
         self.current_offset +=
             record_bytes.len() as u32 + get_varint_len(record_bytes.len() as u64) as u32;
-        Some(record_bytes)
+        Ok(Some(record_bytes))
+    }
+
+    /// Returns the block the last record returned by `next` was read from.
+    pub fn block(&self) -> &BlockMetadata {
+        &self.block_id
     }
 }
 
 pub struct LogIterator2 {
-    file_manager: Rc<RefCell<FileManager>>,
+    file_manager: Arc<FileManager>,
     log_page: Page,
     block_id: BlockMetadata,
     current_offset: u32,
@@ -148,15 +157,14 @@ pub struct LogIterator2 {
 
 // This one reads form the end of the last page and keeps going back.
 impl LogIterator2 {
-    pub fn new(file_manager: Rc<RefCell<FileManager>>, block: &BlockMetadata) -> Self {
-        let file_manager_borrowed = file_manager.borrow_mut();
-        let bytes = vec![0; file_manager_borrowed.block_size()];
+    pub fn new(file_manager: Arc<FileManager>, block: &BlockMetadata) -> Self {
+        let bytes = vec![0; file_manager.block_size()];
         let mut page = Page::builder()
-            .with_block_size(file_manager_borrowed.block_size())
+            .with_block_size(file_manager.block_size())
             .with_log_buffer(bytes)
             .build();
 
-        let boundary = Self::move_to_block(file_manager_borrowed, block, &mut page);
+        let boundary = Self::move_to_block(&file_manager, block, &mut page);
 
         Self {
             file_manager,
@@ -167,7 +175,7 @@ impl LogIterator2 {
     }
 
     fn move_to_block(
-        mut file_manager: RefMut<FileManager>,
+        file_manager: &FileManager,
         block: &BlockMetadata,
         log_page: &mut Page,
     ) -> u32 {
@@ -180,40 +188,135 @@ impl LogIterator2 {
     }
 }
 
+// Group commit: committers that want the log on disk at the same time share one write and one sync. Whoever finds nobody flushing
+// becomes the leader, writes the log page and syncs it without holding the log manager lock (so others can keep appending), and
+// then wakes everyone whose lsn it covered. The ones that came in too late see their lsn isn't durable yet and one of them leads next.
+struct GroupCommit {
+    state: Mutex<GroupCommitState>,
+    flushed: Condvar,
+}
+
+struct GroupCommitState {
+    leader_active: bool,
+    // Highest lsn written and synced by a group flush.
+    durable_lsn: u32,
+}
+
+impl GroupCommit {
+    fn lock_state(&self) -> MutexGuard<'_, GroupCommitState> {
+        self.state.lock().expect("group commit mutex poisoned")
+    }
+}
+
 pub struct LogManager {
     log_file: String,
-    file_manager: Rc<RefCell<FileManager>>,
+    file_manager: Arc<FileManager>,
     log_page: Page,
     current_block: BlockMetadata,
     // I think u32 should be more than enough for the lsn numbers for my purposes. We'll see if that needs to change down the line.
     latest_lsn: u32,
     latest_flushed_lsn: u32,
+    // Transactions that wrote a START record but no COMMIT/ROLLBACK yet. Non-quiescent checkpoints need to list them.
+    active_transactions: BTreeSet<u32>,
+    group_commit: Arc<GroupCommit>,
 }
 
 impl LogManager {
-    pub fn builder(log_file: String, file_manager: Rc<RefCell<FileManager>>) -> LogManagerBuilder {
+    pub fn builder(log_file: String, file_manager: Arc<FileManager>) -> LogManagerBuilder {
         LogManagerBuilder::new(log_file, file_manager)
     }
 
-    /// Flushes the values in the log_page to the disk. Only does this if the latest flushed record is smaller than the latest written record.
-    pub fn flush(&mut self) {
-        if self.latest_lsn >= self.latest_flushed_lsn {
-            self.flush_to_file()
+    /// Same guarantee as `flush`, but meant for committers on different threads: instead of each one writing and syncing the log on
+    /// its own they wait for a shared flush. Takes the mutex instead of &mut self since the lock isn't held while waiting or syncing.
+    pub fn group_flush(log_manager: &Mutex<LogManager>, lsn: u32) -> Result<()> {
+        let (group_commit, file_manager, lsn) = {
+            let log_manager = log_manager.lock().expect("log manager mutex poisoned");
+            (
+                log_manager.group_commit.clone(),
+                log_manager.file_manager.clone(),
+                // Nothing past the latest record can ever become durable, waiting for it would never end.
+                lsn.min(log_manager.latest_lsn),
+            )
+        };
+        file_manager.stats().record_log_flush_request();
+
+        let mut state = group_commit.lock_state();
+        while state.durable_lsn < lsn {
+            if state.leader_active {
+                state = group_commit
+                    .flushed
+                    .wait(state)
+                    .expect("group commit mutex poisoned");
+            } else {
+                state.leader_active = true;
+                drop(state);
+                Self::lead_group_flush(log_manager, &group_commit)?;
+                state = group_commit.lock_state();
+            }
+        }
+        Ok(())
+    }
+
+    // Leader's side of a group flush, the caller has already set leader_active.
+    fn lead_group_flush(log_manager: &Mutex<LogManager>, group_commit: &GroupCommit) -> Result<()> {
+        let result = (|| {
+            let (file_manager, log_file, written_lsn) = {
+                let mut log_manager = log_manager.lock().expect("log manager mutex poisoned");
+                // A plain flush might have gotten there first.
+                if log_manager.latest_flushed_lsn < log_manager.latest_lsn {
+                    let current_block = log_manager.current_block.clone();
+                    let log_manager = &mut *log_manager;
+                    log_manager
+                        .file_manager
+                        .write(&current_block, &mut log_manager.log_page)?;
+                }
+                (
+                    log_manager.file_manager.clone(),
+                    log_manager.log_file.clone(),
+                    log_manager.latest_lsn,
+                )
+            };
+
+            file_manager.sync(&log_file)?;
+            file_manager.stats().record_group_flush();
+
+            let mut log_manager = log_manager.lock().expect("log manager mutex poisoned");
+            log_manager.latest_flushed_lsn = log_manager.latest_flushed_lsn.max(written_lsn);
+            Ok(written_lsn)
+        })();
+
+        let mut state = group_commit.lock_state();
+        state.leader_active = false;
+        if let Ok(written_lsn) = result {
+            state.durable_lsn = state.durable_lsn.max(written_lsn);
         }
+        // Even when it failed, somebody else has to take over.
+        group_commit.flushed.notify_all();
+        result.map(|_| ())
+    }
+
+    /// Makes sure the record with the given lsn is on disk, and synced if the file manager's sync policy says so. The page is only
+    /// written if that record hasn't been flushed already.
+    pub fn flush(&mut self, lsn: u32) -> Result<()> {
+        if lsn >= self.latest_flushed_lsn {
+            self.flush_to_file()?;
+        }
+        Ok(())
     }
 
     // Appends records from right to left. Boundary is where the latest record should start from. The first 4 bytes will always be a u32 representing the boundary.
     // Block would look something like this:                                 boundary ..................(boundary points here)record1.
     // After one more record insertino Block would look something like this: boundary2......(now boundary points here)record2 record1.
     pub fn append(&mut self, record: Vec<u8>) -> Result<u32> {
+        self.check_fits(record.len())?;
         let record_length = record.len();
         // Since bytes are added as varitn of the size followed by the actual bytes, we'd need the varint length for the page fit calculations
         let bytes_needed = get_varint_len(record_length as u64) + record_length;
 
         match self.log_page.read_u32(0) {
             Ok(mut boundary) => {
-                if (boundary as usize - bytes_needed) < size_of::<u32>() {
-                    self.flush();
+                if (boundary as usize).saturating_sub(bytes_needed) < HEADER_SIZE {
+                    self.flush_to_file()?;
                     self.current_block = self.append_new_block()?;
                     boundary = self.log_page.read_u32(0)?;
                 }
@@ -227,38 +330,105 @@ impl LogManager {
                 self.log_page.write_bytes(record_position, record)?;
                 self.log_page.write_u32(0, record_position as u32)?;
                 self.latest_lsn += 1;
+                self.log_page.write_u32(LSN_OFFSET, self.latest_lsn)?;
                 Ok(self.latest_lsn)
             }
             // TODO: Maybe have better error reporting.
-            Err(_) => {
-                return Err(StormDbError::Corrupt(
-                    "No Page Availabe for Log Records.".to_string(),
-                ));
-            }
+            Err(_) => Err(StormDbError::Corrupt {
+                block: None,
+                message: "No Page Availabe for Log Records.".to_string(),
+            }),
         }
     }
 
-    pub fn iterator(&self) -> LogIterator {
+    /// Errors with `StormDbError::OutOfBound` if a record of the given length wouldn't fit in an empty log block. Records don't span blocks.
+    pub fn check_fits(&self, record_len: usize) -> Result<()> {
+        let bytes_needed = get_varint_len(record_len as u64) + record_len;
+        let block_size = self.file_manager.block_size();
+        if bytes_needed > block_size - HEADER_SIZE {
+            return Err(StormDbError::OutOfBound(format!(
+                "Log record of {} bytes doesn't fit in a {} byte log block",
+                record_len, block_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the transactions that started but haven't committed or rolled back yet, oldest first.
+    pub fn active_transactions(&self) -> Vec<u32> {
+        self.active_transactions.iter().copied().collect()
+    }
+
+    pub(crate) fn transaction_started(&mut self, transaction_number: u32) {
+        self.active_transactions.insert(transaction_number);
+    }
+
+    pub(crate) fn transaction_finished(&mut self, transaction_number: u32) {
+        self.active_transactions.remove(&transaction_number);
+    }
+
+    /// Drops every log block before `block_number`. The remaining blocks are moved to the front of the file, so block `block_number` becomes block 0.
+    /// Only safe for blocks recovery won't need anymore, see `truncate_log`.
+    // Can't cut the front off a file, so the tail gets copied into a new file that's renamed over the log. Copying in place could
+    // leave a log that's half old and half new after a crash. Right after a checkpoint the tail is short, so it's cheap enough.
+    pub fn truncate_before(&mut self, block_number: usize) -> Result<()> {
+        if block_number == 0 {
+            return Ok(());
+        }
+        if block_number > self.current_block.block_number() {
+            return Err(StormDbError::IndexOutOfBound(
+                block_number,
+                self.current_block.block_number(),
+            ));
+        }
+
+        self.flush_to_file()?;
+        let file_manager = &self.file_manager;
+        // Starts with "temp" so a crash before the rename leaves something the FileManager cleans up on the next start.
+        let temp_file = format!("temp_{}", self.log_file);
+        // Could be left over from a truncate that failed earlier on.
+        file_manager.truncate(&temp_file, 0)?;
+        let mut page = Page::builder()
+            .with_block_size(file_manager.block_size())
+            .with_buffer()
+            .build();
+        for source in block_number..=self.current_block.block_number() {
+            file_manager.read(&BlockMetadata::new(&self.log_file, source), &mut page)?;
+            file_manager.write(
+                &BlockMetadata::new(&temp_file, source - block_number),
+                &mut page,
+            )?;
+        }
+        file_manager.replace(&temp_file, &self.log_file)?;
+
+        let remaining_blocks = self.current_block.block_number() - block_number + 1;
+        self.current_block = BlockMetadata::new(&self.log_file, remaining_blocks - 1);
+        Ok(())
+    }
+
+    /// Returns an iterator over the log records, newest first. The log page is flushed first so the iterator sees every record.
+    pub fn iterator(&mut self) -> Result<LogIterator> {
+        self.flush_to_file()?;
         LogIterator::new(self.file_manager.clone(), &self.current_block)
     }
 
-    fn flush_to_file(&mut self) {
+    // Durable once this returns, as far as the file manager's sync policy goes.
+    fn flush_to_file(&mut self) -> Result<()> {
         self.file_manager
-            .borrow_mut()
-            .write(&self.current_block, &mut self.log_page)
-            .expect("error writing to log file");
+            .write(&self.current_block, &mut self.log_page)?;
+        self.file_manager.sync(&self.log_file)?;
         self.latest_flushed_lsn = self.latest_lsn;
+        Ok(())
     }
 
     /// Appends a new block to the end of the log_page.
     fn append_new_block(&mut self) -> Result<BlockMetadata> {
-        let block_metadata = self.file_manager.borrow_mut().append(&self.log_file)?;
+        let block_metadata = self.file_manager.append(&self.log_file)?;
         self.log_page
-            .write_u32(0, self.file_manager.borrow_mut().block_size() as u32)?;
+            .write_u32(0, self.file_manager.block_size() as u32)?;
+        self.log_page.write_u32(LSN_OFFSET, self.latest_lsn)?;
         self.file_manager
-            .borrow_mut()
-            .write(&block_metadata, &mut self.log_page)
-            .expect("could not write block id in to log file");
+            .write(&block_metadata, &mut self.log_page)?;
 
         Ok(block_metadata)
     }
@@ -268,7 +438,7 @@ impl LogManager {
 // Will Likely not be the most performant one. I want to try writing this none the less.
 pub struct LogManager2 {
     log_file: String,
-    file_manager: Rc<RefCell<FileManager>>,
+    file_manager: Arc<FileManager>,
     log_page: Page,
     current_block: BlockMetadata,
     // I think u32 should be more than enough for the lsn numbers for my purposes. We'll see if that needs to change down the line.
@@ -277,15 +447,16 @@ pub struct LogManager2 {
 }
 
 impl LogManager2 {
-    pub fn builder(log_file: String, file_manager: Rc<RefCell<FileManager>>) -> LogManagerBuilder {
+    pub fn builder(log_file: String, file_manager: Arc<FileManager>) -> LogManagerBuilder {
         LogManagerBuilder::new(log_file, file_manager)
     }
 
-    /// Flushes the values in the log_page to the disk. Only does this if the latest flushed record is smaller than the latest written record.
-    pub fn flush(&mut self) {
-        if self.latest_lsn >= self.latest_flushed_lsn {
-            self.flush_to_file()
+    /// Makes sure the record with the given lsn is on disk. The page is only written if that record hasn't been flushed already.
+    pub fn flush(&mut self, lsn: u32) -> Result<()> {
+        if lsn >= self.latest_flushed_lsn {
+            self.flush_to_file()?;
         }
+        Ok(())
     }
 
     // Appends records from right to left. Boundary is where the latest record should start from. The first 4 bytes will always be a u32 representing the boundary.
@@ -298,8 +469,8 @@ impl LogManager2 {
 
         match self.log_page.read_u32(0) {
             Ok(mut boundary) => {
-                if (boundary as usize - bytes_needed) < size_of::<u32>() {
-                    self.flush();
+                if (boundary as usize).saturating_sub(bytes_needed) < HEADER_SIZE {
+                    self.flush_to_file()?;
                     self.current_block = self.append_new_block()?;
                     boundary = self.log_page.read_u32(0)?;
                 }
@@ -314,38 +485,36 @@ impl LogManager2 {
                     .write_bytes_for_log_2(record_position, record)?;
                 self.log_page.write_u32(0, record_position as u32)?;
                 self.latest_lsn += 1;
+                self.log_page.write_u32(LSN_OFFSET, self.latest_lsn)?;
                 Ok(self.latest_lsn)
             }
             // TODO: Maybe have better error reporting.
-            Err(_) => {
-                return Err(StormDbError::Corrupt(
-                    "No Page Availabe for Log Records.".to_string(),
-                ));
-            }
+            Err(_) => Err(StormDbError::Corrupt {
+                block: None,
+                message: "No Page Availabe for Log Records.".to_string(),
+            }),
         }
     }
 
-    pub fn iterator(&self) -> LogIterator {
+    pub fn iterator(&self) -> Result<LogIterator> {
         LogIterator::new(self.file_manager.clone(), &self.current_block)
     }
 
-    fn flush_to_file(&mut self) {
+    fn flush_to_file(&mut self) -> Result<()> {
         self.file_manager
-            .borrow_mut()
-            .write(&self.current_block, &mut self.log_page)
-            .expect("error writing to log file");
+            .write(&self.current_block, &mut self.log_page)?;
         self.latest_flushed_lsn = self.latest_lsn;
+        Ok(())
     }
 
     /// Appends a new block to the end of the log_page.
     fn append_new_block(&mut self) -> Result<BlockMetadata> {
-        let block_metadata = self.file_manager.borrow_mut().append(&self.log_file)?;
+        let block_metadata = self.file_manager.append(&self.log_file)?;
         self.log_page
-            .write_u32(0, self.file_manager.borrow_mut().block_size() as u32)?;
+            .write_u32(0, self.file_manager.block_size() as u32)?;
+        self.log_page.write_u32(LSN_OFFSET, self.latest_lsn)?;
         self.file_manager
-            .borrow_mut()
-            .write(&block_metadata, &mut self.log_page)
-            .expect("could not write block id in to log file");
+            .write(&block_metadata, &mut self.log_page)?;
 
         Ok(block_metadata)
     }
@@ -355,14 +524,14 @@ impl LogManager2 {
 // Having to clone the file_manager multiple times. Alas builder is a vice I must endulge in.
 pub struct LogManagerBuilder {
     log_file: String,
-    file_manager: Rc<RefCell<FileManager>>,
+    file_manager: Arc<FileManager>,
     log_page: Page,
 }
 
 impl LogManagerBuilder {
-    pub fn new(log_file: String, file_manager: Rc<RefCell<FileManager>>) -> Self {
+    pub fn new(log_file: String, file_manager: Arc<FileManager>) -> Self {
         let log_page = PageBuilder::new()
-            .with_log_buffer(vec![0; file_manager.borrow().block_size()])
+            .with_log_buffer(vec![0; file_manager.block_size()])
             .build();
         Self {
             log_file,
@@ -373,39 +542,47 @@ impl LogManagerBuilder {
 
     pub fn build(mut self) -> Result<LogManager> {
         let file_manager = self.file_manager.clone();
-        let file_last_block_index = file_manager.borrow_mut().last_block_index(&self.log_file);
+        let file_last_block_index = file_manager.last_block_index(&self.log_file)?;
 
         let block_metadata = match file_last_block_index {
             Some(last_block_index) => {
                 let block_metadata = BlockMetadata::new(&self.log_file, last_block_index);
                 self.file_manager
-                    .borrow_mut()
-                    .read(&block_metadata, &mut self.log_page)
-                    .expect("could not read block id in to page");
+                    .read(&block_metadata, &mut self.log_page)?;
                 block_metadata
             }
             None => self.append_new_block()?,
         };
 
+        // Whatever is on disk has been flushed by definition, so both start off from the lsn stored in the last block.
+        // For a brand new log the page is all zeros so this is 0.
+        let latest_lsn = self.log_page.read_u32(LSN_OFFSET)?;
+
         Ok(LogManager {
             log_file: self.log_file,
             file_manager: self.file_manager,
             log_page: self.log_page,
             current_block: block_metadata,
-            latest_lsn: 0,
-            latest_flushed_lsn: 0,
+            latest_lsn,
+            latest_flushed_lsn: latest_lsn,
+            active_transactions: BTreeSet::new(),
+            group_commit: Arc::new(GroupCommit {
+                state: Mutex::new(GroupCommitState {
+                    leader_active: false,
+                    durable_lsn: latest_lsn,
+                }),
+                flushed: Condvar::new(),
+            }),
         })
     }
 
-    // Much cleaner than having a method with signature like LogManager::append_new_block(file_manager: Rc<RefCell<FileManager>>, log_file: &str, log_page: &mut Page).
+    // Much cleaner than having a method with signature like LogManager::append_new_block(file_manager: Arc<FileManager>, log_file: &str, log_page: &mut Page).
     fn append_new_block(&mut self) -> Result<BlockMetadata> {
-        let block_metadata = self.file_manager.borrow_mut().append(&self.log_file)?;
+        let block_metadata = self.file_manager.append(&self.log_file)?;
         self.log_page
-            .write_u32(0, self.file_manager.borrow_mut().block_size() as u32)?;
+            .write_u32(0, self.file_manager.block_size() as u32)?;
         self.file_manager
-            .borrow_mut()
-            .write(&block_metadata, &mut self.log_page)
-            .expect("could not write block id in to log file");
+            .write(&block_metadata, &mut self.log_page)?;
 
         Ok(block_metadata)
     }
@@ -414,17 +591,19 @@ impl LogManagerBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::rc::Rc;
+    use crate::SyncPolicy;
+    use rstest::rstest;
+    use std::{fs, sync::Mutex};
     use tempdir::TempDir;
     const BLOCK_SIZE: usize = 256;
 
     #[test]
     fn test_log_manger_builder() {
         let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
-        let file_manager = Rc::new(RefCell::new(
+        let file_manager = Arc::new(
             FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
                 .expect("failed to create file manager"),
-        ));
+        );
         let log_manager = LogManager::builder("log.wal".to_string(), file_manager)
             .build()
             .expect("failed to build log manager");
@@ -444,10 +623,10 @@ mod tests {
     #[test]
     fn test_log_manger_append() {
         let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
-        let file_manager = Rc::new(RefCell::new(
+        let file_manager = Arc::new(
             FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
                 .expect("failed to create file manager"),
-        ));
+        );
         let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
             .build()
             .expect("failed to build log manager");
@@ -488,38 +667,477 @@ mod tests {
     #[test]
     fn test_log_iterator() {
         let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
-        let file_manager = Rc::new(RefCell::new(
+        let file_manager = Arc::new(
             FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
                 .expect("failed to create file manager"),
-        ));
+        );
 
-        let log_manager = Rc::new(RefCell::new(
+        let log_manager = Arc::new(Mutex::new(
             LogManager::builder("log.wal".to_string(), file_manager.clone())
                 .build()
                 .expect("failed to build log manager"),
         ));
 
         let initial_block_id = {
-            let mut lm = log_manager.borrow_mut();
+            let mut lm = log_manager.lock().expect("log manager mutex poisoned");
             lm.append("Something".as_bytes().to_vec())
                 .expect("failed to append");
             lm.append("to".as_bytes().to_vec())
                 .expect("failed to append");
-            lm.flush();
+            lm.flush(2).expect("failed to flush");
             // First block ID
             BlockMetadata::new(&lm.log_file, 0)
         };
 
-        let mut log_iterator = LogIterator::new(file_manager.clone(), &initial_block_id);
+        let mut log_iterator = LogIterator::new(file_manager.clone(), &initial_block_id)
+            .expect("failed to create iterator");
         let first = log_iterator.next();
         assert!(first.is_some());
-        assert_eq!(first.unwrap(), vec![116, 111]); // "to"
+        assert_eq!(first.unwrap(), Ok(vec![116, 111])); // "to"
 
         let second = log_iterator.next();
         assert!(second.is_some());
-        assert_eq!(second.unwrap(), "Something".as_bytes().to_vec());
+        assert_eq!(second.unwrap(), Ok("Something".as_bytes().to_vec()));
         assert_eq!(log_iterator.next(), None);
 
         tmp_dir.close().expect("failed to remove temp dir");
     }
+
+    #[test]
+    fn test_log_manager_flush_skips_already_flushed_lsn() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager.clone())
+            .build()
+            .expect("failed to build log manager");
+
+        let first = log_manager
+            .append("first".as_bytes().to_vec())
+            .expect("failed to append");
+        let second = log_manager
+            .append("second".as_bytes().to_vec())
+            .expect("failed to append");
+        log_manager.flush(second).expect("failed to flush");
+        assert_eq!(log_manager.latest_flushed_lsn, second);
+        let blocks_written = file_manager.stats().blocks_written();
+
+        // The first record went out with the second one, so this shouldn't touch the file.
+        log_manager.flush(first).expect("failed to flush");
+        assert_eq!(file_manager.stats().blocks_written(), blocks_written);
+
+        let third = log_manager
+            .append("third".as_bytes().to_vec())
+            .expect("failed to append");
+        log_manager.flush(third).expect("failed to flush");
+        assert_eq!(log_manager.latest_flushed_lsn, third);
+        assert_eq!(file_manager.stats().blocks_written(), blocks_written + 1);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_log_manager_reopen_recovers_latest_lsn() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let records: Vec<Vec<u8>> = (0..40)
+            .map(|i| format!("record number {}", i).into_bytes())
+            .collect();
+
+        {
+            let file_manager = Arc::new(
+                FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                    .expect("failed to create file manager"),
+            );
+            let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+                .build()
+                .expect("failed to build log manager");
+            // 40 records of ~17 bytes don't fit in a 256 byte block, so this spans a few blocks.
+            for record in records.iter() {
+                log_manager
+                    .append(record.clone())
+                    .expect("failed to append");
+            }
+            assert!(log_manager.current_block.block_number() > 0);
+            log_manager.flush(40).expect("failed to flush");
+        }
+
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+            .build()
+            .expect("failed to build log manager");
+        assert_eq!(log_manager.latest_lsn, 40);
+        assert_eq!(log_manager.latest_flushed_lsn, 40);
+
+        let lsn = log_manager
+            .append("after restart".as_bytes().to_vec())
+            .expect("failed to append");
+        assert_eq!(lsn, 41);
+        log_manager.flush(lsn).expect("failed to flush");
+
+        let read_back: Vec<Vec<u8>> = log_manager
+            .iterator()
+            .expect("failed to create iterator")
+            .collect::<Result<_>>()
+            .expect("failed to read log");
+        assert_eq!(read_back.len(), 41);
+        assert_eq!(read_back[0], "after restart".as_bytes().to_vec());
+        assert_eq!(
+            read_back[1..],
+            records.into_iter().rev().collect::<Vec<_>>()[..]
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_log_manager_reopen_after_new_block_with_no_records() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        {
+            let file_manager = Arc::new(
+                FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                    .expect("failed to create file manager"),
+            );
+            let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+                .build()
+                .expect("failed to build log manager");
+            log_manager
+                .append(vec![1; BLOCK_SIZE - 16])
+                .expect("failed to append");
+            // Doesn't fit in what's left of block 0, so block 1 gets appended and written with just its header before the record lands in it.
+            log_manager.append(vec![2; 32]).expect("failed to append");
+            assert_eq!(log_manager.current_block.block_number(), 1);
+        }
+
+        // Record 2 was never flushed, but record 1 went out when block 0 filled up.
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+            .build()
+            .expect("failed to build log manager");
+        assert_eq!(log_manager.current_block.block_number(), 1);
+        assert_eq!(log_manager.latest_lsn, 1);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_log_manager_truncate_before() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager.clone())
+            .build()
+            .expect("failed to build log manager");
+
+        for i in 0..40 {
+            log_manager
+                .append(format!("record number {}", i).into_bytes())
+                .expect("failed to append");
+        }
+        let last_block = log_manager.current_block.block_number();
+        assert!(last_block >= 2);
+
+        // Remember what lives in the last two blocks, that's what should be left over.
+        let mut iterator = log_manager.iterator().expect("failed to create iterator");
+        let mut expected = Vec::new();
+        while let Some(record) = iterator.next() {
+            let record = record.expect("failed to read record");
+            if iterator.block().block_number() < last_block - 1 {
+                break;
+            }
+            expected.push(record);
+        }
+
+        log_manager
+            .truncate_before(last_block - 1)
+            .expect("failed to truncate");
+        assert_eq!(log_manager.current_block.block_number(), 1);
+        assert_eq!(
+            file_manager
+                .last_block_index("log.wal")
+                .expect("failed to get last block"),
+            Some(1)
+        );
+
+        let read_back: Vec<Vec<u8>> = log_manager
+            .iterator()
+            .expect("failed to create iterator")
+            .collect::<Result<_>>()
+            .expect("failed to read log");
+        assert_eq!(read_back, expected);
+
+        // Appending carries on from the same lsn.
+        assert_eq!(
+            log_manager
+                .append("after truncate".as_bytes().to_vec())
+                .expect("failed to append"),
+            41
+        );
+        assert!(!tmp_dir.path().join("temp_log.wal").exists());
+
+        // The truncated log is what's on disk now, a fresh file manager sees the same thing.
+        log_manager.flush(41).expect("failed to flush");
+        drop(log_manager);
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+            .build()
+            .expect("failed to build log manager");
+        let mut reopened: Vec<Vec<u8>> = log_manager
+            .iterator()
+            .expect("failed to create iterator")
+            .collect::<Result<_>>()
+            .expect("failed to read log");
+        assert_eq!(reopened.remove(0), "after truncate".as_bytes().to_vec());
+        assert_eq!(reopened, expected);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_record_too_big_for_a_block() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager)
+            .build()
+            .expect("failed to build log manager");
+
+        // Two bytes of varint for anything 128 bytes or longer.
+        let largest = BLOCK_SIZE - HEADER_SIZE - 2;
+        assert!(matches!(
+            log_manager.append(vec![1; largest + 1]),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert_eq!(
+            log_manager
+                .append(vec![2; largest])
+                .expect("failed to append"),
+            1
+        );
+
+        let read_back: Vec<Vec<u8>> = log_manager
+            .iterator()
+            .expect("failed to create iterator")
+            .collect::<Result<_>>()
+            .expect("failed to read log");
+        assert_eq!(read_back, vec![vec![2; largest]]);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_corrupt_log_block_is_an_error() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let open_file_manager = || {
+            Arc::new(
+                FileManager::builder(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                    .with_checksums(true)
+                    .build()
+                    .expect("failed to create file manager"),
+            )
+        };
+        let mut log_manager = LogManager::builder("log.wal".to_string(), open_file_manager())
+            .build()
+            .expect("failed to build log manager");
+        for i in 0..40 {
+            log_manager
+                .append(format!("record number {}", i).into_bytes())
+                .expect("failed to append");
+        }
+        log_manager.flush(40).expect("failed to flush");
+        let last_block = log_manager.current_block.block_number();
+        drop(log_manager);
+
+        // Flip a bit in the first block behind the FileManager's back.
+        let path = tmp_dir.path().join("log.wal");
+        let mut bytes = fs::read(&path).expect("failed to read file");
+        bytes[HEADER_SIZE] ^= 0x10;
+        fs::write(&path, &bytes).expect("failed to write file");
+
+        // The last block is fine, so the log opens and iterates until it gets to the bad block. Then it stops.
+        let mut log_manager = LogManager::builder("log.wal".to_string(), open_file_manager())
+            .build()
+            .expect("failed to build log manager");
+        let mut iterator = log_manager.iterator().expect("failed to create iterator");
+        let error = iterator
+            .find_map(|record| record.err())
+            .expect("expected the corrupt block to show up");
+        assert!(
+            matches!(&error, StormDbError::Corrupt { block: Some(block), .. } if block.block_number() == 0),
+            "unexpected error {:?}",
+            error
+        );
+        assert!(iterator.next().is_none());
+        drop(log_manager);
+
+        // With the last block bad the log can't even be opened.
+        let slot_size = bytes.len() / (last_block + 1);
+        bytes[last_block * slot_size + HEADER_SIZE] ^= 0x10;
+        fs::write(&path, &bytes).expect("failed to write file");
+        assert!(matches!(
+            LogManager::builder("log.wal".to_string(), open_file_manager()).build(),
+            Err(StormDbError::Corrupt { block: Some(_), .. })
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    #[case::every_flush(SyncPolicy::EveryFlush, 1)]
+    #[case::none(SyncPolicy::None, 0)]
+    fn test_flush_syncs_log(#[case] sync_policy: SyncPolicy, #[case] expected_syncs: u64) {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::with_sync_policy(tmp_dir.path().to_owned(), BLOCK_SIZE, sync_policy)
+                .expect("failed to create file manager"),
+        );
+        let mut log_manager = LogManager::builder("log.wal".to_string(), file_manager.clone())
+            .build()
+            .expect("failed to build log manager");
+
+        let lsn = log_manager
+            .append("commit".as_bytes().to_vec())
+            .expect("failed to append");
+        log_manager.flush(lsn).expect("failed to flush");
+        assert_eq!(file_manager.stats().syncs(), expected_syncs);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_group_flush_past_latest_lsn() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let log_manager = Mutex::new(
+            LogManager::builder("log.wal".to_string(), file_manager.clone())
+                .build()
+                .expect("failed to build log manager"),
+        );
+        let lsn = log_manager
+            .lock()
+            .unwrap()
+            .append("commit".as_bytes().to_vec())
+            .expect("failed to append");
+
+        // Flushes what's there and returns instead of waiting for a record nobody appended.
+        LogManager::group_flush(&log_manager, lsn + 1).expect("failed to group flush");
+        assert_eq!(log_manager.lock().unwrap().latest_flushed_lsn, lsn);
+        assert_eq!(file_manager.stats().group_flushes(), 1);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_group_flush_batches_waiters() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let log_manager = Arc::new(Mutex::new(
+            LogManager::builder("log.wal".to_string(), file_manager.clone())
+                .build()
+                .expect("failed to build log manager"),
+        ));
+        let group_commit = log_manager.lock().unwrap().group_commit.clone();
+        // Pretend a flush is already running so every committer has to wait for the next leader.
+        group_commit.lock_state().leader_active = true;
+
+        let committers: Vec<_> = (0..4)
+            .map(|i| {
+                let log_manager = log_manager.clone();
+                std::thread::spawn(move || {
+                    let lsn = log_manager
+                        .lock()
+                        .unwrap()
+                        .append(format!("commit {i}").into_bytes())
+                        .expect("failed to append");
+                    LogManager::group_flush(&log_manager, lsn).expect("failed to group flush");
+                })
+            })
+            .collect();
+        while file_manager.stats().log_flush_requests() < 4 {
+            std::thread::yield_now();
+        }
+        LogManager::lead_group_flush(&log_manager, &group_commit).expect("failed to lead");
+        for committer in committers {
+            committer.join().expect("committer panicked");
+        }
+
+        assert_eq!(file_manager.stats().group_flushes(), 1);
+        assert_eq!(file_manager.stats().syncs(), 1);
+        assert_eq!(file_manager.stats().requests_per_group_flush(), 4.0);
+        assert_eq!(group_commit.lock_state().durable_lsn, 4);
+        assert_eq!(log_manager.lock().unwrap().latest_flushed_lsn, 4);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_group_flush_concurrent_commits() {
+        let tmp_dir = TempDir::new("test_log_manager").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let log_manager = Arc::new(Mutex::new(
+            LogManager::builder("log.wal".to_string(), file_manager.clone())
+                .build()
+                .expect("failed to build log manager"),
+        ));
+
+        // Enough records to spill over a few blocks while the flushes are going on.
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let log_manager = &log_manager;
+                scope.spawn(move || {
+                    for j in 0..20 {
+                        let lsn = log_manager
+                            .lock()
+                            .unwrap()
+                            .append(format!("commit {i}-{j}").into_bytes())
+                            .expect("failed to append");
+                        LogManager::group_flush(log_manager, lsn).expect("failed to group flush");
+                    }
+                });
+            }
+        });
+
+        let stats = file_manager.stats();
+        assert_eq!(stats.log_flush_requests(), 160);
+        assert!(stats.group_flushes() >= 1 && stats.group_flushes() <= 160);
+        assert_eq!(log_manager.lock().unwrap().latest_flushed_lsn, 160);
+
+        // Everything made it to disk, the newest record comes first.
+        let file_manager_for_reopen = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut reopened = LogManager::builder("log.wal".to_string(), file_manager_for_reopen)
+            .build()
+            .expect("failed to build log manager");
+        assert_eq!(
+            reopened.iterator().expect("failed to get iterator").count(),
+            160
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
 }
@@ -0,0 +1,89 @@
+/*
+HyperLogLog sketch for counting distinct values without keeping them around.
+Paper: https://algo.inria.fr/flajolet/Publications/FlFuGaMe07.pdf
+
+Every value is hashed to 64 bits, the first PRECISION bits pick a register and the register keeps the longest run of leading zeros seen
+in the rest of the hash (plus one). With 2^10 registers the standard error is about 1.04 / sqrt(1024), so ~3%, for a KiB per column.
+Small counts use linear counting on the empty registers, which is close to exact. The hash is 64 bits so the large range correction
+from the paper isn't needed.
+*/
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+const PRECISION: u32 = 10;
+const REGISTERS: usize = 1 << PRECISION;
+
+#[derive(Debug, Clone)]
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub(crate) fn new() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    pub(crate) fn add<T: Hash + ?Sized>(&mut self, value: &T) {
+        // DefaultHasher::new always uses the same keys, so the same value lands in the same register every time.
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // The sentinel bit caps the run at 64 - PRECISION zeros.
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub(crate) fn estimate(&self) -> usize {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-(rank as i32)))
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        let empty = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && empty > 0 {
+            (m * (m / empty as f64).ln()).round() as usize
+        } else {
+            raw.round() as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty(0)]
+    #[case::small(50)]
+    #[case::linear_counting(1_000)]
+    #[case::raw_estimate(100_000)]
+    fn test_estimate(#[case] distinct: usize) {
+        let mut sketch = HyperLogLog::new();
+        // Every value twice, duplicates shouldn't count.
+        for _ in 0..2 {
+            for value in 0..distinct {
+                sketch.add(&value);
+            }
+        }
+        let error = sketch.estimate().abs_diff(distinct) as f64;
+        assert!(
+            error <= (distinct as f64 * 0.05).max(1.0),
+            "estimated {} for {} distinct values",
+            sketch.estimate(),
+            distinct
+        );
+    }
+}
@@ -0,0 +1,149 @@
+/*
+Index Manager API as per the book:
+  public IndexMgr(boolean isnew, TableMgr tblmgr, StatMgr statmgr, Transaction tx);
+  public void createIndex(String idxname, String tblname, String fldname, Transaction tx);
+  public Map<String,IndexInfo> getIndexInfo(String tblname, Transaction tx);
+
+Index Info API:
+  public IndexInfo(String idxname, String fldname, Schema tblSchema, Transaction tx, StatInfo si);
+  public Index open();
+  public int blocksAccessed();
+  public int recordsOutput();
+  public int distinctValues(String fname);
+
+Indexes live in idxcat(indexname, tablename, fieldname). There's no index implementation yet, so IndexInfo can't open one or say how
+many blocks a lookup reads, it only carries the names and the estimates that come from the table's statistics.
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    StormDbError,
+    error::Result,
+    record::{Layout, Schema},
+};
+
+use super::{
+    stat_manager::StatInfo,
+    table_manager::{MAX_NAME, TableManager, check_name},
+};
+
+const INDEX_CATALOG: &str = "idxcat";
+
+/// An index on a single field of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    index_name: String,
+    field_name: String,
+    stat_info: StatInfo,
+}
+
+impl IndexInfo {
+    pub fn new(index_name: String, field_name: String, stat_info: StatInfo) -> Self {
+        Self {
+            index_name,
+            field_name,
+            stat_info,
+        }
+    }
+
+    pub fn index_name(&self) -> &str {
+        &self.index_name
+    }
+
+    pub fn field_name(&self) -> &str {
+        &self.field_name
+    }
+
+    /// Estimated number of records a lookup of one value returns.
+    pub fn records_output(&self) -> usize {
+        self.stat_info.records_output() / self.stat_info.distinct_values(&self.field_name)
+    }
+
+    /// Estimated number of distinct values of the field among the records a lookup returns.
+    pub fn distinct_values(&self, field_name: &str) -> usize {
+        if field_name == self.field_name {
+            1
+        } else {
+            self.stat_info.distinct_values(field_name)
+        }
+    }
+}
+
+pub(crate) struct IndexManager {
+    layout: Layout,
+}
+
+impl IndexManager {
+    pub(crate) fn new(table_manager: &TableManager, is_new: bool) -> Result<Self> {
+        let mut schema = Schema::new();
+        schema
+            .add_string_field("indexname", MAX_NAME)
+            .add_string_field("tablename", MAX_NAME)
+            .add_string_field("fieldname", MAX_NAME);
+        if is_new {
+            table_manager.create_table(INDEX_CATALOG, schema.clone())?;
+        }
+        Ok(Self {
+            layout: Layout::new(schema),
+        })
+    }
+
+    pub(crate) fn create_index(
+        &self,
+        table_manager: &TableManager,
+        index_name: &str,
+        table_name: &str,
+        field_name: &str,
+    ) -> Result<()> {
+        check_name("Index", index_name)?;
+        if !table_manager
+            .get_layout(table_name)?
+            .schema()
+            .has_field(field_name)
+        {
+            return Err(StormDbError::NotFound(format!(
+                "No field {} in table {}",
+                field_name, table_name
+            )));
+        }
+
+        let _catalog = table_manager.lock_catalog();
+        let mut index_catalog = table_manager.scan(INDEX_CATALOG, &self.layout)?;
+        while index_catalog.next()? {
+            if index_catalog.get_string("indexname")? == index_name {
+                return Err(StormDbError::AlreadyExists(format!(
+                    "Index {} already exists",
+                    index_name
+                )));
+            }
+        }
+        index_catalog.insert()?;
+        index_catalog.set_string("indexname", index_name.to_string())?;
+        index_catalog.set_string("tablename", table_name.to_string())?;
+        index_catalog.set_string("fieldname", field_name.to_string())
+    }
+
+    /// Returns the indexes of the table by the field they're on.
+    pub(crate) fn get_index_info(
+        &self,
+        table_manager: &TableManager,
+        table_name: &str,
+        stat_info: StatInfo,
+    ) -> Result<HashMap<String, IndexInfo>> {
+        let mut index_catalog = table_manager.scan(INDEX_CATALOG, &self.layout)?;
+        let mut index_info = HashMap::new();
+        while index_catalog.next()? {
+            if index_catalog.get_string("tablename")? != table_name {
+                continue;
+            }
+            let index_name = index_catalog.get_string("indexname")?;
+            let field_name = index_catalog.get_string("fieldname")?;
+            index_info.insert(
+                field_name.clone(),
+                IndexInfo::new(index_name, field_name, stat_info.clone()),
+            );
+        }
+        Ok(index_info)
+    }
+}
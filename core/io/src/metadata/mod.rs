@@ -0,0 +1,472 @@
+/*
+Metadata Manager API as per the book:
+  public MetadataMgr(boolean isnew, Transaction tx);
+  public void createTable(String tblname, Schema sch, Transaction tx);
+  public Layout getLayout(String tblname, Transaction tx);
+  public void createView(String viewname, String viewdef, Transaction tx);
+  public String getViewDef(String viewname, Transaction tx);
+  public void createIndex(String idxname, String tblname, String fldname, Transaction tx);
+  public Map<String,IndexInfo> getIndexInfo(String tblname, Transaction tx);
+  public StatInfo getStatInfo(String tblname, Layout layout, Transaction tx);
+
+Everything about the database lives in catalog tables next to the data, tblcat, fldcat, viewcat and idxcat. A directory without a
+tblcat is a new database and gets the catalogs created. The stats are only in memory, hence the &mut self where they get touched,
+wrap it in an Arc<Mutex<>> to share it like the BufferManager. They're collected by scanning the table and refreshed once they're
+older than the refresh interval, see stat_manager.rs.
+*/
+mod hyperloglog;
+mod index_manager;
+mod stat_manager;
+mod table_manager;
+mod view_manager;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+pub use index_manager::IndexInfo;
+pub use stat_manager::StatInfo;
+pub use table_manager::MAX_NAME;
+pub use view_manager::MAX_VIEW_DEF;
+
+use crate::{
+    FileManager,
+    error::Result,
+    record::{Layout, Schema},
+};
+
+use index_manager::IndexManager;
+use stat_manager::{DEFAULT_REFRESH_INTERVAL, StatManager};
+use table_manager::{TABLE_CATALOG, TableManager};
+use view_manager::ViewManager;
+
+pub struct MetadataManager {
+    table_manager: TableManager,
+    view_manager: ViewManager,
+    index_manager: IndexManager,
+    stat_manager: StatManager,
+}
+
+impl MetadataManager {
+    /// Opens the catalog of the database directory, creating it if the directory doesn't have one yet.
+    pub fn new(file_manager: Arc<FileManager>) -> Result<Self> {
+        Self::with_stat_refresh(file_manager, DEFAULT_REFRESH_INTERVAL)
+    }
+
+    /// Same as new, with table statistics recollected once they're older than refresh_interval.
+    pub fn with_stat_refresh(
+        file_manager: Arc<FileManager>,
+        refresh_interval: Duration,
+    ) -> Result<Self> {
+        let is_new = file_manager
+            .length(&format!("{}.tbl", TABLE_CATALOG))?
+            .is_none();
+        let table_manager = TableManager::new(file_manager, is_new)?;
+        let view_manager = ViewManager::new(&table_manager, is_new)?;
+        let index_manager = IndexManager::new(&table_manager, is_new)?;
+        Ok(Self {
+            table_manager,
+            view_manager,
+            index_manager,
+            stat_manager: StatManager::new(refresh_interval),
+        })
+    }
+
+    pub fn create_table(&self, table_name: &str, schema: Schema) -> Result<()> {
+        self.table_manager.create_table(table_name, schema)
+    }
+
+    pub fn get_layout(&self, table_name: &str) -> Result<Layout> {
+        self.table_manager.get_layout(table_name)
+    }
+
+    pub fn create_view(&self, view_name: &str, view_def: &str) -> Result<()> {
+        self.view_manager
+            .create_view(&self.table_manager, view_name, view_def)
+    }
+
+    pub fn get_view_def(&self, view_name: &str) -> Result<Option<String>> {
+        self.view_manager
+            .get_view_def(&self.table_manager, view_name)
+    }
+
+    pub fn create_index(&self, index_name: &str, table_name: &str, field_name: &str) -> Result<()> {
+        self.index_manager
+            .create_index(&self.table_manager, index_name, table_name, field_name)
+    }
+
+    /// Returns the indexes of the table by the field they're on.
+    pub fn get_index_info(&mut self, table_name: &str) -> Result<HashMap<String, IndexInfo>> {
+        let layout = self.table_manager.get_layout(table_name)?;
+        let stat_info = self.get_stat_info(table_name, &layout)?;
+        self.index_manager
+            .get_index_info(&self.table_manager, table_name, stat_info)
+    }
+
+    pub fn get_stat_info(&mut self, table_name: &str, layout: &Layout) -> Result<StatInfo> {
+        self.stat_manager
+            .get_stat_info(&self.table_manager, table_name, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StormDbError, TableScan};
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 400;
+
+    fn student_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .add_int_field("id")
+            .add_string_field("name", 10)
+            .add_field("major", crate::FieldType::Int, 0, true);
+        schema
+    }
+
+    fn open(tmp_dir: &TempDir) -> (Arc<FileManager>, MetadataManager) {
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let metadata_manager =
+            MetadataManager::new(file_manager.clone()).expect("failed to open metadata manager");
+        (file_manager, metadata_manager)
+    }
+
+    #[test]
+    fn test_table_catalog_persists() {
+        let tmp_dir = TempDir::new("test_table_catalog").expect("failed to create temp dir");
+        {
+            let (_, metadata_manager) = open(&tmp_dir);
+            metadata_manager
+                .create_table("student", student_schema())
+                .expect("failed to create table");
+            assert_eq!(
+                metadata_manager
+                    .get_layout("student")
+                    .expect("failed to get layout"),
+                Layout::new(student_schema())
+            );
+        }
+
+        // Reopening reads the catalog back instead of creating it again.
+        let (_, metadata_manager) = open(&tmp_dir);
+        assert_eq!(
+            metadata_manager
+                .get_layout("student")
+                .expect("failed to get layout"),
+            Layout::new(student_schema())
+        );
+        // The catalog describes itself.
+        let field_catalog = metadata_manager
+            .get_layout("fldcat")
+            .expect("failed to get layout");
+        assert!(field_catalog.schema().has_field("offset"));
+        for catalog in ["tblcat", "viewcat", "idxcat"] {
+            metadata_manager
+                .get_layout(catalog)
+                .expect("failed to get catalog layout");
+        }
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_small_blocks() {
+        let small_dir = TempDir::new("test_small_blocks").expect("failed to create temp dir");
+        // Big enough for tblcat and fldcat rows, not for viewcat ones.
+        let file_manager = Arc::new(
+            FileManager::new(small_dir.path().to_owned(), 100)
+                .expect("failed to create file manager"),
+        );
+        assert!(matches!(
+            MetadataManager::new(file_manager),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        small_dir.close().expect("failed to remove temp dir");
+
+        let tmp_dir = TempDir::new("test_small_blocks").expect("failed to create temp dir");
+
+        let (_, metadata_manager) = open(&tmp_dir);
+        let mut schema = Schema::new();
+        schema.add_string_field("essay", BLOCK_SIZE);
+        assert!(matches!(
+            metadata_manager.create_table("student", schema),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert!(matches!(
+            metadata_manager.get_layout("student"),
+            Err(StormDbError::NotFound(_))
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_catalog_errors() {
+        let tmp_dir = TempDir::new("test_catalog_errors").expect("failed to create temp dir");
+        let (_, metadata_manager) = open(&tmp_dir);
+        metadata_manager
+            .create_table("student", student_schema())
+            .expect("failed to create table");
+
+        assert!(matches!(
+            metadata_manager.create_table("student", student_schema()),
+            Err(StormDbError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            metadata_manager.get_layout("teacher"),
+            Err(StormDbError::NotFound(_))
+        ));
+        assert!(matches!(
+            metadata_manager.create_table("a_very_long_table_name", student_schema()),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert!(matches!(
+            metadata_manager.create_index("idx", "student", "grade"),
+            Err(StormDbError::NotFound(_))
+        ));
+        metadata_manager
+            .create_index("student_id", "student", "id")
+            .expect("failed to create index");
+        assert!(matches!(
+            metadata_manager.create_index("student_id", "student", "name"),
+            Err(StormDbError::AlreadyExists(_))
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_concurrent_create_table() {
+        let tmp_dir = TempDir::new("test_concurrent_create").expect("failed to create temp dir");
+        let (_, metadata_manager) = open(&tmp_dir);
+        let metadata_manager = Arc::new(metadata_manager);
+
+        // Enough tables that the fldcat rows spill over several blocks while the threads race for them.
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let metadata_manager = metadata_manager.clone();
+                std::thread::spawn(move || {
+                    for table in 0..4 {
+                        metadata_manager
+                            .create_table(&format!("t{}_{}", thread, table), student_schema())
+                            .expect("failed to create table");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("create thread panicked");
+        }
+
+        for thread in 0..8 {
+            for table in 0..4 {
+                assert_eq!(
+                    metadata_manager
+                        .get_layout(&format!("t{}_{}", thread, table))
+                        .expect("failed to get layout"),
+                    Layout::new(student_schema())
+                );
+            }
+        }
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_create_table_clears_leftover_fields() {
+        let tmp_dir = TempDir::new("test_leftover_fields").expect("failed to create temp dir");
+        let (file_manager, metadata_manager) = open(&tmp_dir);
+
+        // What a crash between the fldcat rows and the tblcat row leaves behind.
+        let field_catalog_layout = metadata_manager
+            .get_layout("fldcat")
+            .expect("failed to get layout");
+        let mut field_catalog = TableScan::new(file_manager, "fldcat", field_catalog_layout)
+            .expect("failed to open scan");
+        field_catalog.insert().expect("failed to insert");
+        field_catalog
+            .set_string("tblname", "student".to_string())
+            .expect("failed to set tblname");
+        field_catalog
+            .set_string("fldname", "grade".to_string())
+            .expect("failed to set fldname");
+        assert!(matches!(
+            metadata_manager.get_layout("student"),
+            Err(StormDbError::NotFound(_))
+        ));
+
+        metadata_manager
+            .create_table("student", student_schema())
+            .expect("failed to create table");
+        assert_eq!(
+            metadata_manager
+                .get_layout("student")
+                .expect("failed to get layout"),
+            Layout::new(student_schema())
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_views() {
+        let tmp_dir = TempDir::new("test_views").expect("failed to create temp dir");
+        {
+            let (_, metadata_manager) = open(&tmp_dir);
+            metadata_manager
+                .create_view("honors", "select name from student where id = 1")
+                .expect("failed to create view");
+            assert!(matches!(
+                metadata_manager.create_view("honors", "select id from student"),
+                Err(StormDbError::AlreadyExists(_))
+            ));
+            assert!(matches!(
+                metadata_manager.create_view("long", &"x".repeat(MAX_VIEW_DEF + 1)),
+                Err(StormDbError::OutOfBound(_))
+            ));
+        }
+
+        let (_, metadata_manager) = open(&tmp_dir);
+        assert_eq!(
+            metadata_manager
+                .get_view_def("honors")
+                .expect("failed to get view"),
+            Some("select name from student where id = 1".to_string())
+        );
+        assert_eq!(
+            metadata_manager
+                .get_view_def("missing")
+                .expect("failed to get view"),
+            None
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    fn insert_students(
+        file_manager: &Arc<FileManager>,
+        layout: &Layout,
+        ids: std::ops::Range<i32>,
+    ) {
+        let mut table_scan = TableScan::new(file_manager.clone(), "student", layout.clone())
+            .expect("failed to open scan");
+        for id in ids {
+            table_scan.insert().expect("failed to insert");
+            table_scan.set_int("id", id).expect("failed to set id");
+            table_scan
+                .set_string("name", format!("name{}", id % 10))
+                .expect("failed to set name");
+            if id % 2 == 0 {
+                table_scan
+                    .set_int("major", id % 4)
+                    .expect("failed to set major");
+            }
+        }
+    }
+
+    // The sketches are estimates, small counts come out exact or off by a value or two.
+    fn assert_close(estimate: usize, expected: usize) {
+        assert!(
+            estimate.abs_diff(expected) <= 2,
+            "estimated {} instead of {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_stats_and_index_info() {
+        let tmp_dir = TempDir::new("test_stats").expect("failed to create temp dir");
+        let (file_manager, mut metadata_manager) = open(&tmp_dir);
+        metadata_manager
+            .create_table("student", student_schema())
+            .expect("failed to create table");
+        metadata_manager
+            .create_index("student_id", "student", "id")
+            .expect("failed to create index");
+        metadata_manager
+            .create_index("student_name", "student", "name")
+            .expect("failed to create index");
+
+        let layout = metadata_manager
+            .get_layout("student")
+            .expect("failed to get layout");
+        insert_students(&file_manager, &layout, 0..60);
+
+        let stat_info = metadata_manager
+            .get_stat_info("student", &layout)
+            .expect("failed to get stats");
+        assert_eq!(stat_info.records_output(), 60);
+        assert_eq!(
+            stat_info.blocks_accessed(),
+            file_manager
+                .length("student.tbl")
+                .expect("failed to get length")
+                .expect("table file missing")
+        );
+        assert_close(stat_info.distinct_values("id"), 60);
+        assert_close(stat_info.distinct_values("name"), 10);
+        // Odd ids have no major, the nulls aren't a value.
+        assert_close(stat_info.distinct_values("major"), 2);
+        assert_eq!(stat_info.distinct_values("grade"), 21);
+
+        let index_info = metadata_manager
+            .get_index_info("student")
+            .expect("failed to get index info");
+        assert_eq!(index_info.len(), 2);
+        let name_index = &index_info["name"];
+        assert_eq!(name_index.index_name(), "student_name");
+        assert_eq!(name_index.distinct_values("name"), 1);
+        assert_close(name_index.distinct_values("id"), 60);
+        assert_close(name_index.records_output(), 6);
+        assert_eq!(index_info["id"].index_name(), "student_id");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_stat_refresh() {
+        let tmp_dir = TempDir::new("test_stat_refresh").expect("failed to create temp dir");
+        let (file_manager, mut metadata_manager) = open(&tmp_dir);
+        let mut refreshing_manager =
+            MetadataManager::with_stat_refresh(file_manager.clone(), Duration::ZERO)
+                .expect("failed to open metadata manager");
+        metadata_manager
+            .create_table("student", student_schema())
+            .expect("failed to create table");
+        let layout = metadata_manager
+            .get_layout("student")
+            .expect("failed to get layout");
+
+        // Empty tables still report a distinct value so the planner can divide by it.
+        let stat_info = metadata_manager
+            .get_stat_info("student", &layout)
+            .expect("failed to get stats");
+        assert_eq!(stat_info.records_output(), 0);
+        assert_eq!(stat_info.distinct_values("id"), 1);
+
+        insert_students(&file_manager, &layout, 0..30);
+        // Collected a moment ago, not stale yet.
+        assert_eq!(
+            metadata_manager
+                .get_stat_info("student", &layout)
+                .expect("failed to get stats")
+                .records_output(),
+            0
+        );
+        // Stale right away, every request scans again.
+        for expected in [30, 40] {
+            let stat_info = refreshing_manager
+                .get_stat_info("student", &layout)
+                .expect("failed to get stats");
+            assert_eq!(stat_info.records_output(), expected);
+            assert_close(stat_info.distinct_values("id"), expected);
+            insert_students(&file_manager, &layout, 30..40);
+        }
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
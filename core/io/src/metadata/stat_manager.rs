@@ -0,0 +1,140 @@
+/*
+Stat Manager API as per the book:
+  public StatMgr(TableMgr tm, Transaction tx);
+  public StatInfo getStatInfo(String tblname, Layout lay, Transaction tx);
+
+Stat Info API:
+  public int blocksAccessed();
+  public int recordsOutput();
+  public int distinctValues(String fldname);
+
+Statistics are kept in memory and not in the catalog. A table gets counted by a full scan the first time it's asked about and again once
+its numbers are older than the refresh interval, so the cost of a scan is paid at most once per interval per table. The book guesses the
+distinct values as a third of the records, here the scan feeds every column into a HyperLogLog sketch instead. Null values aren't
+counted as a value.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::Result,
+    record::{FieldType, Layout},
+};
+
+use super::{hyperloglog::HyperLogLog, table_manager::TableManager};
+
+pub(crate) const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the planner knows about a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatInfo {
+    blocks: usize,
+    records: usize,
+    distinct_values: HashMap<String, usize>,
+}
+
+impl StatInfo {
+    pub fn new(blocks: usize, records: usize, distinct_values: HashMap<String, usize>) -> Self {
+        Self {
+            blocks,
+            records,
+            distinct_values,
+        }
+    }
+
+    /// Number of blocks a full scan of the table reads.
+    pub fn blocks_accessed(&self) -> usize {
+        self.blocks
+    }
+
+    /// Number of records in the table.
+    pub fn records_output(&self) -> usize {
+        self.records
+    }
+
+    /// Estimated number of distinct values of the field, never less than one so it's safe to divide by.
+    /// Falls back to the book's guess for a field the table doesn't have.
+    pub fn distinct_values(&self, field_name: &str) -> usize {
+        self.distinct_values
+            .get(field_name)
+            .copied()
+            .unwrap_or(1 + self.records / 3)
+            .max(1)
+    }
+}
+
+pub(crate) struct StatManager {
+    table_stats: HashMap<String, (StatInfo, Instant)>,
+    refresh_interval: Duration,
+}
+
+impl StatManager {
+    pub(crate) fn new(refresh_interval: Duration) -> Self {
+        Self {
+            table_stats: HashMap::new(),
+            refresh_interval,
+        }
+    }
+
+    pub(crate) fn get_stat_info(
+        &mut self,
+        table_manager: &TableManager,
+        table_name: &str,
+        layout: &Layout,
+    ) -> Result<StatInfo> {
+        if let Some((stat_info, collected_at)) = self.table_stats.get(table_name)
+            && collected_at.elapsed() < self.refresh_interval
+        {
+            return Ok(stat_info.clone());
+        }
+        let stat_info = Self::collect(table_manager, table_name, layout)?;
+        self.table_stats
+            .insert(table_name.to_string(), (stat_info.clone(), Instant::now()));
+        Ok(stat_info)
+    }
+
+    fn collect(
+        table_manager: &TableManager,
+        table_name: &str,
+        layout: &Layout,
+    ) -> Result<StatInfo> {
+        let schema = layout.schema();
+        let mut sketches: Vec<HyperLogLog> =
+            schema.fields().iter().map(|_| HyperLogLog::new()).collect();
+        let mut records = 0;
+
+        let mut table_scan = table_manager.scan(table_name, layout)?;
+        while table_scan.next()? {
+            records += 1;
+            for (field_name, sketch) in schema.fields().iter().zip(sketches.iter_mut()) {
+                if table_scan.is_null(field_name)? {
+                    continue;
+                }
+                match schema
+                    .field_type(field_name)
+                    .expect("field comes from the schema")
+                {
+                    FieldType::Int => sketch.add(&table_scan.get_int(field_name)?),
+                    FieldType::String => sketch.add(&table_scan.get_string(field_name)?),
+                    FieldType::Bytes => sketch.add(&table_scan.get_bytes(field_name)?),
+                }
+            }
+        }
+
+        // Sketches are a few percent off either way, there can't be more distinct values than records though.
+        let distinct_values = schema
+            .fields()
+            .iter()
+            .zip(sketches)
+            .map(|(field_name, sketch)| (field_name.clone(), sketch.estimate().min(records)))
+            .collect();
+        let blocks = table_manager
+            .file_manager()
+            .length(&format!("{}.tbl", table_name))?
+            .unwrap_or(0);
+        Ok(StatInfo::new(blocks, records, distinct_values))
+    }
+}
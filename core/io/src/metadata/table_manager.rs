@@ -0,0 +1,181 @@
+/*
+Table Manager API as per the book:
+  public TableMgr(boolean isNew, Transaction tx);
+  public void createTable(String tblname, Schema sch, Transaction tx);
+  public Layout getLayout(String tblname, Transaction tx);
+
+Two catalog tables:
+    tblcat(tblname, slotsize)
+    fldcat(tblname, fldname, type, length, nullable, offset)
+Both describe themselves too, so the catalog can be read with the same code as any other table.
+
+Every TableScan writes back its own copy of the page, so two catalog writers at once would lose each other's rows. They take the
+catalog lock first, that goes for the view and index catalogs too. A table only exists once its tblcat row is there, so its fldcat rows
+are written first and a crash in between leaves rows nobody reads, they're cleared the next time a table of that name is created.
+*/
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    FileManager, StormDbError,
+    error::Result,
+    record::{FieldType, Layout, RecordPage, Schema, TableScan},
+};
+
+/// Longest table, field, view or index name the catalog can hold, in bytes.
+pub const MAX_NAME: usize = 16;
+
+pub(crate) const TABLE_CATALOG: &str = "tblcat";
+pub(crate) const FIELD_CATALOG: &str = "fldcat";
+
+pub(crate) struct TableManager {
+    file_manager: Arc<FileManager>,
+    table_catalog_layout: Layout,
+    field_catalog_layout: Layout,
+    catalog_lock: Mutex<()>,
+}
+
+impl TableManager {
+    pub(crate) fn new(file_manager: Arc<FileManager>, is_new: bool) -> Result<Self> {
+        let mut table_catalog_schema = Schema::new();
+        table_catalog_schema
+            .add_string_field("tblname", MAX_NAME)
+            .add_int_field("slotsize");
+        let mut field_catalog_schema = Schema::new();
+        field_catalog_schema
+            .add_string_field("tblname", MAX_NAME)
+            .add_string_field("fldname", MAX_NAME)
+            .add_int_field("type")
+            .add_int_field("length")
+            .add_int_field("nullable")
+            .add_int_field("offset");
+
+        let table_manager = Self {
+            file_manager,
+            table_catalog_layout: Layout::new(table_catalog_schema.clone()),
+            field_catalog_layout: Layout::new(field_catalog_schema.clone()),
+            catalog_lock: Mutex::new(()),
+        };
+        if is_new {
+            table_manager.create_table(TABLE_CATALOG, table_catalog_schema)?;
+            table_manager.create_table(FIELD_CATALOG, field_catalog_schema)?;
+        }
+        Ok(table_manager)
+    }
+
+    pub(crate) fn file_manager(&self) -> &Arc<FileManager> {
+        &self.file_manager
+    }
+
+    /// Held by whoever writes to a catalog table, from the duplicate check until the last row is written.
+    pub(crate) fn lock_catalog(&self) -> MutexGuard<'_, ()> {
+        self.catalog_lock.lock().expect("catalog mutex poisoned")
+    }
+
+    pub(crate) fn create_table(&self, table_name: &str, schema: Schema) -> Result<()> {
+        check_name("Table", table_name)?;
+        for field_name in schema.fields() {
+            check_name("Field", field_name)?;
+        }
+        let layout = Layout::new(schema);
+        // Checked here and not only when the table is scanned, so a table nobody can insert into never makes it into the catalog.
+        if !RecordPage::fits_in_empty_page(self.file_manager.block_size(), layout.slot_size()) {
+            return Err(StormDbError::OutOfBound(format!(
+                "Records of {} are {} bytes, too big for a {} byte block",
+                table_name,
+                layout.slot_size(),
+                self.file_manager.block_size()
+            )));
+        }
+        let _catalog = self.lock_catalog();
+        if self.find_table(table_name)?.is_some() {
+            return Err(StormDbError::AlreadyExists(format!(
+                "Table {} already exists",
+                table_name
+            )));
+        }
+
+        let mut field_catalog = self.scan(FIELD_CATALOG, &self.field_catalog_layout)?;
+        // Left over from a create that didn't get to its tblcat row.
+        while field_catalog.next()? {
+            if field_catalog.get_string("tblname")? == table_name {
+                field_catalog.delete()?;
+            }
+        }
+        for field_name in layout.schema().fields() {
+            let info = layout
+                .schema()
+                .info(field_name)
+                .expect("field comes from the schema");
+            field_catalog.insert()?;
+            field_catalog.set_string("tblname", table_name.to_string())?;
+            field_catalog.set_string("fldname", field_name.clone())?;
+            field_catalog.set_int("type", info.field_type().code() as i32)?;
+            field_catalog.set_int("length", info.length() as i32)?;
+            field_catalog.set_int("nullable", info.nullable() as i32)?;
+            field_catalog.set_int(
+                "offset",
+                layout
+                    .offset(field_name)
+                    .expect("field comes from the schema") as i32,
+            )?;
+        }
+
+        let mut table_catalog = self.scan(TABLE_CATALOG, &self.table_catalog_layout)?;
+        table_catalog.insert()?;
+        table_catalog.set_string("tblname", table_name.to_string())?;
+        table_catalog.set_int("slotsize", layout.slot_size() as i32)
+    }
+
+    /// Returns the layout of the table, reading it back from the catalog.
+    pub(crate) fn get_layout(&self, table_name: &str) -> Result<Layout> {
+        let Some(slot_size) = self.find_table(table_name)? else {
+            return Err(StormDbError::NotFound(format!("No table {}", table_name)));
+        };
+
+        let mut schema = Schema::new();
+        let mut offsets = HashMap::new();
+        let mut field_catalog = self.scan(FIELD_CATALOG, &self.field_catalog_layout)?;
+        while field_catalog.next()? {
+            if field_catalog.get_string("tblname")? != table_name {
+                continue;
+            }
+            let field_name = field_catalog.get_string("fldname")?;
+            let field_type = FieldType::from_code(field_catalog.get_int("type")? as u64)?;
+            let length = field_catalog.get_int("length")? as usize;
+            let nullable = field_catalog.get_int("nullable")? != 0;
+            schema.add_field(&field_name, field_type, length, nullable);
+            offsets.insert(field_name, field_catalog.get_int("offset")? as usize);
+        }
+        Ok(Layout::with_offsets(schema, offsets, slot_size))
+    }
+
+    // Slot size of the table if it's in the catalog.
+    fn find_table(&self, table_name: &str) -> Result<Option<usize>> {
+        let mut table_catalog = self.scan(TABLE_CATALOG, &self.table_catalog_layout)?;
+        while table_catalog.next()? {
+            if table_catalog.get_string("tblname")? == table_name {
+                return Ok(Some(table_catalog.get_int("slotsize")? as usize));
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn scan(&self, table_name: &str, layout: &Layout) -> Result<TableScan> {
+        TableScan::new(self.file_manager.clone(), table_name, layout.clone())
+    }
+}
+
+/// Names go into MAX_NAME byte catalog fields, better to say so up front than to fail halfway through writing a catalog row.
+pub(crate) fn check_name(kind: &str, name: &str) -> Result<()> {
+    if name.len() > MAX_NAME {
+        return Err(StormDbError::OutOfBound(format!(
+            "{} name {} is longer than {} bytes",
+            kind, name, MAX_NAME
+        )));
+    }
+    Ok(())
+}
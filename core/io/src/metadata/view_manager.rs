@@ -0,0 +1,80 @@
+/*
+View Manager API as per the book:
+  public ViewMgr(boolean isNew, TableMgr tblMgr, Transaction tx);
+  public void createView(String vname, String vdef, Transaction tx);
+  public String getViewDef(String vname, Transaction tx);
+
+Views are stored as their definition (the SQL text) in viewcat(viewname, viewdef).
+*/
+
+use crate::{
+    StormDbError,
+    error::Result,
+    record::{Layout, Schema},
+};
+
+use super::table_manager::{MAX_NAME, TableManager, check_name};
+
+/// Longest view definition the catalog can hold, in bytes.
+pub const MAX_VIEW_DEF: usize = 100;
+
+const VIEW_CATALOG: &str = "viewcat";
+
+pub(crate) struct ViewManager {
+    layout: Layout,
+}
+
+impl ViewManager {
+    pub(crate) fn new(table_manager: &TableManager, is_new: bool) -> Result<Self> {
+        let mut schema = Schema::new();
+        schema
+            .add_string_field("viewname", MAX_NAME)
+            .add_string_field("viewdef", MAX_VIEW_DEF);
+        if is_new {
+            table_manager.create_table(VIEW_CATALOG, schema.clone())?;
+        }
+        Ok(Self {
+            layout: Layout::new(schema),
+        })
+    }
+
+    pub(crate) fn create_view(
+        &self,
+        table_manager: &TableManager,
+        view_name: &str,
+        view_def: &str,
+    ) -> Result<()> {
+        check_name("View", view_name)?;
+        if view_def.len() > MAX_VIEW_DEF {
+            return Err(StormDbError::OutOfBound(format!(
+                "View definition of {} is longer than {} bytes",
+                view_name, MAX_VIEW_DEF
+            )));
+        }
+        let _catalog = table_manager.lock_catalog();
+        if self.get_view_def(table_manager, view_name)?.is_some() {
+            return Err(StormDbError::AlreadyExists(format!(
+                "View {} already exists",
+                view_name
+            )));
+        }
+        let mut view_catalog = table_manager.scan(VIEW_CATALOG, &self.layout)?;
+        view_catalog.insert()?;
+        view_catalog.set_string("viewname", view_name.to_string())?;
+        view_catalog.set_string("viewdef", view_def.to_string())
+    }
+
+    pub(crate) fn get_view_def(
+        &self,
+        table_manager: &TableManager,
+        view_name: &str,
+    ) -> Result<Option<String>> {
+        let mut view_catalog = table_manager.scan(VIEW_CATALOG, &self.layout)?;
+        while view_catalog.next()? {
+            if view_catalog.get_string("viewname")? == view_name {
+                return Ok(Some(view_catalog.get_string("viewdef")?));
+            }
+        }
+        Ok(None)
+    }
+}
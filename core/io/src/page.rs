@@ -15,8 +15,8 @@ pub struct Page {
 
 impl Page {
     // This is prolly not the right thing to do. We'll see.
-    const I32_SIZE: usize = std::mem::size_of::<i32>();
-    const U32_SIZE: usize = std::mem::size_of::<u32>();
+    pub(crate) const I32_SIZE: usize = std::mem::size_of::<i32>();
+    pub(crate) const U32_SIZE: usize = std::mem::size_of::<u32>();
 
     pub fn builder() -> PageBuilder {
         PageBuilder::new()
@@ -158,7 +158,10 @@ impl Page {
             .get((offset + sz)..(offset + sz + varint as usize))
         {
             Some(bytes) => Ok(bytes.into()),
-            None => return Err(StormDbError::Corrupt("Invalid String.".to_string())),
+            None => Err(StormDbError::Corrupt {
+                block: None,
+                message: "Invalid String.".to_string(),
+            }),
         }
     }
 
@@ -249,7 +252,7 @@ impl Page {
     /// ```
     pub fn read_string(&self, offset: usize) -> Result<String> {
         let string_bytes = self.read_bytes(offset)?;
-        Ok(String::from_utf8(string_bytes).map_err(|_| StormDbError::InvalidUtf8)?)
+        String::from_utf8(string_bytes).map_err(|_| StormDbError::InvalidUtf8)
     }
 
     /// Write the string to the given offset.
@@ -321,8 +324,17 @@ impl Page {
     /// assert!(string_size_on_page > 0);
     /// ```
     pub fn max_len(string: &str) -> usize {
-        let string_bytes = string.as_bytes();
-        get_varint_len(string_bytes.len() as u64) + string_bytes.len()
+        Self::max_bytes_len(string.len())
+    }
+
+    /// Returns the lenght in bytes storing `len` bytes with `write_bytes` would take.
+    /// ```
+    /// use file_manager::Page;
+    ///
+    /// assert_eq!(Page::max_bytes_len(10), Page::max_len("0123456789"));
+    /// ```
+    pub fn max_bytes_len(len: usize) -> usize {
+        get_varint_len(len as u64) + len
     }
 }
 
@@ -392,6 +404,12 @@ impl PageBuilder {
     }
 }
 
+impl Default for PageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use rstest::rstest;
@@ -520,10 +538,10 @@ mod test {
         page.write_bool(25, true)?;
         page.write_bool(49, false)?; // Last valid index
 
-        assert_eq!(page.read_bool(0)?, true);
-        assert_eq!(page.read_bool(1)?, false);
-        assert_eq!(page.read_bool(25)?, true);
-        assert_eq!(page.read_bool(49)?, false);
+        assert!(page.read_bool(0)?);
+        assert!(!page.read_bool(1)?);
+        assert!(page.read_bool(25)?);
+        assert!(!page.read_bool(49)?);
 
         Ok(())
     }
@@ -534,11 +552,11 @@ mod test {
 
         // Write true, then overwrite with false
         page.write_bool(10, true)?;
-        assert_eq!(page.read_bool(10)?, true);
+        assert!(page.read_bool(10)?);
         assert_eq!(page.bytes()[10], 1u8);
 
         page.write_bool(10, false)?;
-        assert_eq!(page.read_bool(10)?, false);
+        assert!(!page.read_bool(10)?);
         assert_eq!(page.bytes()[10], 0u8);
 
         Ok(())
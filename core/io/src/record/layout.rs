@@ -0,0 +1,201 @@
+/*
+Layout API as per the book:
+  public Layout(Schema schema);
+  public Layout(Schema schema, Map<String,Integer> offsets, int slotSize);
+  public Schema schema();
+  public int offset(String fldname);
+  public int slotSize();
+
+Where every field of a record sits. A record (what goes into a RecordPage slot) looks like:
+
+    | null bitmap | field 0 | field 1 | ...
+
+The null bitmap has one bit per nullable field and is left out when there are none. Ints take Page::I32_SIZE, strings and bytes take
+the most write_bytes could need for their length (Page::max_bytes_len, a varint length followed by the bytes). Every record of a table
+ends up the same size, so a field can be updated in place without moving the record.
+*/
+
+use std::collections::HashMap;
+
+use crate::{StormDbError, error::Result, page::Page};
+
+use super::{
+    push_varint,
+    schema::{FieldType, Schema},
+    take_varint,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    schema: Schema,
+    offsets: HashMap<String, usize>,
+    // Bit index in the null bitmap for every nullable field.
+    null_bits: HashMap<String, usize>,
+    slot_size: usize,
+}
+
+impl Layout {
+    /// Works out the offsets for a new table.
+    pub fn new(schema: Schema) -> Self {
+        let null_bits = Self::null_bits(&schema);
+        let mut offsets = HashMap::new();
+        let mut offset = null_bits.len().div_ceil(8);
+        for field_name in schema.fields() {
+            offsets.insert(field_name.clone(), offset);
+            offset += Self::field_size(&schema, field_name);
+        }
+        Self {
+            schema,
+            offsets,
+            null_bits,
+            slot_size: offset,
+        }
+    }
+
+    /// For a layout that was already worked out, read back from the catalog for one.
+    pub fn with_offsets(schema: Schema, offsets: HashMap<String, usize>, slot_size: usize) -> Self {
+        let null_bits = Self::null_bits(&schema);
+        Self {
+            schema,
+            offsets,
+            null_bits,
+            slot_size,
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Returns where the field starts inside a record.
+    pub fn offset(&self, field_name: &str) -> Option<usize> {
+        self.offsets.get(field_name).copied()
+    }
+
+    /// Returns the field's bit in the null bitmap, None for fields that can't be null.
+    pub fn null_bit(&self, field_name: &str) -> Option<usize> {
+        self.null_bits.get(field_name).copied()
+    }
+
+    /// Returns the size of a record.
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Returns how many bytes a field takes up in a record at most.
+    pub fn field_size(schema: &Schema, field_name: &str) -> usize {
+        match schema.info(field_name) {
+            Some(info) => match info.field_type() {
+                FieldType::Int => Page::I32_SIZE,
+                FieldType::String | FieldType::Bytes => Page::max_bytes_len(info.length()),
+            },
+            None => 0,
+        }
+    }
+
+    fn null_bits(schema: &Schema) -> HashMap<String, usize> {
+        schema
+            .fields()
+            .iter()
+            .filter(|field_name| schema.is_nullable(field_name) == Some(true))
+            .enumerate()
+            .map(|(bit, field_name)| (field_name.clone(), bit))
+            .collect()
+    }
+
+    /// Serializes the layout: the schema, the slot size and the offset of every field in schema order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.schema.encode(&mut bytes);
+        push_varint(&mut bytes, self.slot_size as u64);
+        for field_name in self.schema.fields() {
+            push_varint(&mut bytes, self.offsets[field_name] as u64);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        let bytes = &mut bytes;
+        let schema = Schema::decode(bytes)?;
+        let slot_size = take_varint(bytes)? as usize;
+        let mut offsets = HashMap::new();
+        for field_name in schema.fields() {
+            offsets.insert(field_name.clone(), take_varint(bytes)? as usize);
+        }
+        if !bytes.is_empty() {
+            return Err(StormDbError::Corrupt {
+                block: None,
+                message: format!("{} bytes left over after the layout", bytes.len()),
+            });
+        }
+        Ok(Self::with_offsets(schema, offsets, slot_size))
+    }
+
+    /// Writes the layout to the page with `Page::write_bytes` and returns how many bytes that took.
+    pub fn write_to_page(&self, page: &mut Page, offset: usize) -> Result<usize> {
+        let bytes = self.to_bytes();
+        let length = Page::max_bytes_len(bytes.len());
+        page.write_bytes(offset, bytes)?;
+        Ok(length)
+    }
+
+    pub fn read_from_page(page: &Page, offset: usize) -> Result<Self> {
+        Self::from_bytes(&page.read_bytes(offset)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn student_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .add_int_field("id")
+            .add_field("name", FieldType::String, 9, true)
+            .add_string_field("major", 200)
+            .add_field("photo", FieldType::Bytes, 16, true);
+        schema
+    }
+
+    #[test]
+    fn test_offsets() {
+        let layout = Layout::new(student_schema());
+        // One byte of null bitmap for name and photo.
+        assert_eq!(layout.offset("id"), Some(1));
+        assert_eq!(layout.offset("name"), Some(1 + Page::I32_SIZE));
+        // 9 bytes plus a one byte length.
+        assert_eq!(layout.offset("major"), Some(15));
+        // 200 needs a two byte varint.
+        assert_eq!(layout.offset("photo"), Some(15 + 202));
+        assert_eq!(layout.slot_size(), 217 + 17);
+        assert_eq!(layout.null_bit("name"), Some(0));
+        assert_eq!(layout.null_bit("photo"), Some(1));
+        assert_eq!(layout.null_bit("id"), None);
+        assert_eq!(layout.offset("grade"), None);
+
+        let mut no_nulls = Schema::new();
+        no_nulls.add_int_field("a").add_int_field("b");
+        let layout = Layout::new(no_nulls);
+        assert_eq!(layout.offset("a"), Some(0));
+        assert_eq!(layout.slot_size(), 2 * Page::I32_SIZE);
+    }
+
+    #[test]
+    fn test_page_round_trip() {
+        let layout = Layout::new(student_schema());
+        let mut page = Page::builder().with_block_size(128).with_buffer().build();
+        let length = layout
+            .write_to_page(&mut page, 10)
+            .expect("failed to write layout");
+        assert_eq!(length, Page::max_bytes_len(layout.to_bytes().len()));
+        assert_eq!(
+            Layout::read_from_page(&page, 10).expect("failed to read layout"),
+            layout
+        );
+
+        // Doesn't fit.
+        let mut small_page = Page::builder().with_block_size(16).with_buffer().build();
+        assert!(layout.write_to_page(&mut small_page, 0).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+/*
+Record management, how rows of a table are laid out in blocks.
+*/
+mod layout;
+mod record_page;
+mod schema;
+mod table_scan;
+
+pub use layout::Layout;
+pub use record_page::RecordPage;
+pub use schema::{FieldInfo, FieldType, Schema};
+pub use table_scan::{Rid, TableScan};
+
+use crate::{
+    error::Result,
+    varint::{read_varint, write_varint},
+};
+
+// Helpers for the varint encoded schema and layout.
+fn push_varint(bytes: &mut Vec<u8>, value: u64) {
+    let mut varint = [0u8; 9];
+    let length = write_varint(&mut varint, value);
+    bytes.extend_from_slice(&varint[..length]);
+}
+
+fn take_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let (value, length) = read_varint(bytes)?;
+    *bytes = &bytes[length..];
+    Ok(value)
+}
@@ -0,0 +1,387 @@
+/*
+Record Page API as per the book:
+  public RecordPage(Transaction tx, BlockId blk, Layout layout);
+  public int getInt(int slot, String fldname);
+  public String getString(int slot, String fldname);
+  public void setInt(int slot, String fldname, int val);
+  public void setString(int slot, String fldname, String val);
+  public void delete(int slot);
+  public void format();
+  public int nextAfter(int slot);
+  public int insertAfter(int slot);
+
+The book gives every slot the same size and puts it at slot * slotsize. I went with a slotted page instead (like SQLite and Postgres),
+so the same page works for fixed size records and variable length ones:
+
+    | slot count (u32) | free end (u32) | slot 0 | slot 1 | ... ->     free space     <- ... | record 1 | record 0 |
+
+Each slot is a used flag and the offset of its record. Records are written with Page::write_bytes, a varint length and the bytes,
+and grow from the end of the page towards the slot directory. A slot keeps its number for as long as the record lives, even across
+compaction, so a (block, slot) pair keeps pointing at the same record.
+Field level access (ints, strings at offsets inside the record) is up to whoever knows the layout, this only deals with whole records.
+A freshly appended (all zero) block is a valid empty record page, there's no separate format step.
+*/
+
+use crate::{
+    StormDbError,
+    error::Result,
+    page::Page,
+    varint::{get_varint_len, read_varint},
+};
+
+const SLOT_COUNT_OFFSET: usize = 0;
+const FREE_END_OFFSET: usize = Page::U32_SIZE;
+const HEADER_SIZE: usize = 2 * Page::U32_SIZE;
+// Used flag followed by the record offset.
+const SLOT_SIZE: usize = 1 + Page::U32_SIZE;
+
+/// A page of records addressed by slot number.
+pub struct RecordPage {
+    page: Page,
+}
+
+impl RecordPage {
+    pub fn new(page: Page) -> Self {
+        Self { page }
+    }
+
+    /// Returns the underlying page, to write it back to its block.
+    pub fn page(&self) -> &Page {
+        &self.page
+    }
+
+    pub fn page_mut(&mut self) -> &mut Page {
+        &mut self.page
+    }
+
+    pub fn into_page(self) -> Page {
+        self.page
+    }
+
+    /// Returns how many bytes it takes to store a record of the given length, slot not included.
+    pub fn stored_len(record_len: usize) -> usize {
+        get_varint_len(record_len as u64) + record_len
+    }
+
+    /// Returns whether a record of the given length fits in an empty page of the block size. If it doesn't, it fits nowhere.
+    pub fn fits_in_empty_page(block_size: usize, record_len: usize) -> bool {
+        Self::directory_end(1) + Self::stored_len(record_len) <= block_size
+    }
+
+    /// Returns the number of slots in the directory, used or not.
+    pub fn slot_count(&self) -> Result<usize> {
+        Ok(self.page.read_u32(SLOT_COUNT_OFFSET)? as usize)
+    }
+
+    /// Returns whether the slot holds a record.
+    pub fn is_used(&self, slot: usize) -> Result<bool> {
+        if slot >= self.slot_count()? {
+            return Ok(false);
+        }
+        self.page.read_bool(Self::slot_offset(slot))
+    }
+
+    /// Returns the slot of the first record after the given one, or the first record at all for None.
+    pub fn next_used_slot(&self, after: Option<usize>) -> Result<Option<usize>> {
+        let start = after.map_or(0, |slot| slot + 1);
+        for slot in start..self.slot_count()? {
+            if self.is_used(slot)? {
+                return Ok(Some(slot));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the record in the slot.
+    pub fn record(&self, slot: usize) -> Result<Vec<u8>> {
+        self.page.read_bytes(self.stored_offset(slot)?)
+    }
+
+    /// Returns where in the page the record's bytes start (past the length), for reading fields in place.
+    pub fn record_offset(&self, slot: usize) -> Result<usize> {
+        let offset = self.stored_offset(slot)?;
+        let (_, length_size) = read_varint(&self.page.bytes()[offset..])?;
+        Ok(offset + length_size)
+    }
+
+    /// Stores the record in the first empty slot and returns the slot, None if the page doesn't have room for it even after compacting.
+    pub fn insert(&mut self, record: &[u8]) -> Result<Option<usize>> {
+        let stored_len = Self::stored_len(record.len());
+        if !self.fits(self.first_empty_slot()?, stored_len)? {
+            self.compact()?;
+            if !self.fits(self.first_empty_slot()?, stored_len)? {
+                return Ok(None);
+            }
+        }
+        let slot = self.first_empty_slot()?;
+        self.place(slot, record)?;
+        Ok(Some(slot))
+    }
+
+    /// Replaces the record in the slot. A record that grows may need the page compacted, and one that doesn't fit anymore at all
+    /// is an error with the old record left in place.
+    pub fn update(&mut self, slot: usize, record: &[u8]) -> Result<()> {
+        let offset = self.stored_offset(slot)?;
+        let old_record = self.page.read_bytes(offset)?;
+        let stored_len = Self::stored_len(record.len());
+        if stored_len <= Self::stored_len(old_record.len()) {
+            // The leftover bytes at the end become a hole until the next compaction.
+            return self.page.write_bytes(offset, record.to_vec());
+        }
+
+        self.set_slot(slot, false, offset)?;
+        if !self.fits(slot, stored_len)? {
+            self.compact()?;
+            if !self.fits(slot, stored_len)? {
+                // It was there a moment ago, so after compacting there's room for it again.
+                self.place(slot, &old_record)?;
+                return Err(StormDbError::OutOfBound(format!(
+                    "Record of {} bytes doesn't fit in slot {} anymore",
+                    record.len(),
+                    slot
+                )));
+            }
+        }
+        self.place(slot, record)
+    }
+
+    /// Empties the slot. The space comes back on the next compaction.
+    pub fn delete(&mut self, slot: usize) -> Result<()> {
+        let offset = self.stored_offset(slot)?;
+        self.set_slot(slot, false, offset)
+    }
+
+    /// Returns the free space between the slot directory and the records, holes left by deletes and updates not included.
+    pub fn free_space(&self) -> Result<usize> {
+        Ok(self.free_end()? - Self::directory_end(self.slot_count()?))
+    }
+
+    /// Moves every record to the end of the page so the holes become one block of free space. Empty slots at the end of the directory
+    /// go away too, slot numbers of records don't change.
+    pub fn compact(&mut self) -> Result<()> {
+        let mut records = Vec::new();
+        let mut slot = None;
+        while let Some(used) = self.next_used_slot(slot)? {
+            records.push((used, self.record(used)?));
+            slot = Some(used);
+        }
+
+        let slot_count = records.last().map_or(0, |(slot, _)| slot + 1);
+        self.page.write_u32(SLOT_COUNT_OFFSET, slot_count as u32)?;
+        self.set_free_end(self.page.block_size)?;
+        for (slot, record) in &records {
+            self.place(*slot, record)?;
+        }
+        let directory_end = Self::directory_end(slot_count);
+        let free_end = self.free_end()?;
+        self.page.byte_buffer[directory_end..free_end].fill(0);
+        Ok(())
+    }
+
+    fn slot_offset(slot: usize) -> usize {
+        HEADER_SIZE + slot * SLOT_SIZE
+    }
+
+    fn directory_end(slot_count: usize) -> usize {
+        Self::slot_offset(slot_count)
+    }
+
+    // A zeroed page hasn't had anything written to it, everything past the header is free.
+    fn free_end(&self) -> Result<usize> {
+        match self.page.read_u32(FREE_END_OFFSET)? {
+            0 => Ok(self.page.block_size),
+            free_end => Ok(free_end as usize),
+        }
+    }
+
+    fn set_free_end(&mut self, free_end: usize) -> Result<()> {
+        self.page.write_u32(FREE_END_OFFSET, free_end as u32)
+    }
+
+    // Offset of the record's length, where write_bytes put it.
+    fn stored_offset(&self, slot: usize) -> Result<usize> {
+        if !self.is_used(slot)? {
+            return Err(StormDbError::OutOfBound(format!(
+                "Slot {} doesn't hold a record",
+                slot
+            )));
+        }
+        Ok(self.page.read_u32(Self::slot_offset(slot) + 1)? as usize)
+    }
+
+    fn set_slot(&mut self, slot: usize, used: bool, offset: usize) -> Result<()> {
+        self.page.write_bool(Self::slot_offset(slot), used)?;
+        self.page
+            .write_u32(Self::slot_offset(slot) + 1, offset as u32)
+    }
+
+    fn first_empty_slot(&self) -> Result<usize> {
+        let slot_count = self.slot_count()?;
+        for slot in 0..slot_count {
+            if !self.is_used(slot)? {
+                return Ok(slot);
+            }
+        }
+        Ok(slot_count)
+    }
+
+    // Whether a record of stored_len fits in the free space with the slot directory reaching at least up to slot.
+    fn fits(&self, slot: usize, stored_len: usize) -> Result<bool> {
+        let slot_count = self.slot_count()?.max(slot + 1);
+        Ok(Self::directory_end(slot_count) + stored_len <= self.free_end()?)
+    }
+
+    // Writes the record right below the free end and points the slot at it. Caller made sure it fits.
+    fn place(&mut self, slot: usize, record: &[u8]) -> Result<()> {
+        let offset = self.free_end()? - Self::stored_len(record.len());
+        self.page.write_bytes(offset, record.to_vec())?;
+        self.set_free_end(offset)?;
+        if slot >= self.slot_count()? {
+            self.page.write_u32(SLOT_COUNT_OFFSET, (slot + 1) as u32)?;
+        }
+        self.set_slot(slot, true, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const BLOCK_SIZE: usize = 64;
+
+    fn empty_record_page() -> RecordPage {
+        RecordPage::new(
+            Page::builder()
+                .with_block_size(BLOCK_SIZE)
+                .with_buffer()
+                .build(),
+        )
+    }
+
+    fn used_slots(record_page: &RecordPage) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut slot = None;
+        while let Some(used) = record_page
+            .next_used_slot(slot)
+            .expect("failed to get slot")
+        {
+            slots.push(used);
+            slot = Some(used);
+        }
+        slots
+    }
+
+    #[test]
+    fn test_insert_delete_and_reuse_slots() {
+        let mut record_page = empty_record_page();
+        assert_eq!(used_slots(&record_page), vec![]);
+        assert_eq!(
+            record_page.free_space().expect("failed to get free space"),
+            BLOCK_SIZE - HEADER_SIZE
+        );
+
+        for (expected_slot, record) in [b"first".as_slice(), b"second", b"third"]
+            .iter()
+            .enumerate()
+        {
+            let slot = record_page.insert(record).expect("failed to insert");
+            assert_eq!(slot, Some(expected_slot));
+        }
+        record_page.delete(1).expect("failed to delete");
+        assert_eq!(used_slots(&record_page), vec![0, 2]);
+        assert!(record_page.record(1).is_err());
+
+        // The empty slot gets handed out again.
+        assert_eq!(
+            record_page.insert(b"fourth").expect("failed to insert"),
+            Some(1)
+        );
+        assert_eq!(record_page.record(1).expect("failed to read"), b"fourth");
+        assert_eq!(record_page.record(2).expect("failed to read"), b"third");
+
+        let offset = record_page.record_offset(0).expect("failed to get offset");
+        assert_eq!(&record_page.page().bytes()[offset..offset + 5], b"first");
+    }
+
+    #[test]
+    fn test_full_page_compacts_before_giving_up() {
+        let mut record_page = empty_record_page();
+        let record = [7u8; 10];
+        let mut slots = Vec::new();
+        while let Some(slot) = record_page.insert(&record).expect("failed to insert") {
+            slots.push(slot);
+        }
+        // Header + 3 * (slot + record) = 8 + 3 * 16 = 56, a fourth one needs 16 more.
+        assert_eq!(slots, vec![0, 1, 2]);
+
+        record_page.delete(0).expect("failed to delete");
+        record_page.delete(2).expect("failed to delete");
+        assert_eq!(
+            record_page.free_space().expect("failed to get free space"),
+            8
+        );
+        // Doesn't fit as it is, but does once the holes are squeezed out.
+        let bigger = [9u8; 20];
+        assert_eq!(
+            record_page.insert(&bigger).expect("failed to insert"),
+            Some(0)
+        );
+        assert_eq!(record_page.record(0).expect("failed to read"), bigger);
+        assert_eq!(record_page.record(1).expect("failed to read"), record);
+        assert_eq!(used_slots(&record_page), vec![0, 1]);
+    }
+
+    #[rstest]
+    #[case::shrinks(b"tiny".as_slice())]
+    #[case::same_size(b"mid-sized".as_slice())]
+    #[case::grows(b"a good deal longer record".as_slice())]
+    fn test_update(#[case] new_record: &[u8]) {
+        let mut record_page = empty_record_page();
+        record_page.insert(b"first").expect("failed to insert");
+        record_page.insert(b"mid-sized").expect("failed to insert");
+        record_page.insert(b"last").expect("failed to insert");
+
+        record_page.update(1, new_record).expect("failed to update");
+        assert_eq!(record_page.record(0).expect("failed to read"), b"first");
+        assert_eq!(record_page.record(1).expect("failed to read"), new_record);
+        assert_eq!(record_page.record(2).expect("failed to read"), b"last");
+    }
+
+    #[test]
+    fn test_update_that_does_not_fit_keeps_old_record() {
+        let mut record_page = empty_record_page();
+        record_page.insert(b"first").expect("failed to insert");
+        record_page.insert(b"second").expect("failed to insert");
+
+        let too_big = [1u8; BLOCK_SIZE];
+        assert!(matches!(
+            record_page.update(0, &too_big),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert_eq!(record_page.record(0).expect("failed to read"), b"first");
+        assert_eq!(record_page.record(1).expect("failed to read"), b"second");
+    }
+
+    #[test]
+    fn test_compact_drops_trailing_empty_slots() {
+        let mut record_page = empty_record_page();
+        for record in [b"a".as_slice(), b"b", b"c"] {
+            record_page.insert(record).expect("failed to insert");
+        }
+        record_page.delete(0).expect("failed to delete");
+        record_page.delete(2).expect("failed to delete");
+        record_page.compact().expect("failed to compact");
+
+        assert_eq!(
+            record_page.slot_count().expect("failed to get slot count"),
+            2
+        );
+        assert_eq!(used_slots(&record_page), vec![1]);
+        assert_eq!(record_page.record(1).expect("failed to read"), b"b");
+        assert_eq!(
+            record_page.free_space().expect("failed to get free space"),
+            BLOCK_SIZE - HEADER_SIZE - 2 * SLOT_SIZE - RecordPage::stored_len(1)
+        );
+    }
+}
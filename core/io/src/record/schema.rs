@@ -0,0 +1,241 @@
+/*
+Schema API as per the book:
+  public Schema();
+  public void addField(String fldname, int type, int length);
+  public void addIntField(String fldname);
+  public void addStringField(String fldname, int length);
+  public void add(String fldname, Schema sch);
+  public void addAll(Schema sch);
+  public List<String> fields();
+  public boolean hasField(String fldname);
+  public int type(String fldname);
+  public int length(String fldname);
+
+The book uses the java.sql.Types ints for the type, an enum is the obvious thing here. I also added a bytes type and nullable fields.
+Lengths are in bytes, not characters like in the book. A string field of length 10 holds 10 bytes of UTF-8.
+*/
+
+use std::collections::HashMap;
+
+use crate::{StormDbError, error::Result};
+
+use super::{push_varint, take_varint};
+
+/// Type of a field in a table row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Int,
+    String,
+    Bytes,
+}
+
+impl FieldType {
+    pub(crate) fn code(self) -> u64 {
+        match self {
+            FieldType::Int => 1,
+            FieldType::String => 2,
+            FieldType::Bytes => 3,
+        }
+    }
+
+    pub(crate) fn from_code(code: u64) -> Result<Self> {
+        match code {
+            1 => Ok(FieldType::Int),
+            2 => Ok(FieldType::String),
+            3 => Ok(FieldType::Bytes),
+            _ => Err(StormDbError::Corrupt {
+                block: None,
+                message: format!("Unknown field type {}", code),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    field_type: FieldType,
+    // Max number of bytes for strings and bytes, ignored for ints.
+    length: usize,
+    nullable: bool,
+}
+
+impl FieldInfo {
+    pub fn field_type(&self) -> FieldType {
+        self.field_type
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+}
+
+/// The fields of a table row, in the order they were added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    fields: Vec<String>,
+    info: HashMap<String, FieldInfo>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field, or replaces the type of one that's already there (it keeps its place).
+    pub fn add_field(
+        &mut self,
+        field_name: &str,
+        field_type: FieldType,
+        length: usize,
+        nullable: bool,
+    ) -> &mut Self {
+        let info = FieldInfo {
+            field_type,
+            length,
+            nullable,
+        };
+        if self.info.insert(field_name.to_string(), info).is_none() {
+            self.fields.push(field_name.to_string());
+        }
+        self
+    }
+
+    pub fn add_int_field(&mut self, field_name: &str) -> &mut Self {
+        self.add_field(field_name, FieldType::Int, 0, false)
+    }
+
+    /// Adds a string field holding up to `length` bytes.
+    pub fn add_string_field(&mut self, field_name: &str, length: usize) -> &mut Self {
+        self.add_field(field_name, FieldType::String, length, false)
+    }
+
+    /// Adds a bytes field holding up to `length` bytes.
+    pub fn add_bytes_field(&mut self, field_name: &str, length: usize) -> &mut Self {
+        self.add_field(field_name, FieldType::Bytes, length, false)
+    }
+
+    /// Adds the field the way it is in the other schema. Does nothing if the other schema doesn't have it.
+    pub fn add(&mut self, field_name: &str, schema: &Schema) -> &mut Self {
+        if let Some(info) = schema.info(field_name) {
+            self.add_field(field_name, info.field_type, info.length, info.nullable);
+        }
+        self
+    }
+
+    pub fn add_all(&mut self, schema: &Schema) -> &mut Self {
+        for field_name in schema.fields() {
+            self.add(field_name, schema);
+        }
+        self
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn has_field(&self, field_name: &str) -> bool {
+        self.info.contains_key(field_name)
+    }
+
+    pub fn info(&self, field_name: &str) -> Option<&FieldInfo> {
+        self.info.get(field_name)
+    }
+
+    pub fn field_type(&self, field_name: &str) -> Option<FieldType> {
+        self.info(field_name).map(FieldInfo::field_type)
+    }
+
+    pub fn length(&self, field_name: &str) -> Option<usize> {
+        self.info(field_name).map(FieldInfo::length)
+    }
+
+    pub fn is_nullable(&self, field_name: &str) -> Option<bool> {
+        self.info(field_name).map(FieldInfo::nullable)
+    }
+
+    // field count, then for every field: name, type, length, nullable. All varints except the name which goes length first.
+    pub(crate) fn encode(&self, bytes: &mut Vec<u8>) {
+        push_varint(bytes, self.fields.len() as u64);
+        for field_name in &self.fields {
+            let info = self.info[field_name];
+            push_varint(bytes, field_name.len() as u64);
+            bytes.extend_from_slice(field_name.as_bytes());
+            push_varint(bytes, info.field_type.code());
+            push_varint(bytes, info.length as u64);
+            push_varint(bytes, info.nullable as u64);
+        }
+    }
+
+    pub(crate) fn decode(bytes: &mut &[u8]) -> Result<Self> {
+        let mut schema = Schema::new();
+        for _ in 0..take_varint(bytes)? {
+            let name_length = take_varint(bytes)? as usize;
+            let Some((name, rest)) = bytes.split_at_checked(name_length) else {
+                return Err(StormDbError::Corrupt {
+                    block: None,
+                    message: "Schema ends in the middle of a field name".to_string(),
+                });
+            };
+            let field_name = std::str::from_utf8(name).map_err(|_| StormDbError::InvalidUtf8)?;
+            *bytes = rest;
+            let field_type = FieldType::from_code(take_varint(bytes)?)?;
+            let length = take_varint(bytes)? as usize;
+            let nullable = take_varint(bytes)? != 0;
+            schema.add_field(field_name, field_type, length, nullable);
+        }
+        Ok(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_add_all() {
+        let mut student = Schema::new();
+        student
+            .add_int_field("id")
+            .add_string_field("name", 20)
+            .add_field("photo", FieldType::Bytes, 100, true);
+        // Replacing a field keeps it where it was.
+        student.add_string_field("name", 30);
+        assert_eq!(student.fields(), ["id", "name", "photo"]);
+        assert_eq!(student.length("name"), Some(30));
+        assert_eq!(student.is_nullable("photo"), Some(true));
+        assert_eq!(student.field_type("grade"), None);
+
+        let mut joined = Schema::new();
+        joined
+            .add_int_field("grade")
+            .add("name", &student)
+            .add("nope", &student);
+        joined.add_all(&student);
+        assert_eq!(joined.fields(), ["grade", "name", "id", "photo"]);
+        assert_eq!(joined.info("photo"), student.info("photo"));
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let mut schema = Schema::new();
+        schema
+            .add_int_field("id")
+            .add_field("name", FieldType::String, 200, true)
+            .add_bytes_field("blob", 3);
+        let mut bytes = Vec::new();
+        schema.encode(&mut bytes);
+
+        let mut rest = bytes.as_slice();
+        assert_eq!(Schema::decode(&mut rest).expect("failed to decode"), schema);
+        assert!(rest.is_empty());
+
+        // Cut off anywhere it's an error, not a panic.
+        for length in 1..bytes.len() {
+            assert!(Schema::decode(&mut &bytes[..length]).is_err());
+        }
+    }
+}
@@ -0,0 +1,497 @@
+/*
+Table Scan API as per the book:
+  public TableScan(Transaction tx, String tblname, Layout layout);
+  public void beforeFirst();
+  public boolean next();
+  public int getInt(String fldname);
+  public String getString(String fldname);
+  public boolean hasField(String fldname);
+  public void close();
+  public void setInt(String fldname, int val);
+  public void setString(String fldname, String val);
+  public void insert();
+  public void delete();
+  public void moveToRid(RID rid);
+  public RID getRid();
+
+Walks the records of a table, block after block. The table lives in `<table name>.tbl`, every block is a RecordPage and every record is
+laid out by the Layout. No transactions yet, the scan reads and writes blocks through the FileManager directly and every change is
+written back right away, so there's no close that has to be called.
+*/
+
+use std::sync::Arc;
+
+use crate::{
+    BlockMetadata, FileManager, StormDbError,
+    error::Result,
+    page::Page,
+    record::{FieldType, Layout, RecordPage},
+};
+
+/// Record id, the block of the table a record is in and its slot in there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rid {
+    pub block: usize,
+    pub slot: usize,
+}
+
+pub struct TableScan {
+    file_manager: Arc<FileManager>,
+    file_name: String,
+    layout: Layout,
+    block: BlockMetadata,
+    record_page: RecordPage,
+    // None is before the first record of the block.
+    slot: Option<usize>,
+}
+
+impl TableScan {
+    /// Opens a scan on the table, positioned before the first record. A table without any blocks gets an empty one.
+    /// Fails if a record of the layout doesn't fit in a block, insert would never find room for it.
+    pub fn new(file_manager: Arc<FileManager>, table_name: &str, layout: Layout) -> Result<Self> {
+        if !RecordPage::fits_in_empty_page(file_manager.block_size(), layout.slot_size()) {
+            return Err(StormDbError::OutOfBound(format!(
+                "Records of {} are {} bytes, too big for a {} byte block",
+                table_name,
+                layout.slot_size(),
+                file_manager.block_size()
+            )));
+        }
+        let file_name = format!("{}.tbl", table_name);
+        if file_manager.length(&file_name)?.unwrap_or(0) == 0 {
+            file_manager.append(&file_name)?;
+        }
+        let page = Page::builder()
+            .with_block_size(file_manager.block_size())
+            .with_buffer()
+            .build();
+        let mut table_scan = Self {
+            file_manager,
+            block: BlockMetadata::new(&file_name, 0),
+            file_name,
+            layout,
+            record_page: RecordPage::new(page),
+            slot: None,
+        };
+        table_scan.move_to_block(0)?;
+        Ok(table_scan)
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn has_field(&self, field_name: &str) -> bool {
+        self.layout.schema().has_field(field_name)
+    }
+
+    pub fn before_first(&mut self) -> Result<()> {
+        self.move_to_block(0)
+    }
+
+    // Not an Iterator, the scan itself is the cursor and the values are read off it with get_*.
+    /// Moves to the next record, false once there aren't any left.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<bool> {
+        loop {
+            if let Some(slot) = self.record_page.next_used_slot(self.slot)? {
+                self.slot = Some(slot);
+                return Ok(true);
+            }
+            if self.is_last_block()? {
+                return Ok(false);
+            }
+            self.move_to_block(self.block.block_number() + 1)?;
+        }
+    }
+
+    pub fn get_int(&self, field_name: &str) -> Result<i32> {
+        let offset = self.field_offset(field_name, FieldType::Int)?;
+        self.record_page.page().read_int(offset)
+    }
+
+    pub fn get_string(&self, field_name: &str) -> Result<String> {
+        let offset = self.field_offset(field_name, FieldType::String)?;
+        self.record_page.page().read_string(offset)
+    }
+
+    pub fn get_bytes(&self, field_name: &str) -> Result<Vec<u8>> {
+        let offset = self.field_offset(field_name, FieldType::Bytes)?;
+        self.record_page.page().read_bytes(offset)
+    }
+
+    /// Returns whether the field of the current record is null. Fields that aren't nullable never are.
+    pub fn is_null(&self, field_name: &str) -> Result<bool> {
+        match self.null_bit_position(field_name)? {
+            Some((offset, mask)) => Ok(self.record_page.page().bytes()[offset] & mask != 0),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_int(&mut self, field_name: &str, value: i32) -> Result<()> {
+        let offset = self.field_offset(field_name, FieldType::Int)?;
+        self.record_page.page_mut().write_int(offset, value)?;
+        self.set_not_null(field_name)
+    }
+
+    pub fn set_string(&mut self, field_name: &str, value: String) -> Result<()> {
+        self.set_bytes_of_type(field_name, FieldType::String, value.into_bytes())
+    }
+
+    pub fn set_bytes(&mut self, field_name: &str, value: Vec<u8>) -> Result<()> {
+        self.set_bytes_of_type(field_name, FieldType::Bytes, value)
+    }
+
+    /// Sets the field of the current record to null. Only works on nullable fields.
+    pub fn set_null(&mut self, field_name: &str) -> Result<()> {
+        let Some((offset, mask)) = self.null_bit_position(field_name)? else {
+            return Err(StormDbError::OutOfBound(format!(
+                "Field {} can't be null",
+                field_name
+            )));
+        };
+        self.record_page.page_mut().byte_buffer[offset] |= mask;
+        self.write_back()
+    }
+
+    /// Adds a new record and moves to it. It goes into the first block from here on that has room, or a new block at the end.
+    /// Nullable fields start off null, everything else zero or empty.
+    pub fn insert(&mut self) -> Result<()> {
+        let record = vec![0u8; self.layout.slot_size()];
+        loop {
+            if let Some(slot) = self.record_page.insert(&record)? {
+                self.slot = Some(slot);
+                break;
+            }
+            if self.is_last_block()? {
+                let block = self.file_manager.append(&self.file_name)?;
+                self.move_to_block(block.block_number())?;
+            } else {
+                self.move_to_block(self.block.block_number() + 1)?;
+            }
+        }
+
+        let fields = self.layout.schema().fields().to_vec();
+        for field_name in &fields {
+            if let Some((offset, mask)) = self.null_bit_position(field_name)? {
+                self.record_page.page_mut().byte_buffer[offset] |= mask;
+            }
+        }
+        self.write_back()
+    }
+
+    /// Deletes the current record. The scan stays where it is, next moves on to the record after it.
+    pub fn delete(&mut self) -> Result<()> {
+        self.record_page.delete(self.current_slot()?)?;
+        self.write_back()
+    }
+
+    pub fn get_rid(&self) -> Result<Rid> {
+        Ok(Rid {
+            block: self.block.block_number(),
+            slot: self.current_slot()?,
+        })
+    }
+
+    /// Moves to the record with the given id.
+    pub fn move_to_rid(&mut self, rid: Rid) -> Result<()> {
+        self.move_to_block(rid.block)?;
+        if !self.record_page.is_used(rid.slot)? {
+            return Err(StormDbError::NotFound(format!(
+                "No record at slot {} of {}",
+                rid.slot, self.block
+            )));
+        }
+        self.slot = Some(rid.slot);
+        Ok(())
+    }
+
+    // A failed read leaves the page alone, so the scan only moves once the block is in. Otherwise it would write the old block's
+    // records into the new one.
+    fn move_to_block(&mut self, block_number: usize) -> Result<()> {
+        let block = BlockMetadata::new(&self.file_name, block_number);
+        self.file_manager
+            .read(&block, self.record_page.page_mut())?;
+        self.block = block;
+        self.slot = None;
+        Ok(())
+    }
+
+    fn is_last_block(&self) -> Result<bool> {
+        let block_count = self.file_manager.length(&self.file_name)?.unwrap_or(0);
+        Ok(self.block.block_number() + 1 >= block_count)
+    }
+
+    fn write_back(&mut self) -> Result<()> {
+        self.file_manager
+            .write(&self.block, self.record_page.page_mut())
+    }
+
+    fn current_slot(&self) -> Result<usize> {
+        self.slot.ok_or_else(|| {
+            StormDbError::NotFound(format!("Scan of {} isn't on a record", self.file_name))
+        })
+    }
+
+    // Offset of the field of the current record in the page, after checking the field has the type the caller expects.
+    fn field_offset(&self, field_name: &str, field_type: FieldType) -> Result<usize> {
+        let schema = self.layout.schema();
+        match (
+            schema.field_type(field_name),
+            self.layout.offset(field_name),
+        ) {
+            (Some(actual_type), Some(offset)) if actual_type == field_type => {
+                Ok(self.record_page.record_offset(self.current_slot()?)? + offset)
+            }
+            (Some(actual_type), _) => Err(StormDbError::OutOfBound(format!(
+                "Field {} is {:?}, not {:?}",
+                field_name, actual_type, field_type
+            ))),
+            (None, _) => Err(StormDbError::NotFound(format!(
+                "No field {} in {}",
+                field_name, self.file_name
+            ))),
+        }
+    }
+
+    // Byte offset in the page and bit mask of the field's null bit, None if it isn't nullable.
+    fn null_bit_position(&self, field_name: &str) -> Result<Option<(usize, u8)>> {
+        if !self.has_field(field_name) {
+            return Err(StormDbError::NotFound(format!(
+                "No field {} in {}",
+                field_name, self.file_name
+            )));
+        }
+        let Some(bit) = self.layout.null_bit(field_name) else {
+            return Ok(None);
+        };
+        let record_offset = self.record_page.record_offset(self.current_slot()?)?;
+        Ok(Some((record_offset + bit / 8, 1 << (bit % 8))))
+    }
+
+    fn set_not_null(&mut self, field_name: &str) -> Result<()> {
+        if let Some((offset, mask)) = self.null_bit_position(field_name)? {
+            self.record_page.page_mut().byte_buffer[offset] &= !mask;
+        }
+        self.write_back()
+    }
+
+    fn set_bytes_of_type(
+        &mut self,
+        field_name: &str,
+        field_type: FieldType,
+        value: Vec<u8>,
+    ) -> Result<()> {
+        let offset = self.field_offset(field_name, field_type)?;
+        let length = self.layout.schema().length(field_name).unwrap_or(0);
+        if value.len() > length {
+            return Err(StormDbError::OutOfBound(format!(
+                "{} bytes don't fit in field {}, it holds {}",
+                value.len(),
+                field_name,
+                length
+            )));
+        }
+        self.record_page.page_mut().write_bytes(offset, value)?;
+        self.set_not_null(field_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Schema;
+    use tempdir::TempDir;
+
+    const BLOCK_SIZE: usize = 128;
+
+    fn student_layout() -> Layout {
+        let mut schema = Schema::new();
+        schema
+            .add_int_field("id")
+            .add_string_field("name", 10)
+            .add_field("photo", FieldType::Bytes, 4, true);
+        Layout::new(schema)
+    }
+
+    fn insert_students(table_scan: &mut TableScan, count: i32) -> Vec<Rid> {
+        (0..count)
+            .map(|id| {
+                table_scan.insert().expect("failed to insert");
+                table_scan.set_int("id", id).expect("failed to set id");
+                table_scan
+                    .set_string("name", format!("student{}", id))
+                    .expect("failed to set name");
+                table_scan.get_rid().expect("failed to get rid")
+            })
+            .collect()
+    }
+
+    fn ids(table_scan: &mut TableScan) -> Vec<i32> {
+        table_scan.before_first().expect("failed to rewind");
+        let mut ids = Vec::new();
+        while table_scan.next().expect("failed to move to next") {
+            ids.push(table_scan.get_int("id").expect("failed to get id"));
+        }
+        ids
+    }
+
+    #[test]
+    fn test_insert_scan_and_delete() {
+        let tmp_dir = TempDir::new("test_table_scan").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut table_scan = TableScan::new(file_manager.clone(), "student", student_layout())
+            .expect("failed to open scan");
+        assert_eq!(ids(&mut table_scan), vec![]);
+
+        let rids = insert_students(&mut table_scan, 20);
+        // 21 byte records, four to a block.
+        let block_count = file_manager
+            .length("student.tbl")
+            .expect("failed to get length")
+            .expect("table file missing");
+        assert!(block_count > 1);
+        assert_eq!(rids.last().unwrap().block, block_count - 1);
+        assert_eq!(ids(&mut table_scan), (0..20).collect::<Vec<_>>());
+
+        table_scan.before_first().expect("failed to rewind");
+        while table_scan.next().expect("failed to move to next") {
+            if table_scan.get_int("id").expect("failed to get id") % 3 != 0 {
+                table_scan.delete().expect("failed to delete");
+            }
+        }
+        assert_eq!(ids(&mut table_scan), vec![0, 3, 6, 9, 12, 15, 18]);
+
+        // A fresh scan of the same table sees the same thing, and inserts fill the holes first.
+        let mut reopened = TableScan::new(file_manager.clone(), "student", student_layout())
+            .expect("failed to open scan");
+        reopened.insert().expect("failed to insert");
+        reopened.set_int("id", 100).expect("failed to set id");
+        assert_eq!(reopened.get_rid().expect("failed to get rid"), rids[1]);
+        assert_eq!(ids(&mut reopened), vec![0, 100, 3, 6, 9, 12, 15, 18]);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_record_too_big_for_block() {
+        let tmp_dir = TempDir::new("test_table_scan").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut schema = Schema::new();
+        schema.add_int_field("id").add_string_field("bio", 200);
+        assert!(matches!(
+            TableScan::new(file_manager.clone(), "student", Layout::new(schema)),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        // Nothing got appended for it either.
+        assert_eq!(
+            file_manager
+                .length("student.tbl")
+                .expect("failed to get length"),
+            None
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_move_to_rid_and_update() {
+        let tmp_dir = TempDir::new("test_table_scan").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut table_scan =
+            TableScan::new(file_manager, "student", student_layout()).expect("failed to open scan");
+        let rids = insert_students(&mut table_scan, 10);
+
+        table_scan.move_to_rid(rids[7]).expect("failed to move");
+        assert_eq!(
+            table_scan.get_string("name").expect("failed to get name"),
+            "student7"
+        );
+        assert!(table_scan.is_null("photo").expect("failed to check null"));
+        assert!(!table_scan.is_null("id").expect("failed to check null"));
+
+        table_scan
+            .set_bytes("photo", vec![1, 2, 3])
+            .expect("failed to set photo");
+        table_scan
+            .set_string("name", "renamed".to_string())
+            .expect("failed to set name");
+        table_scan.move_to_rid(rids[0]).expect("failed to move");
+        table_scan.move_to_rid(rids[7]).expect("failed to move");
+        assert_eq!(
+            table_scan.get_bytes("photo").expect("failed to get photo"),
+            vec![1, 2, 3]
+        );
+        assert!(!table_scan.is_null("photo").expect("failed to check null"));
+        assert_eq!(
+            table_scan.get_string("name").expect("failed to get name"),
+            "renamed"
+        );
+        table_scan.set_null("photo").expect("failed to set null");
+        assert!(table_scan.is_null("photo").expect("failed to check null"));
+
+        // Wrong type, too long, unknown field, not nullable, deleted record.
+        assert!(matches!(
+            table_scan.get_int("name"),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert!(matches!(
+            table_scan.set_string("name", "far too long".to_string()),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        assert!(matches!(
+            table_scan.get_int("grade"),
+            Err(StormDbError::NotFound(_))
+        ));
+        assert!(matches!(
+            table_scan.set_null("id"),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        table_scan.delete().expect("failed to delete");
+        assert!(matches!(
+            table_scan.move_to_rid(rids[7]),
+            Err(StormDbError::NotFound(_))
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_failed_move_keeps_scan_in_place() {
+        let tmp_dir = TempDir::new("test_table_scan").expect("failed to create temp dir");
+        let file_manager = Arc::new(
+            FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                .expect("failed to create file manager"),
+        );
+        let mut table_scan = TableScan::new(file_manager.clone(), "student", student_layout())
+            .expect("failed to open scan");
+        let rids = insert_students(&mut table_scan, 3);
+
+        // Block 5 isn't there, the scan stays on the last record it was on.
+        assert!(matches!(
+            table_scan.move_to_rid(Rid { block: 5, slot: 0 }),
+            Err(StormDbError::ShortRead { .. })
+        ));
+        assert_eq!(table_scan.get_rid().expect("failed to get rid"), rids[2]);
+        table_scan.set_int("id", 99).expect("failed to set id");
+
+        assert_eq!(
+            file_manager
+                .length("student.tbl")
+                .expect("failed to get length"),
+            Some(1)
+        );
+        assert_eq!(ids(&mut table_scan), vec![0, 1, 99]);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
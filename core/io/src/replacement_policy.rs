@@ -0,0 +1,377 @@
+/*
+Replacement policies for the buffer pool. The book only has the naive one (first unpinned buffer) and leaves the others as exercises.
+Policies only ever deal with buffer ids, the buffer manager tells them when a buffer gets pinned/unpinned and asks them for a victim when it needs a frame.
+*/
+use std::collections::VecDeque;
+
+/// Decides which buffer gets replaced when a block that isn't in the pool needs to be pinned.
+/// Has to be Send so the buffer manager can be shared between threads.
+pub trait ReplacementPolicy: Send {
+    /// Called every time a buffer is pinned. `loaded` is true when the buffer was just assigned a new block (a miss) and false when the block was already there (a hit).
+    fn record_pin(&mut self, buffer_id: usize, loaded: bool);
+
+    /// Called every time a buffer is unpinned.
+    fn record_unpin(&mut self, _buffer_id: usize) {}
+
+    /// Returns the buffer that should be replaced, None if every buffer is pinned.
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize>;
+}
+
+/// The replacement strategies available to the `BufferManager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementStrategy {
+    /// First unpinned buffer wins.
+    Naive,
+    /// Least recently pinned buffer wins.
+    Lru,
+    /// Second chance, sweeps the buffers with a clock hand and a reference bit per buffer.
+    Clock,
+    /// Buffer with the largest backward distance to its K-th most recent pin wins.
+    LruK(usize),
+    /// Simplified 2Q, buffers referenced once are kept apart from the ones referenced again and are replaced first.
+    TwoQueue,
+}
+
+impl ReplacementStrategy {
+    /// Creates the policy for a pool of `num_buffers` buffers.
+    pub fn policy(&self, num_buffers: usize) -> Box<dyn ReplacementPolicy> {
+        match self {
+            Self::Naive => Box::new(NaivePolicy::new(num_buffers)),
+            Self::Lru => Box::new(LruPolicy::new(num_buffers)),
+            Self::Clock => Box::new(ClockPolicy::new(num_buffers)),
+            Self::LruK(k) => Box::new(LruKPolicy::new(num_buffers, *k)),
+            Self::TwoQueue => Box::new(TwoQueuePolicy::new(num_buffers)),
+        }
+    }
+}
+
+pub struct NaivePolicy {
+    num_buffers: usize,
+}
+
+impl NaivePolicy {
+    pub fn new(num_buffers: usize) -> Self {
+        Self { num_buffers }
+    }
+}
+
+impl ReplacementPolicy for NaivePolicy {
+    fn record_pin(&mut self, _buffer_id: usize, _loaded: bool) {}
+
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        (0..self.num_buffers).find(|&buffer_id| !is_pinned(buffer_id))
+    }
+}
+
+pub struct LruPolicy {
+    // Logical clock, bumped on every pin. None means the buffer was never used so it's the first thing we replace.
+    tick: u64,
+    last_used: Vec<Option<u64>>,
+}
+
+impl LruPolicy {
+    pub fn new(num_buffers: usize) -> Self {
+        Self {
+            tick: 0,
+            last_used: vec![None; num_buffers],
+        }
+    }
+}
+
+impl ReplacementPolicy for LruPolicy {
+    fn record_pin(&mut self, buffer_id: usize, _loaded: bool) {
+        self.tick += 1;
+        self.last_used[buffer_id] = Some(self.tick);
+    }
+
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        (0..self.last_used.len())
+            .filter(|&buffer_id| !is_pinned(buffer_id))
+            // None sorts before Some, so unused buffers go first.
+            .min_by_key(|&buffer_id| self.last_used[buffer_id])
+    }
+}
+
+pub struct ClockPolicy {
+    hand: usize,
+    referenced: Vec<bool>,
+}
+
+impl ClockPolicy {
+    pub fn new(num_buffers: usize) -> Self {
+        Self {
+            hand: 0,
+            referenced: vec![false; num_buffers],
+        }
+    }
+}
+
+impl ReplacementPolicy for ClockPolicy {
+    fn record_pin(&mut self, buffer_id: usize, _loaded: bool) {
+        self.referenced[buffer_id] = true;
+    }
+
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        let num_buffers = self.referenced.len();
+        // The first sweep clears the reference bits, so two of them are enough to find a victim if there's any unpinned buffer.
+        for _ in 0..(2 * num_buffers) {
+            let buffer_id = self.hand;
+            self.hand = (self.hand + 1) % num_buffers;
+
+            if is_pinned(buffer_id) {
+                continue;
+            }
+            if self.referenced[buffer_id] {
+                self.referenced[buffer_id] = false;
+            } else {
+                return Some(buffer_id);
+            }
+        }
+        None
+    }
+}
+
+// History is kept per buffer and dropped when the buffer gets a new block. The paper keeps it around for a while after eviction too,
+// I'll look into that if the hit rate calls for it.
+pub struct LruKPolicy {
+    k: usize,
+    tick: u64,
+    // Most recent pin at the back.
+    history: Vec<VecDeque<u64>>,
+}
+
+impl LruKPolicy {
+    pub fn new(num_buffers: usize, k: usize) -> Self {
+        Self {
+            k: k.max(1),
+            tick: 0,
+            history: vec![VecDeque::new(); num_buffers],
+        }
+    }
+}
+
+impl ReplacementPolicy for LruKPolicy {
+    fn record_pin(&mut self, buffer_id: usize, loaded: bool) {
+        self.tick += 1;
+        let history = &mut self.history[buffer_id];
+        if loaded {
+            history.clear();
+        }
+        history.push_back(self.tick);
+        if history.len() > self.k {
+            history.pop_front();
+        }
+    }
+
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        // Buffers with less than K pins have an infinite backward K-distance so they go first, ties broken by plain LRU.
+        // Buffers with K pins are ordered by their K-th most recent pin. Unused buffers have an empty history and go before everything.
+        (0..self.history.len())
+            .filter(|&buffer_id| !is_pinned(buffer_id))
+            .min_by_key(|&buffer_id| {
+                let history = &self.history[buffer_id];
+                if history.len() < self.k {
+                    (false, history.back().copied().unwrap_or(0))
+                } else {
+                    (true, history.front().copied().unwrap_or(0))
+                }
+            })
+    }
+}
+
+pub struct TwoQueuePolicy {
+    num_buffers: usize,
+    // Max size of the first queue before we start evicting from it even if the second one has candidates.
+    first_queue_threshold: usize,
+    // Buffers pinned once since they were loaded, in FIFO order.
+    first_queue: VecDeque<usize>,
+    // Buffers pinned more than once, least recently used at the front.
+    second_queue: VecDeque<usize>,
+}
+
+impl TwoQueuePolicy {
+    pub fn new(num_buffers: usize) -> Self {
+        Self {
+            num_buffers,
+            // The paper suggests 25% of the pool.
+            first_queue_threshold: (num_buffers / 4).max(1),
+            first_queue: VecDeque::new(),
+            second_queue: VecDeque::new(),
+        }
+    }
+
+    fn remove(queue: &mut VecDeque<usize>, buffer_id: usize) -> bool {
+        match queue.iter().position(|&id| id == buffer_id) {
+            Some(position) => {
+                queue.remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn first_unpinned(queue: &VecDeque<usize>, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        queue
+            .iter()
+            .copied()
+            .find(|&buffer_id| !is_pinned(buffer_id))
+    }
+}
+
+impl ReplacementPolicy for TwoQueuePolicy {
+    fn record_pin(&mut self, buffer_id: usize, loaded: bool) {
+        if loaded {
+            Self::remove(&mut self.first_queue, buffer_id);
+            Self::remove(&mut self.second_queue, buffer_id);
+            self.first_queue.push_back(buffer_id);
+            return;
+        }
+
+        // Any hit moves the buffer to the back of the second queue, whichever queue it was in before.
+        if !Self::remove(&mut self.first_queue, buffer_id) {
+            Self::remove(&mut self.second_queue, buffer_id);
+        }
+        self.second_queue.push_back(buffer_id);
+    }
+
+    fn choose_victim(&mut self, is_pinned: &dyn Fn(usize) -> bool) -> Option<usize> {
+        // Buffers that were never handed out aren't in either queue, use those up first.
+        let unused = (0..self.num_buffers).find(|buffer_id| {
+            !self.first_queue.contains(buffer_id) && !self.second_queue.contains(buffer_id)
+        });
+        if unused.is_some() {
+            return unused;
+        }
+
+        let from_first = Self::first_unpinned(&self.first_queue, is_pinned);
+        let from_second = Self::first_unpinned(&self.second_queue, is_pinned);
+        if self.first_queue.len() > self.first_queue_threshold {
+            from_first.or(from_second)
+        } else {
+            from_second.or(from_first)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn nothing_pinned(_: usize) -> bool {
+        false
+    }
+
+    #[rstest]
+    #[case(ReplacementStrategy::Naive)]
+    #[case(ReplacementStrategy::Lru)]
+    #[case(ReplacementStrategy::Clock)]
+    #[case(ReplacementStrategy::LruK(2))]
+    #[case(ReplacementStrategy::TwoQueue)]
+    fn test_no_victim_when_everything_pinned(#[case] strategy: ReplacementStrategy) {
+        let mut policy = strategy.policy(3);
+        for buffer_id in 0..3 {
+            policy.record_pin(buffer_id, true);
+        }
+        assert_eq!(policy.choose_victim(&|_| true), None);
+    }
+
+    #[rstest]
+    #[case(ReplacementStrategy::Naive)]
+    #[case(ReplacementStrategy::Lru)]
+    #[case(ReplacementStrategy::Clock)]
+    #[case(ReplacementStrategy::LruK(2))]
+    #[case(ReplacementStrategy::TwoQueue)]
+    fn test_victim_is_never_pinned(#[case] strategy: ReplacementStrategy) {
+        let mut policy = strategy.policy(4);
+        for buffer_id in 0..4 {
+            policy.record_pin(buffer_id, true);
+        }
+        let victim = policy.choose_victim(&|buffer_id| buffer_id != 2);
+        assert_eq!(victim, Some(2));
+    }
+
+    #[test]
+    fn test_naive_picks_first_unpinned() {
+        let mut policy = NaivePolicy::new(3);
+        assert_eq!(policy.choose_victim(&|buffer_id| buffer_id == 0), Some(1));
+    }
+
+    #[test]
+    fn test_lru_picks_least_recently_pinned() {
+        let mut policy = LruPolicy::new(3);
+        policy.record_pin(0, true);
+        policy.record_pin(1, true);
+        policy.record_pin(2, true);
+        policy.record_pin(0, false);
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(1));
+    }
+
+    #[test]
+    fn test_lru_prefers_unused_buffers() {
+        let mut policy = LruPolicy::new(3);
+        policy.record_pin(0, true);
+        policy.record_pin(2, true);
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(1));
+    }
+
+    #[test]
+    fn test_clock_gives_second_chance() {
+        let mut policy = ClockPolicy::new(3);
+        policy.record_pin(0, true);
+        policy.record_pin(1, true);
+        policy.record_pin(2, true);
+
+        // Every bit is set, so the first sweep clears them and the hand comes back to 0.
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(0));
+
+        // 1 gets referenced again so it survives the next sweep while 2 doesn't.
+        policy.record_pin(0, true);
+        policy.record_pin(1, false);
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(2));
+    }
+
+    #[test]
+    fn test_lru_k_prefers_buffers_with_fewer_than_k_pins() {
+        let mut policy = LruKPolicy::new(3, 2);
+        policy.record_pin(0, true);
+        policy.record_pin(0, false);
+        policy.record_pin(1, true);
+        policy.record_pin(1, false);
+        // 2 is the most recently pinned, but only pinned once so its backward 2-distance is infinite.
+        policy.record_pin(2, true);
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(2));
+    }
+
+    #[test]
+    fn test_lru_k_orders_by_kth_most_recent_pin() {
+        let mut policy = LruKPolicy::new(2, 2);
+        policy.record_pin(0, true); // 1
+        policy.record_pin(1, true); // 2
+        policy.record_pin(1, false); // 3
+        policy.record_pin(0, false); // 4
+        // Plain LRU would pick 1, but 0's second most recent pin is older.
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(0));
+    }
+
+    #[test]
+    fn test_two_queue_evicts_single_pins_first() {
+        let mut policy = TwoQueuePolicy::new(4);
+        for buffer_id in 0..4 {
+            policy.record_pin(buffer_id, true);
+        }
+        // 0 and 1 get hit again, moving them to the second queue.
+        policy.record_pin(0, false);
+        policy.record_pin(1, false);
+
+        // First queue has 2 and 3, which is over the threshold of 1, so 2 goes first.
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(2));
+    }
+
+    #[test]
+    fn test_two_queue_uses_unused_buffers_first() {
+        let mut policy = TwoQueuePolicy::new(3);
+        policy.record_pin(0, true);
+        assert_eq!(policy.choose_victim(&nothing_pinned), Some(1));
+    }
+}
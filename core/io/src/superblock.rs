@@ -0,0 +1,203 @@
+/*
+Superblock, a small header file in the database directory that says how the files in it were written. Without it nothing stops
+someone from opening a directory with a different block size (or without checksums) and reading every block wrong.
+
+Layout (big endian like the rest of the on-disk stuff):
+    magic (8 bytes) | format version (u32) | block size (u32) | created at, seconds since the epoch (u64) | flags (u32) | CRC32C (u32)
+*/
+
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{StormDbError, checksum::crc32c, error::Result};
+
+pub(crate) const SUPERBLOCK_FILE: &str = "stormdb.super";
+// Starts with "temp" so a crash between writing and renaming it leaves something the FileManager cleans up on the next start.
+const TEMP_SUPERBLOCK_FILE: &str = "temp_stormdb.super";
+
+const MAGIC: &[u8; 8] = b"STORMDB\0";
+/// Bumped whenever the layout of the files changes in a way older code can't read.
+pub const FORMAT_VERSION: u32 = 1;
+const CHECKSUMS_FLAG: u32 = 1;
+const SIZE: usize = 8 + 4 + 4 + 8 + 4 + 4;
+
+/// What the superblock of a database directory says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Superblock {
+    format_version: u32,
+    block_size: usize,
+    created_at: u64,
+    checksums: bool,
+}
+
+impl Superblock {
+    pub(crate) fn new(block_size: usize, checksums: bool) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Self {
+            format_version: FORMAT_VERSION,
+            block_size,
+            created_at,
+            checksums,
+        }
+    }
+
+    pub fn format_version(&self) -> u32 {
+        self.format_version
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// When the database was created, to the second.
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.created_at)
+    }
+
+    /// Whether blocks carry a checksum.
+    pub fn checksums(&self) -> bool {
+        self.checksums
+    }
+
+    /// Reads the superblock in the directory, None if there isn't one yet.
+    pub(crate) fn read(db_directory: &Path) -> Result<Option<Self>> {
+        match fs::read(db_directory.join(SUPERBLOCK_FILE)) {
+            Ok(bytes) => Self::decode(&bytes).map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Writes the superblock to a temp file first and renames it over, so there's never a half written one in place.
+    pub(crate) fn write(&self, db_directory: &Path) -> Result<()> {
+        let temp_path = db_directory.join(TEMP_SUPERBLOCK_FILE);
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&self.encode())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, db_directory.join(SUPERBLOCK_FILE))?;
+        // The rename only sticks once the directory is synced.
+        File::open(db_directory)?.sync_all()?;
+        Ok(())
+    }
+
+    /// Checks that the database can be opened with the given settings.
+    pub(crate) fn validate(&self, block_size: usize, checksums: bool) -> Result<()> {
+        if self.format_version != FORMAT_VERSION {
+            return Err(StormDbError::IncompatibleDatabase(format!(
+                "Database has format version {}, this build only reads version {}",
+                self.format_version, FORMAT_VERSION
+            )));
+        }
+        if self.block_size != block_size {
+            return Err(StormDbError::IncompatibleDatabase(format!(
+                "Database has a block size of {}, but was opened with {}",
+                self.block_size, block_size
+            )));
+        }
+        if self.checksums != checksums {
+            return Err(StormDbError::IncompatibleDatabase(format!(
+                "Database was created with checksums {}, but was opened with them {}",
+                on_off(self.checksums),
+                on_off(checksums)
+            )));
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.format_version.to_be_bytes());
+        bytes.extend_from_slice(&(self.block_size as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.created_at.to_be_bytes());
+        let flags = if self.checksums { CHECKSUMS_FLAG } else { 0 };
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        let crc = crc32c(&bytes);
+        bytes.extend_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != SIZE || &bytes[..8] != MAGIC {
+            return Err(StormDbError::Corrupt {
+                block: None,
+                message: "Superblock is missing its magic number, not a StormDB directory?"
+                    .to_string(),
+            });
+        }
+        let u32_at = |offset: usize| {
+            u32::from_be_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        if u32_at(SIZE - 4) != crc32c(&bytes[..SIZE - 4]) {
+            return Err(StormDbError::Corrupt {
+                block: None,
+                message: "Superblock checksum doesn't match".to_string(),
+            });
+        }
+        Ok(Self {
+            format_version: u32_at(8),
+            block_size: u32_at(12) as usize,
+            created_at: u64::from_be_bytes(bytes[16..24].try_into().expect("8 bytes")),
+            checksums: u32_at(24) & CHECKSUMS_FLAG != 0,
+        })
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_superblock_round_trip() {
+        let tmp_dir = TempDir::new("test_superblock").expect("failed to create temp dir");
+        assert_eq!(
+            Superblock::read(tmp_dir.path()).expect("failed to read"),
+            None
+        );
+
+        let superblock = Superblock::new(4096, true);
+        superblock.write(tmp_dir.path()).expect("failed to write");
+        assert_eq!(
+            Superblock::read(tmp_dir.path()).expect("failed to read"),
+            Some(superblock)
+        );
+        assert!(!tmp_dir.path().join(TEMP_SUPERBLOCK_FILE).exists());
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[rstest]
+    #[case::flipped_block_size(13)]
+    #[case::flipped_magic(0)]
+    #[case::flipped_checksum(SIZE - 1)]
+    fn test_damaged_superblock_is_corrupt(#[case] flipped_offset: usize) {
+        let tmp_dir = TempDir::new("test_superblock").expect("failed to create temp dir");
+        Superblock::new(4096, false)
+            .write(tmp_dir.path())
+            .expect("failed to write");
+
+        let path = tmp_dir.path().join(SUPERBLOCK_FILE);
+        let mut bytes = fs::read(&path).expect("failed to read file");
+        bytes[flipped_offset] ^= 0x01;
+        fs::write(&path, bytes).expect("failed to write file");
+        assert!(matches!(
+            Superblock::read(tmp_dir.path()),
+            Err(StormDbError::Corrupt { .. })
+        ));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{BlockMetadata, BufferManager, error::Result};
+
+/// Keeps track of the buffers a transaction has pinned, so they can all be unpinned when it's done.
+pub(crate) struct BufferList {
+    buffer_manager: Arc<Mutex<BufferManager>>,
+    buffers: HashMap<BlockMetadata, usize>,
+    // A block can be pinned more than once, so every pin is kept around and not just the block.
+    pins: Vec<BlockMetadata>,
+}
+
+impl BufferList {
+    pub(crate) fn new(buffer_manager: Arc<Mutex<BufferManager>>) -> Self {
+        Self {
+            buffer_manager,
+            buffers: HashMap::new(),
+            pins: Vec::new(),
+        }
+    }
+
+    /// Returns the id of the buffer the block is pinned to, None if the transaction hasn't pinned it.
+    pub(crate) fn buffer_id(&self, block: &BlockMetadata) -> Option<usize> {
+        self.buffers.get(block).copied()
+    }
+
+    pub(crate) fn pin(&mut self, block: &BlockMetadata) -> Result<()> {
+        let buffer_id = self
+            .buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned")
+            .pin(block)?;
+        self.buffers.insert(block.clone(), buffer_id);
+        self.pins.push(block.clone());
+        Ok(())
+    }
+
+    pub(crate) fn unpin(&mut self, block: &BlockMetadata) -> Result<()> {
+        let Some(buffer_id) = self.buffers.get(block).copied() else {
+            return Ok(());
+        };
+
+        self.buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned")
+            .unpin(buffer_id)?;
+        if let Some(position) = self.pins.iter().position(|pinned| pinned == block) {
+            self.pins.remove(position);
+        }
+        if !self.pins.contains(block) {
+            self.buffers.remove(block);
+        }
+        Ok(())
+    }
+
+    // Unpins everything even if one of them fails, the first error is what comes back.
+    pub(crate) fn unpin_all(&mut self) -> Result<()> {
+        let mut buffer_manager = self
+            .buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned");
+        let mut result = Ok(());
+        for block in self.pins.iter() {
+            if let Some(buffer_id) = self.buffers.get(block) {
+                let unpinned = buffer_manager.unpin(*buffer_id);
+                if result.is_ok() {
+                    result = unpinned;
+                }
+            }
+        }
+        self.buffers.clear();
+        self.pins.clear();
+        result
+    }
+}
@@ -0,0 +1,97 @@
+/*
+Concurrency Manager API:
+  public ConcurrencyMgr();
+  public void sLock(BlockId blk);
+  public void xLock(BlockId blk);
+  public void release();
+
+Strict two phase locking. Locks are only ever taken while the transaction runs and all of them go away together on commit or rollback.
+The book has one static lock table for everything, here it's handed in so every database (and every test) gets its own.
+*/
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{BlockMetadata, error::Result};
+
+use super::lock_table::{LockMode, LockTable};
+
+/// Keeps track of the locks a single transaction holds so it doesn't ask the lock table twice for the same thing.
+pub(crate) struct ConcurrencyManager {
+    // None means the transaction runs on its own (recovery for one) and doesn't need any locks.
+    lock_table: Option<Arc<LockTable>>,
+    transaction_number: u32,
+    locks: HashMap<BlockMetadata, LockMode>,
+}
+
+impl ConcurrencyManager {
+    pub(crate) fn new(transaction_number: u32, lock_table: Option<Arc<LockTable>>) -> Self {
+        Self {
+            lock_table,
+            transaction_number,
+            locks: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn slock(&mut self, block: &BlockMetadata) -> Result<()> {
+        if self.locks.contains_key(block) {
+            return Ok(());
+        }
+        self.acquire(block, LockMode::Shared)
+    }
+
+    pub(crate) fn xlock(&mut self, block: &BlockMetadata) -> Result<()> {
+        if self.locks.get(block) == Some(&LockMode::Exclusive) {
+            return Ok(());
+        }
+        self.acquire(block, LockMode::Exclusive)
+    }
+
+    /// Gives back every lock the transaction holds.
+    pub(crate) fn release(&mut self) {
+        if let Some(lock_table) = &self.lock_table {
+            for block in self.locks.keys() {
+                lock_table.unlock(block, self.transaction_number);
+            }
+            lock_table.transaction_finished(self.transaction_number);
+        }
+        self.locks.clear();
+    }
+
+    fn acquire(&mut self, block: &BlockMetadata, mode: LockMode) -> Result<()> {
+        if let Some(lock_table) = &self.lock_table {
+            lock_table.acquire(block, self.transaction_number, mode)?;
+        }
+        self.locks.insert(block.clone(), mode);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StormDbError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_locks_held_until_release() {
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_millis(50)));
+        let block = BlockMetadata::new("test.tbl", 0);
+        let mut first = ConcurrencyManager::new(1, Some(lock_table.clone()));
+        let mut second = ConcurrencyManager::new(2, Some(lock_table.clone()));
+
+        first.slock(&block).expect("failed to slock");
+        first.xlock(&block).expect("failed to xlock");
+        // Already covered by the xlock.
+        first.slock(&block).expect("failed to slock");
+        assert!(matches!(
+            second.slock(&block),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        first.release();
+        second.slock(&block).expect("failed to slock");
+        assert!(matches!(
+            first.xlock(&block),
+            Err(StormDbError::LockAbort(_))
+        ));
+    }
+}
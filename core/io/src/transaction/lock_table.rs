@@ -0,0 +1,678 @@
+/*
+Lock Table API:
+  public void sLock(BlockId blk);
+  public void xLock(BlockId blk);
+  public void unlock(BlockId blk);
+
+The book keeps a count per block (-1 for an xlock) and has every waiter wake up on notifyAll and check again. Here every lock remembers
+which transactions hold it and which ones are waiting for it, in order. Waiters are still woken up all at once, but only the one at
+the front of the queue (or one that already holds the lock and is upgrading) gets to take it.
+
+Waiting on a lock also adds edges to a waits-for graph, from the waiter to everyone it's waiting for. Depending on the deadlock policy
+that graph gets searched for cycles, or the ages of the transactions involved decide who waits and who aborts (wait-die and wound-wait).
+*/
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{BlockMetadata, StormDbError, error::Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// What the lock table does about transactions waiting on each other in a circle. The max wait applies no matter what.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadlockPolicy {
+    /// Nothing, whoever waits longer than the max wait gets aborted.
+    #[default]
+    Timeout,
+    /// Looks for a cycle in the waits-for graph whenever a transaction has to wait and aborts one transaction from it.
+    Detection(VictimSelection),
+    /// Older transactions wait for younger ones, a younger one asking for a lock an older one stands in the way of aborts right away.
+    WaitDie,
+    /// Older transactions wound (abort) the younger ones standing in their way, younger ones wait for older ones.
+    /// A wounded transaction finds out on its next lock request, one that never asks for another lock gets to finish.
+    WoundWait,
+}
+
+/// Which transaction of a waits-for cycle gets aborted. Transaction numbers go up, so the highest one is the youngest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VictimSelection {
+    Youngest,
+    /// Ties go to the youngest.
+    FewestLocks,
+}
+
+#[derive(Default)]
+struct LockState {
+    shared: HashSet<u32>,
+    exclusive: Option<u32>,
+    waiting: VecDeque<(u32, LockMode)>,
+}
+
+impl LockState {
+    fn holds(&self, transaction_number: u32) -> bool {
+        self.exclusive == Some(transaction_number) || self.shared.contains(&transaction_number)
+    }
+
+    // Everyone the transaction has to wait for: holders of conflicting locks and, unless it's upgrading, everyone queued up ahead of it.
+    // Transactions that already hold the lock skip the queue, otherwise an upgrade would wait behind someone waiting on the upgrader.
+    fn blockers(&self, transaction_number: u32, mode: LockMode) -> HashSet<u32> {
+        let mut blockers = HashSet::new();
+        if let Some(holder) = self.exclusive
+            && holder != transaction_number
+        {
+            blockers.insert(holder);
+        }
+        if mode == LockMode::Exclusive {
+            blockers.extend(
+                self.shared
+                    .iter()
+                    .filter(|holder| **holder != transaction_number),
+            );
+        }
+        if !self.holds(transaction_number) {
+            blockers.extend(
+                self.waiting
+                    .iter()
+                    .map(|(waiter, _)| *waiter)
+                    .take_while(|waiter| *waiter != transaction_number),
+            );
+        }
+        blockers
+    }
+
+    fn grant(&mut self, transaction_number: u32, mode: LockMode) {
+        self.stop_waiting(transaction_number);
+        match mode {
+            LockMode::Shared => {
+                self.shared.insert(transaction_number);
+            }
+            LockMode::Exclusive => {
+                self.shared.remove(&transaction_number);
+                self.exclusive = Some(transaction_number);
+            }
+        }
+    }
+
+    fn is_waiting(&self, transaction_number: u32) -> bool {
+        self.waiting
+            .iter()
+            .any(|(waiter, _)| *waiter == transaction_number)
+    }
+
+    fn is_unused(&self) -> bool {
+        self.shared.is_empty() && self.exclusive.is_none() && self.waiting.is_empty()
+    }
+
+    fn stop_waiting(&mut self, transaction_number: u32) {
+        self.waiting
+            .retain(|(waiter, _)| *waiter != transaction_number);
+    }
+}
+
+#[derive(Default)]
+struct LockTableState {
+    locks: HashMap<BlockMetadata, LockState>,
+    // Waits-for graph, every waiting transaction points at the transactions it's waiting for.
+    waits_for: HashMap<u32, HashSet<u32>>,
+    // Deadlock victims and wounded transactions that haven't found out yet.
+    aborted: HashSet<u32>,
+}
+
+impl LockTableState {
+    // Only the waiting transaction's edges changed, so any new cycle has to go through it.
+    fn find_cycle(&self, transaction_number: u32) -> Option<Vec<u32>> {
+        let mut path = vec![transaction_number];
+        let mut visited = HashSet::from([transaction_number]);
+        self.visit(
+            transaction_number,
+            transaction_number,
+            &mut path,
+            &mut visited,
+        )
+        .then_some(path)
+    }
+
+    fn visit(
+        &self,
+        current: u32,
+        start: u32,
+        path: &mut Vec<u32>,
+        visited: &mut HashSet<u32>,
+    ) -> bool {
+        for next in self.waits_for.get(&current).into_iter().flatten() {
+            // Already on their way out, whatever they're part of is getting broken up anyway.
+            if self.aborted.contains(next) {
+                continue;
+            }
+            if *next == start {
+                return true;
+            }
+            if visited.insert(*next) {
+                path.push(*next);
+                if self.visit(*next, start, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    fn choose_victim(&self, cycle: &[u32], selection: VictimSelection) -> u32 {
+        let victim = match selection {
+            VictimSelection::Youngest => cycle.iter().max().copied(),
+            VictimSelection::FewestLocks => cycle
+                .iter()
+                .min_by_key(|transaction_number| {
+                    (
+                        self.locks_held(**transaction_number),
+                        Reverse(**transaction_number),
+                    )
+                })
+                .copied(),
+        };
+        victim.expect("a cycle has at least one transaction")
+    }
+
+    fn locks_held(&self, transaction_number: u32) -> usize {
+        self.locks
+            .values()
+            .filter(|lock| lock.holds(transaction_number))
+            .count()
+    }
+}
+
+/// Shared and exclusive locks on blocks, one table shared by every transaction of a database.
+/// A transaction that can't get a lock gets `StormDbError::LockAbort` and is expected to roll back. That happens after the max wait
+/// or earlier, depending on the deadlock policy.
+pub struct LockTable {
+    state: Mutex<LockTableState>,
+    released: Condvar,
+    max_wait: Duration,
+    deadlock_policy: DeadlockPolicy,
+}
+
+impl Default for LockTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockTable {
+    /// Same as the book, 10 seconds.
+    pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(10);
+
+    pub fn new() -> Self {
+        Self::with_max_wait(Self::DEFAULT_MAX_WAIT)
+    }
+
+    /// Creates a lock table where lock requests give up after `max_wait`.
+    pub fn with_max_wait(max_wait: Duration) -> Self {
+        Self::with_deadlock_policy(DeadlockPolicy::default(), max_wait)
+    }
+
+    /// Creates a lock table that deals with deadlocks using the given policy, with `max_wait` as the last resort.
+    pub fn with_deadlock_policy(deadlock_policy: DeadlockPolicy, max_wait: Duration) -> Self {
+        Self {
+            state: Mutex::new(LockTableState::default()),
+            released: Condvar::new(),
+            max_wait,
+            deadlock_policy,
+        }
+    }
+
+    pub fn deadlock_policy(&self) -> DeadlockPolicy {
+        self.deadlock_policy
+    }
+
+    /// Grants the transaction a shared lock on the block, waiting while some other transaction holds an exclusive one.
+    pub fn slock(&self, block: &BlockMetadata, transaction_number: u32) -> Result<()> {
+        self.acquire(block, transaction_number, LockMode::Shared)
+    }
+
+    /// Grants the transaction an exclusive lock on the block, waiting while any other transaction holds a lock on it.
+    /// A shared lock the transaction already holds gets upgraded.
+    pub fn xlock(&self, block: &BlockMetadata, transaction_number: u32) -> Result<()> {
+        self.acquire(block, transaction_number, LockMode::Exclusive)
+    }
+
+    /// Releases whatever lock the transaction holds on the block and wakes up the waiters.
+    pub fn unlock(&self, block: &BlockMetadata, transaction_number: u32) {
+        let mut state = self.lock_state();
+        if let Some(lock) = state.locks.get_mut(block) {
+            lock.shared.remove(&transaction_number);
+            if lock.exclusive == Some(transaction_number) {
+                lock.exclusive = None;
+            }
+            if lock.is_unused() {
+                state.locks.remove(block);
+            }
+        }
+        self.released.notify_all();
+    }
+
+    /// Forgets about a transaction that released all of its locks, including a pending wound it never found out about.
+    pub(crate) fn transaction_finished(&self, transaction_number: u32) {
+        let mut state = self.lock_state();
+        state.aborted.remove(&transaction_number);
+        state.waits_for.remove(&transaction_number);
+    }
+
+    pub(crate) fn acquire(
+        &self,
+        block: &BlockMetadata,
+        transaction_number: u32,
+        mode: LockMode,
+    ) -> Result<()> {
+        let deadline = Instant::now() + self.max_wait;
+        let mut state = self.lock_state();
+
+        loop {
+            if state.aborted.contains(&transaction_number) {
+                return Err(self.abort(
+                    &mut state,
+                    block,
+                    transaction_number,
+                    "was aborted to resolve a deadlock",
+                ));
+            }
+
+            let lock = state.locks.entry(block.clone()).or_default();
+            let blockers = lock.blockers(transaction_number, mode);
+            if blockers.is_empty() {
+                lock.grant(transaction_number, mode);
+                state.waits_for.remove(&transaction_number);
+                // Whoever is next in line might be compatible with us (shared behind shared), give them a go.
+                self.released.notify_all();
+                return Ok(());
+            }
+
+            if !lock.is_waiting(transaction_number) {
+                lock.waiting.push_back((transaction_number, mode));
+            }
+            state.waits_for.insert(transaction_number, blockers.clone());
+
+            match self.deadlock_policy {
+                DeadlockPolicy::Timeout => {}
+                DeadlockPolicy::Detection(selection) => {
+                    if let Some(cycle) = state.find_cycle(transaction_number) {
+                        let victim = state.choose_victim(&cycle, selection);
+                        if victim == transaction_number {
+                            return Err(self.abort(
+                                &mut state,
+                                block,
+                                transaction_number,
+                                "was chosen as a deadlock victim",
+                            ));
+                        }
+                        // The victim is waiting too (it's part of the cycle), it finds out once it wakes up.
+                        state.aborted.insert(victim);
+                        self.released.notify_all();
+                    }
+                }
+                DeadlockPolicy::WaitDie => {
+                    if blockers.iter().any(|blocker| *blocker < transaction_number) {
+                        return Err(self.abort(
+                            &mut state,
+                            block,
+                            transaction_number,
+                            "died waiting for an older transaction",
+                        ));
+                    }
+                }
+                DeadlockPolicy::WoundWait => {
+                    let mut wounded = false;
+                    for blocker in blockers {
+                        if blocker > transaction_number {
+                            wounded |= state.aborted.insert(blocker);
+                        }
+                    }
+                    if wounded {
+                        self.released.notify_all();
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(self.abort(
+                    &mut state,
+                    block,
+                    transaction_number,
+                    &format!("timed out waiting for {:?} lock", mode),
+                ));
+            }
+
+            state = self
+                .released
+                .wait_timeout(state, deadline - now)
+                .expect("lock table mutex poisoned")
+                .0;
+        }
+    }
+
+    // Takes the transaction out of the queue and the waits-for graph and hands back the error it should roll back with.
+    fn abort(
+        &self,
+        state: &mut LockTableState,
+        block: &BlockMetadata,
+        transaction_number: u32,
+        reason: &str,
+    ) -> StormDbError {
+        state.aborted.remove(&transaction_number);
+        state.waits_for.remove(&transaction_number);
+        if let Some(lock) = state.locks.get_mut(block) {
+            lock.stop_waiting(transaction_number);
+            if lock.is_unused() {
+                state.locks.remove(block);
+            }
+        }
+        // We might have been the one holding up the queue.
+        self.released.notify_all();
+        StormDbError::LockAbort(format!(
+            "Transaction {} {} on {}",
+            transaction_number, reason, block
+        ))
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, LockTableState> {
+        self.state.lock().expect("lock table mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn test_shared_locks_are_compatible() {
+        let lock_table = LockTable::with_max_wait(Duration::from_millis(50));
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        lock_table.slock(&block, 1).expect("failed to slock");
+        lock_table.slock(&block, 2).expect("failed to slock");
+        assert!(matches!(
+            lock_table.xlock(&block, 3),
+            Err(StormDbError::LockAbort(_))
+        ));
+    }
+
+    #[test]
+    fn test_xlock_times_out_while_other_holds_lock() {
+        let lock_table = LockTable::with_max_wait(Duration::from_millis(50));
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        lock_table.xlock(&block, 1).expect("failed to xlock");
+        assert!(matches!(
+            lock_table.slock(&block, 2),
+            Err(StormDbError::LockAbort(_))
+        ));
+        assert!(matches!(
+            lock_table.xlock(&block, 2),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        // Different block, no conflict.
+        lock_table
+            .xlock(&BlockMetadata::new("test.tbl", 1), 2)
+            .expect("failed to xlock");
+    }
+
+    #[test]
+    fn test_upgrade_shared_to_exclusive() {
+        let lock_table = LockTable::with_max_wait(Duration::from_millis(50));
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        lock_table.slock(&block, 1).expect("failed to slock");
+        lock_table.xlock(&block, 1).expect("failed to upgrade");
+        assert!(matches!(
+            lock_table.slock(&block, 2),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        lock_table.unlock(&block, 1);
+        lock_table.slock(&block, 2).expect("failed to slock");
+    }
+
+    #[test]
+    fn test_waiter_gets_lock_once_released() {
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_secs(5)));
+        let block = BlockMetadata::new("test.tbl", 0);
+        lock_table.xlock(&block, 1).expect("failed to xlock");
+
+        let waiter = {
+            let lock_table = lock_table.clone();
+            let block = block.clone();
+            thread::spawn(move || {
+                let start = Instant::now();
+                lock_table.xlock(&block, 2).expect("failed to xlock");
+                start.elapsed()
+            })
+        };
+
+        thread::sleep(Duration::from_millis(100));
+        lock_table.unlock(&block, 1);
+        let waited = waiter.join().expect("waiter panicked");
+        assert!(waited >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_waiters_served_in_order() {
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_secs(5)));
+        let block = BlockMetadata::new("test.tbl", 0);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        lock_table.xlock(&block, 1).expect("failed to xlock");
+
+        let mut waiters = Vec::new();
+        for transaction_number in [2, 3, 4] {
+            let lock_table = lock_table.clone();
+            let block = block.clone();
+            let order = order.clone();
+            waiters.push(thread::spawn(move || {
+                lock_table
+                    .xlock(&block, transaction_number)
+                    .expect("failed to xlock");
+                order
+                    .lock()
+                    .expect("order mutex poisoned")
+                    .push(transaction_number);
+                lock_table.unlock(&block, transaction_number);
+            }));
+            // Give each waiter time to queue up before the next one.
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        lock_table.unlock(&block, 1);
+        for waiter in waiters {
+            waiter.join().expect("waiter panicked");
+        }
+        assert_eq!(*order.lock().expect("order mutex poisoned"), vec![2, 3, 4]);
+    }
+
+    // Runs a lock request on its own thread so the test can go on and complete the deadlock.
+    fn spawn_xlock(
+        lock_table: &Arc<LockTable>,
+        block: &BlockMetadata,
+        transaction_number: u32,
+    ) -> thread::JoinHandle<Result<()>> {
+        let lock_table = lock_table.clone();
+        let block = block.clone();
+        thread::spawn(move || lock_table.xlock(&block, transaction_number))
+    }
+
+    #[rstest]
+    #[case::youngest_is_the_requester(VictimSelection::Youngest, 1, 2)]
+    #[case::youngest_is_already_waiting(VictimSelection::Youngest, 2, 1)]
+    #[case::fewest_locks(VictimSelection::FewestLocks, 1, 2)]
+    fn test_detection_aborts_victim(
+        #[case] selection: VictimSelection,
+        #[case] first_waiter: u32,
+        #[case] second_waiter: u32,
+    ) {
+        let lock_table = Arc::new(LockTable::with_deadlock_policy(
+            DeadlockPolicy::Detection(selection),
+            LockTable::DEFAULT_MAX_WAIT,
+        ));
+        let blocks = [
+            BlockMetadata::new("test.tbl", 0),
+            BlockMetadata::new("test.tbl", 1),
+            BlockMetadata::new("test.tbl", 2),
+        ];
+        // 1 holds block 0, 2 holds blocks 1 and 2. Each one then asks for a block the other holds.
+        lock_table.xlock(&blocks[0], 1).expect("failed to xlock");
+        lock_table.xlock(&blocks[1], 2).expect("failed to xlock");
+        lock_table.xlock(&blocks[2], 2).expect("failed to xlock");
+        let wanted = |transaction_number: u32| {
+            if transaction_number == 1 {
+                &blocks[1]
+            } else {
+                &blocks[0]
+            }
+        };
+
+        let start = Instant::now();
+        let first = spawn_xlock(&lock_table, wanted(first_waiter), first_waiter);
+        thread::sleep(Duration::from_millis(100));
+        let second = spawn_xlock(&lock_table, wanted(second_waiter), second_waiter);
+
+        let expected_victim = match selection {
+            VictimSelection::Youngest => 2,
+            VictimSelection::FewestLocks => 1,
+        };
+        let (victim, survivor) = if first_waiter == expected_victim {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        assert!(matches!(
+            victim.join().expect("victim panicked"),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        // Victim rolls back, which lets the survivor through.
+        for block in &blocks {
+            lock_table.unlock(block, expected_victim);
+        }
+        lock_table.transaction_finished(expected_victim);
+        survivor
+            .join()
+            .expect("survivor panicked")
+            .expect("survivor should get the lock");
+        assert!(start.elapsed() < LockTable::DEFAULT_MAX_WAIT);
+    }
+
+    #[test]
+    fn test_timeout_policy_leaves_deadlock_to_max_wait() {
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_millis(200)));
+        let first_block = BlockMetadata::new("test.tbl", 0);
+        let second_block = BlockMetadata::new("test.tbl", 1);
+        lock_table.xlock(&first_block, 1).expect("failed to xlock");
+        lock_table.xlock(&second_block, 2).expect("failed to xlock");
+
+        let start = Instant::now();
+        let first = spawn_xlock(&lock_table, &second_block, 1);
+        let second = spawn_xlock(&lock_table, &first_block, 2);
+        for waiter in [first, second] {
+            assert!(matches!(
+                waiter.join().expect("waiter panicked"),
+                Err(StormDbError::LockAbort(_))
+            ));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_wait_die() {
+        let lock_table = Arc::new(LockTable::with_deadlock_policy(
+            DeadlockPolicy::WaitDie,
+            LockTable::DEFAULT_MAX_WAIT,
+        ));
+        let old_block = BlockMetadata::new("test.tbl", 0);
+        let young_block = BlockMetadata::new("test.tbl", 1);
+        lock_table.xlock(&old_block, 1).expect("failed to xlock");
+        lock_table.xlock(&young_block, 2).expect("failed to xlock");
+
+        // The older one waits.
+        let old = spawn_xlock(&lock_table, &young_block, 1);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!old.is_finished());
+
+        // The younger one dies instead of closing the circle.
+        let start = Instant::now();
+        assert!(matches!(
+            lock_table.xlock(&old_block, 2),
+            Err(StormDbError::LockAbort(_))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        lock_table.unlock(&young_block, 2);
+        lock_table.transaction_finished(2);
+        old.join()
+            .expect("older transaction panicked")
+            .expect("older transaction should get the lock");
+    }
+
+    #[test]
+    fn test_wound_wait() {
+        let lock_table = Arc::new(LockTable::with_deadlock_policy(
+            DeadlockPolicy::WoundWait,
+            LockTable::DEFAULT_MAX_WAIT,
+        ));
+        let old_block = BlockMetadata::new("test.tbl", 0);
+        let young_block = BlockMetadata::new("test.tbl", 1);
+        lock_table.xlock(&old_block, 1).expect("failed to xlock");
+        lock_table.xlock(&young_block, 2).expect("failed to xlock");
+
+        // The younger one waits for the older one.
+        let young = spawn_xlock(&lock_table, &old_block, 2);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!young.is_finished());
+
+        // The older one asking for the younger one's block wounds it, which wakes it up and aborts it.
+        let old = spawn_xlock(&lock_table, &young_block, 1);
+        assert!(matches!(
+            young.join().expect("younger transaction panicked"),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        lock_table.unlock(&young_block, 2);
+        lock_table.transaction_finished(2);
+        old.join()
+            .expect("older transaction panicked")
+            .expect("older transaction should get the lock");
+    }
+
+    #[test]
+    fn test_wounded_transaction_aborts_on_next_request() {
+        let lock_table = Arc::new(LockTable::with_deadlock_policy(
+            DeadlockPolicy::WoundWait,
+            LockTable::DEFAULT_MAX_WAIT,
+        ));
+        let block = BlockMetadata::new("test.tbl", 0);
+        lock_table.xlock(&block, 2).expect("failed to xlock");
+
+        let old = spawn_xlock(&lock_table, &block, 1);
+        thread::sleep(Duration::from_millis(100));
+        // Not waiting on anything, so it only finds out now.
+        assert!(matches!(
+            lock_table.slock(&BlockMetadata::new("test.tbl", 1), 2),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        lock_table.unlock(&block, 2);
+        lock_table.transaction_finished(2);
+        old.join()
+            .expect("older transaction panicked")
+            .expect("older transaction should get the lock");
+    }
+}
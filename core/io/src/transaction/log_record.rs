@@ -0,0 +1,406 @@
+/*
+Log records as per the book. Each one is serialized into a Page and handed to LogManager::append as a plain byte vec.
+Layout is always the record type as a u32 followed by the fields of that record:
+    CHECKPOINT: <type>
+    NQCKPT:     <type> <number of active transactions> <txnum>...
+    START:      <type> <txnum>
+    COMMIT:     <type> <txnum>
+    ROLLBACK:   <type> <txnum>
+    SETINT:     <type> <txnum> <file name> <block number> <offset> <old value> <new value>
+    SETSTRING:  <type> <txnum> <file name> <block number> <offset> <old value> <new value>
+
+Strings (file name and string values) go through Page::write_string so they're stored as a varint length followed by the bytes.
+The book only logs the old value. The new one is there so redo/undo recovery can replay committed changes.
+*/
+use crate::{BlockMetadata, BufferManager, LogManager, Page, StormDbError, error::Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogRecord {
+    Checkpoint,
+    NonQuiescentCheckpoint {
+        active_transactions: Vec<u32>,
+    },
+    Start {
+        transaction_number: u32,
+    },
+    Commit {
+        transaction_number: u32,
+    },
+    Rollback {
+        transaction_number: u32,
+    },
+    SetInt {
+        transaction_number: u32,
+        block: BlockMetadata,
+        offset: usize,
+        old_value: i32,
+        new_value: i32,
+    },
+    SetString {
+        transaction_number: u32,
+        block: BlockMetadata,
+        offset: usize,
+        old_value: String,
+        new_value: String,
+    },
+}
+
+impl LogRecord {
+    const CHECKPOINT: u32 = 0;
+    const START: u32 = 1;
+    const COMMIT: u32 = 2;
+    const ROLLBACK: u32 = 3;
+    const SET_INT: u32 = 4;
+    const SET_STRING: u32 = 5;
+    const NON_QUIESCENT_CHECKPOINT: u32 = 6;
+
+    /// Deserializes a record from the bytes returned by the log iterator.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let page = Page::builder().with_log_buffer(bytes).build();
+        let record_type = page.read_u32(0)?;
+        let offset = Page::U32_SIZE;
+
+        match record_type {
+            Self::CHECKPOINT => Ok(Self::Checkpoint),
+            Self::NON_QUIESCENT_CHECKPOINT => {
+                let count = page.read_u32(offset)? as usize;
+                let active_transactions = (0..count)
+                    .map(|i| page.read_u32(offset + (i + 1) * Page::U32_SIZE))
+                    .collect::<Result<Vec<u32>>>()?;
+                Ok(Self::NonQuiescentCheckpoint {
+                    active_transactions,
+                })
+            }
+            Self::START => Ok(Self::Start {
+                transaction_number: page.read_u32(offset)?,
+            }),
+            Self::COMMIT => Ok(Self::Commit {
+                transaction_number: page.read_u32(offset)?,
+            }),
+            Self::ROLLBACK => Ok(Self::Rollback {
+                transaction_number: page.read_u32(offset)?,
+            }),
+            Self::SET_INT => {
+                let transaction_number = page.read_u32(offset)?;
+                let (block, offset) = Self::read_block(&page, offset + Page::U32_SIZE)?;
+                Ok(Self::SetInt {
+                    transaction_number,
+                    block,
+                    offset: page.read_u32(offset)? as usize,
+                    old_value: page.read_int(offset + Page::U32_SIZE)?,
+                    new_value: page.read_int(offset + Page::U32_SIZE + Page::I32_SIZE)?,
+                })
+            }
+            Self::SET_STRING => {
+                let transaction_number = page.read_u32(offset)?;
+                let (block, offset) = Self::read_block(&page, offset + Page::U32_SIZE)?;
+                let old_value = page.read_string(offset + Page::U32_SIZE)?;
+                let new_value =
+                    page.read_string(offset + Page::U32_SIZE + Page::max_len(&old_value))?;
+                Ok(Self::SetString {
+                    transaction_number,
+                    block,
+                    offset: page.read_u32(offset)? as usize,
+                    old_value,
+                    new_value,
+                })
+            }
+            _ => Err(StormDbError::Corrupt {
+                block: None,
+                message: format!("Unknown log record type {}.", record_type),
+            }),
+        }
+    }
+
+    /// Serializes the record into the bytes that get appended to the log.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut page = Page::builder()
+            .with_block_size(self.serialized_size())
+            .with_buffer()
+            .build();
+
+        match self {
+            Self::Checkpoint => page.write_u32(0, Self::CHECKPOINT)?,
+            Self::NonQuiescentCheckpoint {
+                active_transactions,
+            } => {
+                page.write_u32(0, Self::NON_QUIESCENT_CHECKPOINT)?;
+                page.write_u32(Page::U32_SIZE, active_transactions.len() as u32)?;
+                for (i, transaction_number) in active_transactions.iter().enumerate() {
+                    page.write_u32((i + 2) * Page::U32_SIZE, *transaction_number)?;
+                }
+            }
+            Self::Start { transaction_number } => {
+                page.write_u32(0, Self::START)?;
+                page.write_u32(Page::U32_SIZE, *transaction_number)?;
+            }
+            Self::Commit { transaction_number } => {
+                page.write_u32(0, Self::COMMIT)?;
+                page.write_u32(Page::U32_SIZE, *transaction_number)?;
+            }
+            Self::Rollback { transaction_number } => {
+                page.write_u32(0, Self::ROLLBACK)?;
+                page.write_u32(Page::U32_SIZE, *transaction_number)?;
+            }
+            Self::SetInt {
+                transaction_number,
+                block,
+                offset,
+                old_value,
+                new_value,
+            } => {
+                page.write_u32(0, Self::SET_INT)?;
+                page.write_u32(Page::U32_SIZE, *transaction_number)?;
+                let value_offset = Self::write_block(&mut page, 2 * Page::U32_SIZE, block)?;
+                page.write_u32(value_offset, *offset as u32)?;
+                page.write_int(value_offset + Page::U32_SIZE, *old_value)?;
+                page.write_int(value_offset + Page::U32_SIZE + Page::I32_SIZE, *new_value)?;
+            }
+            Self::SetString {
+                transaction_number,
+                block,
+                offset,
+                old_value,
+                new_value,
+            } => {
+                page.write_u32(0, Self::SET_STRING)?;
+                page.write_u32(Page::U32_SIZE, *transaction_number)?;
+                let value_offset = Self::write_block(&mut page, 2 * Page::U32_SIZE, block)?;
+                page.write_u32(value_offset, *offset as u32)?;
+                page.write_string(value_offset + Page::U32_SIZE, old_value.clone())?;
+                page.write_string(
+                    value_offset + Page::U32_SIZE + Page::max_len(old_value),
+                    new_value.clone(),
+                )?;
+            }
+        }
+
+        Ok(page.byte_buffer)
+    }
+
+    /// Serializes the record and appends it to the log. Returns the lsn of the record.
+    pub fn write_to_log(&self, log_manager: &mut LogManager) -> Result<u32> {
+        log_manager.append(self.to_bytes()?)
+    }
+
+    /// Returns the transaction the record belongs to, None for checkpoints.
+    pub fn transaction_number(&self) -> Option<u32> {
+        match self {
+            Self::Checkpoint | Self::NonQuiescentCheckpoint { .. } => None,
+            Self::Start { transaction_number }
+            | Self::Commit { transaction_number }
+            | Self::Rollback { transaction_number }
+            | Self::SetInt {
+                transaction_number, ..
+            }
+            | Self::SetString {
+                transaction_number, ..
+            } => Some(*transaction_number),
+        }
+    }
+
+    /// Undoes the change described by the record by writing the old value back. Only the SETINT and SETSTRING records change anything,
+    /// the rest are no-ops. The write isn't logged (undoing an undo is not a thing) and the buffer is marked as modified by `transaction_number`,
+    /// which is the transaction doing the undo and not necessarily the one that made the change.
+    pub fn undo(&self, transaction_number: u32, buffer_manager: &mut BufferManager) -> Result<()> {
+        match self {
+            Self::SetInt {
+                block,
+                offset,
+                old_value,
+                ..
+            } => Self::write_int_to_block(
+                transaction_number,
+                buffer_manager,
+                block,
+                *offset,
+                *old_value,
+            ),
+            Self::SetString {
+                block,
+                offset,
+                old_value,
+                ..
+            } => Self::write_string_to_block(
+                transaction_number,
+                buffer_manager,
+                block,
+                *offset,
+                old_value,
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Same as `undo`, except the new value is written.
+    pub fn redo(&self, transaction_number: u32, buffer_manager: &mut BufferManager) -> Result<()> {
+        match self {
+            Self::SetInt {
+                block,
+                offset,
+                new_value,
+                ..
+            } => Self::write_int_to_block(
+                transaction_number,
+                buffer_manager,
+                block,
+                *offset,
+                *new_value,
+            ),
+            Self::SetString {
+                block,
+                offset,
+                new_value,
+                ..
+            } => Self::write_string_to_block(
+                transaction_number,
+                buffer_manager,
+                block,
+                *offset,
+                new_value,
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_int_to_block(
+        transaction_number: u32,
+        buffer_manager: &mut BufferManager,
+        block: &BlockMetadata,
+        offset: usize,
+        value: i32,
+    ) -> Result<()> {
+        let buffer_id = buffer_manager.pin(block)?;
+        let buffer = buffer_manager.buffer_mut(buffer_id)?;
+        let result = buffer.contents_mut().write_int(offset, value);
+        buffer.set_modified(transaction_number, None);
+        buffer_manager.unpin(buffer_id)?;
+        result
+    }
+
+    fn write_string_to_block(
+        transaction_number: u32,
+        buffer_manager: &mut BufferManager,
+        block: &BlockMetadata,
+        offset: usize,
+        value: &str,
+    ) -> Result<()> {
+        let buffer_id = buffer_manager.pin(block)?;
+        let buffer = buffer_manager.buffer_mut(buffer_id)?;
+        let result = buffer
+            .contents_mut()
+            .write_string(offset, value.to_string());
+        buffer.set_modified(transaction_number, None);
+        buffer_manager.unpin(buffer_id)?;
+        result
+    }
+
+    fn serialized_size(&self) -> usize {
+        match self {
+            Self::Checkpoint => Page::U32_SIZE,
+            Self::NonQuiescentCheckpoint {
+                active_transactions,
+            } => (2 + active_transactions.len()) * Page::U32_SIZE,
+            Self::Start { .. } | Self::Commit { .. } | Self::Rollback { .. } => 2 * Page::U32_SIZE,
+            // type, txnum, block number, offset and the two i32 values.
+            Self::SetInt { block, .. } => {
+                4 * Page::U32_SIZE + 2 * Page::I32_SIZE + Page::max_len(&block.file_name())
+            }
+            Self::SetString {
+                block,
+                old_value,
+                new_value,
+                ..
+            } => {
+                4 * Page::U32_SIZE
+                    + Page::max_len(&block.file_name())
+                    + Page::max_len(old_value)
+                    + Page::max_len(new_value)
+            }
+        }
+    }
+
+    // Returns the offset right after the block.
+    fn write_block(page: &mut Page, offset: usize, block: &BlockMetadata) -> Result<usize> {
+        let file_name = block.file_name();
+        let block_number_offset = offset + Page::max_len(&file_name);
+        page.write_string(offset, file_name)?;
+        page.write_u32(block_number_offset, block.block_number() as u32)?;
+        Ok(block_number_offset + Page::U32_SIZE)
+    }
+
+    fn read_block(page: &Page, offset: usize) -> Result<(BlockMetadata, usize)> {
+        let file_name = page.read_string(offset)?;
+        let block_number_offset = offset + Page::max_len(&file_name);
+        let block_number = page.read_u32(block_number_offset)? as usize;
+        Ok((
+            BlockMetadata::new(&file_name, block_number),
+            block_number_offset + Page::U32_SIZE,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(LogRecord::Checkpoint)]
+    #[case(LogRecord::NonQuiescentCheckpoint { active_transactions: vec![] })]
+    #[case(LogRecord::NonQuiescentCheckpoint { active_transactions: vec![3, 8, 12] })]
+    #[case(LogRecord::Start { transaction_number: 7 })]
+    #[case(LogRecord::Commit { transaction_number: 7 })]
+    #[case(LogRecord::Rollback { transaction_number: 7 })]
+    #[case(LogRecord::SetInt {
+        transaction_number: 3,
+        block: BlockMetadata::new("students.tbl", 12),
+        offset: 80,
+        old_value: -42,
+        new_value: 42,
+    })]
+    #[case(LogRecord::SetString {
+        transaction_number: 3,
+        block: BlockMetadata::new("students.tbl", 12),
+        offset: 80,
+        old_value: "old value".to_string(),
+        new_value: "new value".to_string(),
+    })]
+    #[case(LogRecord::SetString {
+        transaction_number: 3,
+        block: BlockMetadata::new("students.tbl", 0),
+        offset: 0,
+        old_value: String::new(),
+        new_value: "ünïcödé".to_string(),
+    })]
+    fn test_log_record_round_trip(#[case] record: LogRecord) -> Result<()> {
+        let bytes = record.to_bytes()?;
+        assert_eq!(LogRecord::from_bytes(bytes)?, record);
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_record_unknown_type() {
+        let mut page = Page::builder().with_block_size(8).with_buffer().build();
+        page.write_u32(0, 99).expect("failed to write type");
+        assert_eq!(
+            LogRecord::from_bytes(page.byte_buffer),
+            Err(StormDbError::Corrupt {
+                block: None,
+                message: "Unknown log record type 99.".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_log_record_transaction_number() {
+        assert_eq!(LogRecord::Checkpoint.transaction_number(), None);
+        assert_eq!(
+            LogRecord::Commit {
+                transaction_number: 4
+            }
+            .transaction_number(),
+            Some(4)
+        );
+    }
+}
@@ -0,0 +1,784 @@
+/*
+Transaction API:
+  public Transaction(FileMgr fm, LogMgr lm, BufferMgr bm);
+  public void commit();
+  public void rollback();
+  public void recover();
+  public void pin(BlockId blk);
+  public void unpin(BlockId blk);
+  public int getInt(BlockId blk, int offset);
+  public String getString(BlockId blk, int offset);
+  public void setInt(BlockId blk, int offset, int val, boolean okToLog);
+  public void setString(BlockId blk, int offset, String val, boolean okToLog);
+  public int availableBuffs();
+  public int size(String filename);
+  public BlockId append(String filename);
+  public int blockSize();
+ */
+mod buffer_list;
+mod concurrency_manager;
+mod lock_table;
+mod log_record;
+mod recovery_manager;
+mod version_store;
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    BlockMetadata, BufferManager, FileManager, LogManager, Page, StormDbError, error::Result,
+};
+
+use buffer_list::BufferList;
+use concurrency_manager::ConcurrencyManager;
+pub use lock_table::{DeadlockPolicy, LockTable, VictimSelection};
+pub use log_record::LogRecord;
+use recovery_manager::RecoveryManager;
+pub use recovery_manager::{RecoveryMode, checkpoint, truncate_log};
+pub use version_store::VersionStore;
+use version_store::{Snapshot, Value, Write};
+
+// Transaction numbers are handed out process wide, same as the static counter in the book.
+static NEXT_TRANSACTION_NUMBER: AtomicU32 = AtomicU32::new(0);
+
+pub struct Transaction {
+    file_manager: Arc<FileManager>,
+    buffer_manager: Arc<Mutex<BufferManager>>,
+    recovery_manager: RecoveryManager,
+    concurrency_manager: ConcurrencyManager,
+    // Only there in snapshot isolation mode.
+    snapshot: Option<Snapshot>,
+    transaction_number: u32,
+    buffers: BufferList,
+}
+
+impl Transaction {
+    /// Starts a new transaction in undo-only recovery mode, which writes a START record to the log.
+    /// It doesn't take any locks, so it's only safe when nothing else runs at the same time. Use the builder with a lock table otherwise.
+    pub fn new(
+        file_manager: Arc<FileManager>,
+        log_manager: Arc<Mutex<LogManager>>,
+        buffer_manager: Arc<Mutex<BufferManager>>,
+    ) -> Result<Self> {
+        Self::builder(file_manager, log_manager, buffer_manager).build()
+    }
+
+    pub fn builder(
+        file_manager: Arc<FileManager>,
+        log_manager: Arc<Mutex<LogManager>>,
+        buffer_manager: Arc<Mutex<BufferManager>>,
+    ) -> TransactionBuilder {
+        TransactionBuilder::new(file_manager, log_manager, buffer_manager)
+    }
+
+    pub fn transaction_number(&self) -> u32 {
+        self.transaction_number
+    }
+
+    /// The commit timestamp the transaction reads as of, None unless it runs in snapshot isolation mode.
+    pub fn read_timestamp(&self) -> Option<u64> {
+        self.snapshot.as_ref().map(Snapshot::read_timestamp)
+    }
+
+    /// Commits the transaction. The COMMIT record is on disk by the time this returns, after that every lock is released and
+    /// everything is unpinned.
+    /// In snapshot isolation mode this is also when the writes hit the pages. If somebody else committed a write overlapping one of
+    /// ours after our snapshot was taken the transaction is rolled back instead and `StormDbError::WriteConflict` comes back. Any other
+    /// failure rolls it back too, with none of its writes on the pages.
+    pub fn commit(&mut self) -> Result<()> {
+        if let Some(snapshot) = self.snapshot.take()
+            && let Err(error) = self.commit_snapshot(&snapshot)
+        {
+            self.rollback()?;
+            return Err(error);
+        }
+
+        self.recovery_manager.commit()?;
+        self.concurrency_manager.release();
+        self.buffers.unpin_all()
+    }
+
+    /// Undoes every change made by the transaction and writes a ROLLBACK record, then releases the locks and unpins everything.
+    pub fn rollback(&mut self) -> Result<()> {
+        // Pending snapshot writes never made it to a page, there's nothing to undo for them.
+        self.snapshot = None;
+        self.recovery_manager.rollback()?;
+        self.concurrency_manager.release();
+        self.buffers.unpin_all()
+    }
+
+    /// Brings the database back to a consistent state after a crash. Should run in its own transaction before any other one starts.
+    pub fn recover(&mut self) -> Result<()> {
+        self.recovery_manager.recover()
+    }
+
+    /// Pins the block for the rest of the transaction, or until it's unpinned.
+    pub fn pin(&mut self, block: &BlockMetadata) -> Result<()> {
+        self.buffers.pin(block)
+    }
+
+    pub fn unpin(&mut self, block: &BlockMetadata) -> Result<()> {
+        self.buffers.unpin(block)
+    }
+
+    /// Reads an int from the block, which has to be pinned by the transaction. Takes a shared lock on the block, or reads from the
+    /// snapshot in snapshot isolation mode.
+    pub fn get_int(&mut self, block: &BlockMetadata, offset: usize) -> Result<i32> {
+        self.get_value(block, |page| page.read_int(offset).map(Value::Int))?
+            .into_int()
+    }
+
+    /// Reads a string from the block, which has to be pinned by the transaction. Takes a shared lock on the block, or reads from the
+    /// snapshot in snapshot isolation mode.
+    pub fn get_string(&mut self, block: &BlockMetadata, offset: usize) -> Result<String> {
+        self.get_value(block, |page| page.read_string(offset).map(Value::String))?
+            .into_string()
+    }
+
+    /// Writes an int to the block, which has to be pinned by the transaction, under an exclusive lock. With `ok_to_log` a SETINT record
+    /// holding the old value is written first so the change can be undone. In snapshot isolation mode the write waits for commit.
+    pub fn set_int(
+        &mut self,
+        block: &BlockMetadata,
+        offset: usize,
+        value: i32,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.set_value(block, offset, Value::Int(value), ok_to_log)
+    }
+
+    /// Writes a string to the block, which has to be pinned by the transaction, under an exclusive lock. With `ok_to_log` a SETSTRING
+    /// record holding the old value is written first so the change can be undone. In snapshot isolation mode the write waits for commit.
+    pub fn set_string(
+        &mut self,
+        block: &BlockMetadata,
+        offset: usize,
+        value: &str,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.set_value(block, offset, Value::String(value.to_string()), ok_to_log)
+    }
+
+    /// Returns the number of blocks in the file. Takes a shared lock on the end of the file so nobody appends in the meantime.
+    pub fn size(&mut self, file_name: &str) -> Result<usize> {
+        self.concurrency_manager
+            .slock(&Self::end_of_file(file_name))?;
+        Ok(self
+            .file_manager
+            .last_block_index(file_name)?
+            .map_or(0, |last_block_index| last_block_index + 1))
+    }
+
+    /// Appends a new block to the file. Takes an exclusive lock on the end of the file.
+    pub fn append(&mut self, file_name: &str) -> Result<BlockMetadata> {
+        self.concurrency_manager
+            .xlock(&Self::end_of_file(file_name))?;
+        self.file_manager.append(file_name)
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.file_manager.block_size()
+    }
+
+    pub fn available_buffers(&self) -> usize {
+        self.buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned")
+            .available()
+    }
+
+    // The book locks a dummy block -1 to stand in for the end of the file. Block numbers are usize here so it's the last one instead.
+    fn end_of_file(file_name: &str) -> BlockMetadata {
+        BlockMetadata::new(file_name, usize::MAX)
+    }
+
+    fn get_value(
+        &mut self,
+        block: &BlockMetadata,
+        read_page: impl FnOnce(&Page) -> Result<Value>,
+    ) -> Result<Value> {
+        let buffer_id = self.buffer_id(block)?;
+        let buffer_manager = || {
+            self.buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+        };
+
+        match &self.snapshot {
+            Some(snapshot) => {
+                let page = snapshot.read(block, || {
+                    let buffer_manager = buffer_manager();
+                    let contents = buffer_manager.buffer(buffer_id)?.contents();
+                    Ok(Page {
+                        block_size: contents.block_size,
+                        byte_buffer: contents.byte_buffer.clone(),
+                    })
+                })?;
+                read_page(&page)
+            }
+            None => {
+                self.concurrency_manager.slock(block)?;
+                read_page(buffer_manager().buffer(buffer_id)?.contents())
+            }
+        }
+    }
+
+    fn set_value(
+        &mut self,
+        block: &BlockMetadata,
+        offset: usize,
+        value: Value,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        let buffer_id = self.buffer_id(block)?;
+        // Snapshot writes only get logged at commit, too late to turn down one whose record doesn't fit in the log. Without a snapshot
+        // the record is written before the page is touched, so it fails early enough on its own.
+        if self.snapshot.is_some() && ok_to_log {
+            let buffer_manager = self
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned");
+            self.recovery_manager.check_loggable(
+                buffer_manager.buffer(buffer_id)?,
+                offset,
+                &value,
+            )?;
+        }
+        match &mut self.snapshot {
+            Some(snapshot) => snapshot.write(block, offset, value, ok_to_log),
+            None => {
+                self.concurrency_manager.xlock(block)?;
+                self.write_value(buffer_id, offset, &value, ok_to_log)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the snapshot's writes to their pages. Pinning the blocks again (they might have been unpinned since) and checking the writes
+    // fit happens before the first page changes. If logging fails halfway through, the bytes already written are put back.
+    fn commit_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let mut commit = snapshot.begin_commit()?;
+        for write in snapshot.writes() {
+            if self.buffers.buffer_id(&write.block).is_none() {
+                self.buffers.pin(&write.block)?;
+            }
+            if write.end() > self.file_manager.block_size() {
+                return Err(StormDbError::OutOfBound(format!(
+                    "Write of bytes {}..{} doesn't fit in {}",
+                    write.offset,
+                    write.end(),
+                    write.block
+                )));
+            }
+        }
+
+        let mut applied: Vec<(usize, &Write, Vec<u8>)> = Vec::new();
+        for write in snapshot.writes() {
+            let buffer_id = self.buffer_id(&write.block)?;
+            let before = self
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .buffer(buffer_id)?
+                .contents()
+                .bytes()[write.offset..write.end()]
+                .to_vec();
+            if let Err(error) =
+                self.write_value(buffer_id, write.offset, &write.value, write.ok_to_log)
+            {
+                let mut buffer_manager = self
+                    .buffer_manager
+                    .lock()
+                    .expect("buffer manager mutex poisoned");
+                for (buffer_id, write, before) in applied.iter().rev() {
+                    buffer_manager
+                        .buffer_mut(*buffer_id)
+                        .expect("the write went through, so the buffer is in the pool")
+                        .contents_mut()
+                        .byte_buffer[write.offset..write.end()]
+                        .copy_from_slice(before);
+                }
+                return Err(error);
+            }
+            applied.push((buffer_id, write, before));
+        }
+
+        for (_, write, before) in applied {
+            commit.overwrote(write, before);
+        }
+        commit.finish();
+        Ok(())
+    }
+
+    fn write_value(
+        &mut self,
+        buffer_id: usize,
+        offset: usize,
+        value: &Value,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        let mut buffer_manager = self
+            .buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned");
+        let buffer = buffer_manager.buffer_mut(buffer_id)?;
+
+        let lsn = match value {
+            Value::Int(value) => {
+                let lsn = if ok_to_log {
+                    Some(self.recovery_manager.set_int(buffer, offset, *value)?)
+                } else {
+                    None
+                };
+                buffer.contents_mut().write_int(offset, *value)?;
+                lsn
+            }
+            Value::String(value) => {
+                let lsn = if ok_to_log {
+                    Some(self.recovery_manager.set_string(buffer, offset, value)?)
+                } else {
+                    None
+                };
+                buffer.contents_mut().write_string(offset, value.clone())?;
+                lsn
+            }
+        };
+        buffer.set_modified(self.transaction_number, lsn);
+        Ok(())
+    }
+
+    fn buffer_id(&self, block: &BlockMetadata) -> Result<usize> {
+        self.buffers.buffer_id(block).ok_or_else(|| {
+            StormDbError::BlockNotPinned(format!(
+                "Transaction {} has not pinned {}",
+                self.transaction_number, block
+            ))
+        })
+    }
+}
+
+pub struct TransactionBuilder {
+    file_manager: Arc<FileManager>,
+    log_manager: Arc<Mutex<LogManager>>,
+    buffer_manager: Arc<Mutex<BufferManager>>,
+    lock_table: Option<Arc<LockTable>>,
+    version_store: Option<Arc<VersionStore>>,
+    recovery_mode: RecoveryMode,
+}
+
+impl TransactionBuilder {
+    pub fn new(
+        file_manager: Arc<FileManager>,
+        log_manager: Arc<Mutex<LogManager>>,
+        buffer_manager: Arc<Mutex<BufferManager>>,
+    ) -> Self {
+        Self {
+            file_manager,
+            log_manager,
+            buffer_manager,
+            lock_table: None,
+            version_store: None,
+            recovery_mode: RecoveryMode::default(),
+        }
+    }
+
+    /// Locks blocks through the given table. Every transaction running against the same database has to share it.
+    pub fn with_lock_table(mut self, lock_table: Arc<LockTable>) -> Self {
+        self.lock_table = Some(lock_table);
+        self
+    }
+
+    /// Runs the transaction under snapshot isolation instead of two phase locking, it doesn't lock anything even with a lock table.
+    /// Every transaction touching the same database has to share the version store and should be in this mode, writes made outside
+    /// of it aren't versioned.
+    pub fn with_snapshot_isolation(mut self, version_store: Arc<VersionStore>) -> Self {
+        self.version_store = Some(version_store);
+        self
+    }
+
+    /// Every transaction touching the same database should use the same recovery mode.
+    pub fn with_recovery_mode(mut self, recovery_mode: RecoveryMode) -> Self {
+        self.recovery_mode = recovery_mode;
+        self
+    }
+
+    /// Starts the transaction, which writes a START record to the log.
+    pub fn build(self) -> Result<Transaction> {
+        let transaction_number = NEXT_TRANSACTION_NUMBER.fetch_add(1, Ordering::SeqCst) + 1;
+        let recovery_manager = RecoveryManager::new(
+            transaction_number,
+            self.log_manager,
+            self.buffer_manager.clone(),
+            self.recovery_mode,
+        )?;
+
+        let (lock_table, snapshot) = match self.version_store {
+            Some(version_store) => (None, Some(Snapshot::begin(version_store))),
+            None => (self.lock_table, None),
+        };
+
+        Ok(Transaction {
+            file_manager: self.file_manager,
+            buffers: BufferList::new(self.buffer_manager.clone()),
+            buffer_manager: self.buffer_manager,
+            recovery_manager,
+            concurrency_manager: ConcurrencyManager::new(transaction_number, lock_table),
+            snapshot,
+            transaction_number,
+        })
+    }
+}
+
+// The storage stack the tests of every module in here run against.
+#[cfg(test)]
+mod test_database {
+    use std::sync::{Arc, Mutex};
+
+    use tempdir::TempDir;
+
+    use super::{TransactionBuilder, VersionStore};
+    use crate::{BlockMetadata, BufferManager, FileManager, LogManager, Page};
+
+    pub(super) const BLOCK_SIZE: usize = 400;
+
+    pub(super) struct Database {
+        pub(super) file_manager: Arc<FileManager>,
+        pub(super) log_manager: Arc<Mutex<LogManager>>,
+        pub(super) buffer_manager: Arc<Mutex<BufferManager>>,
+        // Only used by snapshot isolation transactions.
+        pub(super) version_store: Arc<VersionStore>,
+    }
+
+    impl Database {
+        // Dropping a Database without committing is how the tests crash: whatever only lived in the buffer pool or the log page is gone.
+        pub(super) fn open(tmp_dir: &TempDir) -> Self {
+            let file_manager = Arc::new(
+                FileManager::new(tmp_dir.path().to_owned(), BLOCK_SIZE)
+                    .expect("failed to create file manager"),
+            );
+            let log_manager = Arc::new(Mutex::new(
+                LogManager::builder("log.wal".to_string(), file_manager.clone())
+                    .build()
+                    .expect("failed to build log manager"),
+            ));
+            let buffer_manager = Arc::new(Mutex::new(BufferManager::new(
+                file_manager.clone(),
+                log_manager.clone(),
+                8,
+            )));
+            Self {
+                file_manager,
+                log_manager,
+                buffer_manager,
+                version_store: Arc::new(VersionStore::new()),
+            }
+        }
+
+        pub(super) fn builder(&self) -> TransactionBuilder {
+            TransactionBuilder::new(
+                self.file_manager.clone(),
+                self.log_manager.clone(),
+                self.buffer_manager.clone(),
+            )
+        }
+
+        // Reads straight from the file, bypassing the buffer pool. A block that never made it to disk reads as zeros.
+        pub(super) fn read_from_disk(&self, block: &BlockMetadata) -> Page {
+            let mut page = Page::builder()
+                .with_block_size(BLOCK_SIZE)
+                .with_buffer()
+                .build();
+            self.file_manager
+                .read_zero_filled(block, &mut page)
+                .expect("failed to read block");
+            page
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    use test_database::{BLOCK_SIZE, Database};
+
+    fn new_transaction(database: &Database) -> Transaction {
+        database
+            .builder()
+            .build()
+            .expect("failed to start transaction")
+    }
+
+    fn locking_transaction(database: &Database, lock_table: &Arc<LockTable>) -> Transaction {
+        database
+            .builder()
+            .with_lock_table(lock_table.clone())
+            .build()
+            .expect("failed to start transaction")
+    }
+
+    #[test]
+    fn test_committed_changes_visible_to_next_transaction() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 1);
+
+        let mut first = new_transaction(&database);
+        first.pin(&block).expect("failed to pin");
+        first
+            .set_int(&block, 80, 1, false)
+            .expect("failed to set int");
+        first
+            .set_string(&block, 40, "one", false)
+            .expect("failed to set string");
+        first.commit().expect("failed to commit");
+
+        let mut second = new_transaction(&database);
+        second.pin(&block).expect("failed to pin");
+        assert_eq!(second.get_int(&block, 80).expect("failed to get int"), 1);
+        assert_eq!(
+            second.get_string(&block, 40).expect("failed to get string"),
+            "one"
+        );
+        second
+            .set_int(&block, 80, 2, true)
+            .expect("failed to set int");
+        second
+            .set_string(&block, 40, "one!", true)
+            .expect("failed to set string");
+        second.commit().expect("failed to commit");
+
+        let mut third = new_transaction(&database);
+        third.pin(&block).expect("failed to pin");
+        assert_eq!(third.get_int(&block, 80).expect("failed to get int"), 2);
+        assert_eq!(
+            third.get_string(&block, 40).expect("failed to get string"),
+            "one!"
+        );
+        third.commit().expect("failed to commit");
+
+        assert_eq!(
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .available(),
+            8
+        );
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_rollback_restores_old_values() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut first = new_transaction(&database);
+        first.pin(&block).expect("failed to pin");
+        first
+            .set_int(&block, 80, 100, true)
+            .expect("failed to set int");
+        first
+            .set_string(&block, 40, "before", true)
+            .expect("failed to set string");
+        first.commit().expect("failed to commit");
+
+        let mut second = new_transaction(&database);
+        second.pin(&block).expect("failed to pin");
+        second
+            .set_int(&block, 80, 200, true)
+            .expect("failed to set int");
+        second
+            .set_int(&block, 80, 300, true)
+            .expect("failed to set int");
+        second
+            .set_string(&block, 40, "after", true)
+            .expect("failed to set string");
+        assert_eq!(second.get_int(&block, 80).expect("failed to get int"), 300);
+        second.rollback().expect("failed to rollback");
+
+        let mut third = new_transaction(&database);
+        third.pin(&block).expect("failed to pin");
+        assert_eq!(third.get_int(&block, 80).expect("failed to get int"), 100);
+        assert_eq!(
+            third.get_string(&block, 40).expect("failed to get string"),
+            "before"
+        );
+        third.commit().expect("failed to commit");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_log_records_written_in_order() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut transaction = new_transaction(&database);
+        let transaction_number = transaction.transaction_number();
+        transaction.pin(&block).expect("failed to pin");
+        transaction
+            .set_int(&block, 0, 5, true)
+            .expect("failed to set int");
+        transaction.commit().expect("failed to commit");
+
+        let records: Vec<LogRecord> = database
+            .log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .iterator()
+            .expect("failed to create iterator")
+            .map(|bytes| {
+                LogRecord::from_bytes(bytes.expect("failed to read log"))
+                    .expect("failed to read record")
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                LogRecord::Commit { transaction_number },
+                LogRecord::SetInt {
+                    transaction_number,
+                    block: block.clone(),
+                    offset: 0,
+                    old_value: 0,
+                    new_value: 5,
+                },
+                LogRecord::Start { transaction_number },
+            ]
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_write_too_big_for_the_log_changes_nothing() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut transaction = new_transaction(&database);
+        transaction.pin(&block).expect("failed to pin");
+        // Fits in the page, but its SETSTRING record doesn't fit in a log block.
+        let result = transaction.set_string(&block, 0, &"x".repeat(BLOCK_SIZE - 20), true);
+        assert!(matches!(result, Err(StormDbError::OutOfBound(_))));
+        assert_eq!(
+            transaction
+                .get_string(&block, 0)
+                .expect("failed to get string"),
+            ""
+        );
+
+        // The log still takes records after that.
+        transaction
+            .set_string(&block, 0, "fits", true)
+            .expect("failed to set string");
+        transaction.commit().expect("failed to commit");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_get_int_on_unpinned_block_fails() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let mut transaction = new_transaction(&database);
+
+        let result = transaction.get_int(&BlockMetadata::new("test.tbl", 0), 0);
+        assert!(matches!(result, Err(StormDbError::BlockNotPinned(_))));
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_write_blocks_readers_until_commit() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_millis(50)));
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut writer = locking_transaction(&database, &lock_table);
+        writer.pin(&block).expect("failed to pin");
+        writer
+            .set_int(&block, 0, 7, true)
+            .expect("failed to set int");
+
+        let mut reader = locking_transaction(&database, &lock_table);
+        reader.pin(&block).expect("failed to pin");
+        assert!(matches!(
+            reader.get_int(&block, 0),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        writer.commit().expect("failed to commit");
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 7);
+        reader.commit().expect("failed to commit");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_readers_block_writer_until_rollback() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_millis(50)));
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut first_reader = locking_transaction(&database, &lock_table);
+        let mut second_reader = locking_transaction(&database, &lock_table);
+        for reader in [&mut first_reader, &mut second_reader] {
+            reader.pin(&block).expect("failed to pin");
+            reader.get_int(&block, 0).expect("failed to get int");
+        }
+
+        let mut writer = locking_transaction(&database, &lock_table);
+        writer.pin(&block).expect("failed to pin");
+        assert!(matches!(
+            writer.set_int(&block, 0, 1, true),
+            Err(StormDbError::LockAbort(_))
+        ));
+        // Locks are only let go of at the very end, unpinning doesn't count.
+        first_reader.unpin(&block).expect("failed to unpin");
+        assert!(matches!(
+            writer.set_int(&block, 0, 1, true),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        first_reader.rollback().expect("failed to rollback");
+        second_reader.commit().expect("failed to commit");
+        writer
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        writer.commit().expect("failed to commit");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_size_blocks_append() {
+        let tmp_dir = TempDir::new("test_transaction").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let lock_table = Arc::new(LockTable::with_max_wait(Duration::from_millis(50)));
+
+        let mut reader = locking_transaction(&database, &lock_table);
+        assert_eq!(reader.size("test.tbl").expect("failed to get size"), 0);
+
+        let mut appender = locking_transaction(&database, &lock_table);
+        assert!(matches!(
+            appender.append("test.tbl"),
+            Err(StormDbError::LockAbort(_))
+        ));
+
+        reader.commit().expect("failed to commit");
+        appender.append("test.tbl").expect("failed to append");
+        appender.commit().expect("failed to commit");
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
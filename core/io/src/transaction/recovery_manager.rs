@@ -0,0 +1,860 @@
+/*
+Recovery Manager API:
+  public RecoveryMgr(Transaction tx, int txnum, LogMgr lm, BufferMgr bm);
+  public void commit();
+  public void rollback();
+  public void recover();
+  public int setInt(Buffer buff, int offset, int newval);
+  public int setString(Buffer buff, int offset, String newval);
+
+The book passes the transaction around so undo can go through tx.setInt. Here undo/redo talk to the buffer manager directly,
+that way the recovery manager can live inside the transaction without the two borrowing each other.
+
+Checkpoints come in two flavours. CHECKPOINT is written when nothing is active, recovery never has to look past it.
+NQCKPT lists the transactions that were active when it was written, recovery has to keep going back until it has seen the START
+of every one of those that never finished.
+*/
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use crate::{Buffer, BufferManager, LogManager, error::Result};
+
+use super::{LogRecord, Value};
+
+/// How the recovery manager makes committed changes durable and what recovery does about them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryMode {
+    /// Commit forces every buffer the transaction modified to disk, so recovery only ever has to undo uncommitted changes.
+    #[default]
+    UndoOnly,
+    /// Commit only forces the log. Recovery undoes uncommitted changes going backwards and then replays committed ones going forwards.
+    RedoUndo,
+}
+
+pub(crate) struct RecoveryManager {
+    log_manager: Arc<Mutex<LogManager>>,
+    buffer_manager: Arc<Mutex<BufferManager>>,
+    transaction_number: u32,
+    mode: RecoveryMode,
+}
+
+impl RecoveryManager {
+    /// Writes a START record for the transaction.
+    pub(crate) fn new(
+        transaction_number: u32,
+        log_manager: Arc<Mutex<LogManager>>,
+        buffer_manager: Arc<Mutex<BufferManager>>,
+        mode: RecoveryMode,
+    ) -> Result<Self> {
+        {
+            let mut log_manager = log_manager.lock().expect("log manager mutex poisoned");
+            LogRecord::Start { transaction_number }.write_to_log(&mut log_manager)?;
+            log_manager.transaction_started(transaction_number);
+        }
+        Ok(Self {
+            log_manager,
+            buffer_manager,
+            transaction_number,
+            mode,
+        })
+    }
+
+    /// Writes a COMMIT record and group flushes the log up to it. In undo-only mode the modified buffers get flushed first.
+    pub(crate) fn commit(&mut self) -> Result<()> {
+        if self.mode == RecoveryMode::UndoOnly {
+            self.buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .flush_all(self.transaction_number)?;
+        }
+        let lsn = {
+            let mut log_manager = self.log_manager.lock().expect("log manager mutex poisoned");
+            let lsn = LogRecord::Commit {
+                transaction_number: self.transaction_number,
+            }
+            .write_to_log(&mut log_manager)?;
+            log_manager.transaction_finished(self.transaction_number);
+            lsn
+        };
+        // Other transactions committing around the same time share the write and the sync.
+        LogManager::group_flush(&self.log_manager, lsn)
+    }
+
+    /// Undoes the transaction's changes, flushes them and writes a ROLLBACK record.
+    pub(crate) fn rollback(&mut self) -> Result<()> {
+        self.do_rollback()?;
+        self.buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned")
+            .flush_all(self.transaction_number)?;
+        let mut log_manager = self.log_manager.lock().expect("log manager mutex poisoned");
+        let lsn = LogRecord::Rollback {
+            transaction_number: self.transaction_number,
+        }
+        .write_to_log(&mut log_manager)?;
+        log_manager.transaction_finished(self.transaction_number);
+        log_manager.flush(lsn)
+    }
+
+    /// Restores the database to a consistent state after a crash and writes a quiescent checkpoint. Meant to run before any other transaction starts.
+    pub(crate) fn recover(&mut self) -> Result<()> {
+        self.do_recover()?;
+        self.buffer_manager
+            .lock()
+            .expect("buffer manager mutex poisoned")
+            .flush_all(self.transaction_number)?;
+        let lsn = LogRecord::Checkpoint
+            .write_to_log(&mut self.log_manager.lock().expect("log manager mutex poisoned"))?;
+        self.log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .flush(lsn)
+    }
+
+    /// Writes a SETINT record for the change about to be made to the buffer and returns its lsn.
+    pub(crate) fn set_int(
+        &mut self,
+        buffer: &Buffer,
+        offset: usize,
+        new_value: i32,
+    ) -> Result<u32> {
+        self.set_record(buffer, offset, &Value::Int(new_value))?
+            .write_to_log(&mut self.log_manager.lock().expect("log manager mutex poisoned"))
+    }
+
+    /// Writes a SETSTRING record for the change about to be made to the buffer and returns its lsn.
+    pub(crate) fn set_string(
+        &mut self,
+        buffer: &Buffer,
+        offset: usize,
+        new_value: &str,
+    ) -> Result<u32> {
+        self.set_record(buffer, offset, &Value::String(new_value.to_string()))?
+            .write_to_log(&mut self.log_manager.lock().expect("log manager mutex poisoned"))
+    }
+
+    /// Errors with `StormDbError::OutOfBound` if the record for writing the value to the buffer wouldn't fit in the log. Logs nothing.
+    pub(crate) fn check_loggable(
+        &self,
+        buffer: &Buffer,
+        offset: usize,
+        new_value: &Value,
+    ) -> Result<()> {
+        let record_len = self
+            .set_record(buffer, offset, new_value)?
+            .to_bytes()?
+            .len();
+        self.log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .check_fits(record_len)
+    }
+
+    // The SETINT/SETSTRING record for the change, holding the value currently in the buffer as the old one.
+    fn set_record(&self, buffer: &Buffer, offset: usize, new_value: &Value) -> Result<LogRecord> {
+        let block = buffer
+            .block()
+            .expect("pinned buffer should have a block")
+            .clone();
+        Ok(match new_value {
+            Value::Int(new_value) => LogRecord::SetInt {
+                transaction_number: self.transaction_number,
+                block,
+                offset,
+                old_value: buffer.contents().read_int(offset)?,
+                new_value: *new_value,
+            },
+            Value::String(new_value) => LogRecord::SetString {
+                transaction_number: self.transaction_number,
+                block,
+                offset,
+                old_value: buffer.contents().read_string(offset)?,
+                new_value: new_value.clone(),
+            },
+        })
+    }
+
+    // Walks the log backwards undoing this transaction's changes until its START record.
+    fn do_rollback(&mut self) -> Result<()> {
+        let log_iterator = self
+            .log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .iterator()?;
+        for bytes in log_iterator {
+            let record = LogRecord::from_bytes(bytes?)?;
+            if record.transaction_number() != Some(self.transaction_number) {
+                continue;
+            }
+            if matches!(record, LogRecord::Start { .. }) {
+                break;
+            }
+            record.undo(
+                self.transaction_number,
+                &mut self
+                    .buffer_manager
+                    .lock()
+                    .expect("buffer manager mutex poisoned"),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Walks the log backwards until the last checkpoint (or the start of the log) undoing every change of a transaction that neither committed
+    // nor rolled back. In redo/undo mode the changes of committed transactions are collected on the way and replayed oldest first afterwards.
+    // Checkpoints flush every modified buffer, so nothing from before one ever needs a redo.
+    fn do_recover(&mut self) -> Result<()> {
+        let mut finished_transactions = HashSet::new();
+        let mut committed_transactions = HashSet::new();
+        let mut redo_records = Vec::new();
+        // Set once we pass a NQCKPT, holds the transactions whose START we still need to reach.
+        let mut pending_starts: Option<HashSet<u32>> = None;
+
+        let log_iterator = self
+            .log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .iterator()?;
+        for bytes in log_iterator {
+            let record = LogRecord::from_bytes(bytes?)?;
+            match record {
+                LogRecord::Checkpoint => break,
+                LogRecord::NonQuiescentCheckpoint {
+                    ref active_transactions,
+                } => {
+                    let unfinished: HashSet<u32> = active_transactions
+                        .iter()
+                        .filter(|transaction_number| {
+                            !finished_transactions.contains(*transaction_number)
+                        })
+                        .copied()
+                        .collect();
+                    if unfinished.is_empty() {
+                        break;
+                    }
+                    pending_starts = Some(unfinished);
+                }
+                LogRecord::Start { transaction_number } => {
+                    if let Some(pending) = pending_starts.as_mut() {
+                        pending.remove(&transaction_number);
+                        if pending.is_empty() {
+                            break;
+                        }
+                    }
+                }
+                LogRecord::Commit { transaction_number } => {
+                    finished_transactions.insert(transaction_number);
+                    committed_transactions.insert(transaction_number);
+                }
+                LogRecord::Rollback { transaction_number } => {
+                    finished_transactions.insert(transaction_number);
+                }
+                LogRecord::SetInt {
+                    transaction_number, ..
+                }
+                | LogRecord::SetString {
+                    transaction_number, ..
+                } => {
+                    if !finished_transactions.contains(&transaction_number) {
+                        record.undo(
+                            self.transaction_number,
+                            &mut self
+                                .buffer_manager
+                                .lock()
+                                .expect("buffer manager mutex poisoned"),
+                        )?;
+                    } else if self.mode == RecoveryMode::RedoUndo
+                        && pending_starts.is_none()
+                        && committed_transactions.contains(&transaction_number)
+                    {
+                        redo_records.push(record);
+                    }
+                }
+            }
+        }
+
+        for record in redo_records.iter().rev() {
+            record.redo(
+                self.transaction_number,
+                &mut self
+                    .buffer_manager
+                    .lock()
+                    .expect("buffer manager mutex poisoned"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Flushes every modified buffer and writes a checkpoint record, returns its lsn. If no transaction is active the checkpoint is a
+/// quiescent CHECKPOINT, otherwise it's a NQCKPT listing the active transactions.
+// The buffer manager stays locked until the record is in the log. Transactions log and change a page under that lock, so no change
+// can slip in after the flush but before the record, where redo would skip it even though it never made it to disk.
+pub fn checkpoint(
+    log_manager: &Arc<Mutex<LogManager>>,
+    buffer_manager: &Arc<Mutex<BufferManager>>,
+) -> Result<u32> {
+    let mut buffer_manager = buffer_manager
+        .lock()
+        .expect("buffer manager mutex poisoned");
+    buffer_manager.flush_dirty()?;
+
+    let mut log_manager = log_manager.lock().expect("log manager mutex poisoned");
+    let active_transactions = log_manager.active_transactions();
+    let record = if active_transactions.is_empty() {
+        LogRecord::Checkpoint
+    } else {
+        LogRecord::NonQuiescentCheckpoint {
+            active_transactions,
+        }
+    };
+    let lsn = record.write_to_log(&mut log_manager)?;
+    log_manager.flush(lsn)?;
+    Ok(lsn)
+}
+
+/// Drops the log blocks recovery will never read again, returns how many blocks went away. That's everything before the block of the
+/// latest CHECKPOINT, or for a NQCKPT everything before the block holding the oldest START of the transactions it lists that were still unfinished.
+pub fn truncate_log(log_manager: &Arc<Mutex<LogManager>>) -> Result<usize> {
+    let mut finished_transactions = HashSet::new();
+    let mut pending_starts: Option<HashSet<u32>> = None;
+    let mut first_needed_block = None;
+
+    let mut log_iterator = log_manager
+        .lock()
+        .expect("log manager mutex poisoned")
+        .iterator()?;
+    while let Some(bytes) = log_iterator.next() {
+        let block_number = log_iterator.block().block_number();
+        match LogRecord::from_bytes(bytes?)? {
+            LogRecord::Checkpoint => {
+                first_needed_block = Some(block_number);
+                break;
+            }
+            LogRecord::NonQuiescentCheckpoint {
+                active_transactions,
+            } => {
+                let unfinished: HashSet<u32> = active_transactions
+                    .into_iter()
+                    .filter(|transaction_number| {
+                        !finished_transactions.contains(transaction_number)
+                    })
+                    .collect();
+                if unfinished.is_empty() {
+                    first_needed_block = Some(block_number);
+                    break;
+                }
+                pending_starts = Some(unfinished);
+            }
+            LogRecord::Start { transaction_number } => {
+                if let Some(pending) = pending_starts.as_mut() {
+                    pending.remove(&transaction_number);
+                    if pending.is_empty() {
+                        first_needed_block = Some(block_number);
+                        break;
+                    }
+                }
+            }
+            LogRecord::Commit { transaction_number }
+            | LogRecord::Rollback { transaction_number } => {
+                finished_transactions.insert(transaction_number);
+            }
+            _ => {}
+        }
+    }
+
+    match first_needed_block {
+        Some(block_number) => {
+            log_manager
+                .lock()
+                .expect("log manager mutex poisoned")
+                .truncate_before(block_number)?;
+            Ok(block_number)
+        }
+        // No checkpoint at all, recovery might need every single record.
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BlockMetadata, Transaction};
+
+    use super::*;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    use crate::transaction::test_database::Database;
+
+    fn transaction(database: &Database, mode: RecoveryMode) -> Transaction {
+        database
+            .builder()
+            .with_recovery_mode(mode)
+            .build()
+            .expect("failed to start transaction")
+    }
+
+    #[test]
+    fn test_undo_only_recovery_undoes_uncommitted_changes() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut committed = transaction(&database, RecoveryMode::UndoOnly);
+            committed.pin(&block).expect("failed to pin");
+            committed
+                .set_int(&block, 80, 100, true)
+                .expect("failed to set int");
+            committed
+                .set_string(&block, 40, "committed", true)
+                .expect("failed to set string");
+            committed.commit().expect("failed to commit");
+
+            let mut uncommitted = transaction(&database, RecoveryMode::UndoOnly);
+            uncommitted.pin(&block).expect("failed to pin");
+            uncommitted
+                .set_int(&block, 80, 200, true)
+                .expect("failed to set int");
+            uncommitted
+                .set_string(&block, 40, "uncommitted", true)
+                .expect("failed to set string");
+            // Pretend the buffer got replaced, so the uncommitted change makes it to disk before the crash.
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .flush_all(uncommitted.transaction_number())
+                .expect("failed to flush");
+            assert_eq!(
+                database
+                    .read_from_disk(&block)
+                    .read_int(80)
+                    .expect("failed to read int"),
+                200
+            );
+        }
+
+        let database = Database::open(&tmp_dir);
+        let mut recovery = transaction(&database, RecoveryMode::UndoOnly);
+        recovery.recover().expect("failed to recover");
+
+        let page = database.read_from_disk(&block);
+        assert_eq!(page.read_int(80).expect("failed to read int"), 100);
+        assert_eq!(
+            page.read_string(40).expect("failed to read string"),
+            "committed"
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_undo_only_recovery_leaves_rolled_back_transactions_alone() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut rolled_back = transaction(&database, RecoveryMode::UndoOnly);
+            rolled_back.pin(&block).expect("failed to pin");
+            rolled_back
+                .set_int(&block, 0, 7, true)
+                .expect("failed to set int");
+            rolled_back.rollback().expect("failed to rollback");
+
+            let mut committed = transaction(&database, RecoveryMode::UndoOnly);
+            committed.pin(&block).expect("failed to pin");
+            committed
+                .set_int(&block, 0, 9, true)
+                .expect("failed to set int");
+            committed.commit().expect("failed to commit");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::UndoOnly)
+            .recover()
+            .expect("failed to recover");
+        assert_eq!(
+            database
+                .read_from_disk(&block)
+                .read_int(0)
+                .expect("failed to read int"),
+            9
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_redo_undo_recovery_replays_committed_changes() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+        let other_block = BlockMetadata::new("test.tbl", 1);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut committed = transaction(&database, RecoveryMode::RedoUndo);
+            committed.pin(&block).expect("failed to pin");
+            committed
+                .set_int(&block, 80, 100, true)
+                .expect("failed to set int");
+            committed
+                .set_int(&block, 80, 150, true)
+                .expect("failed to set int");
+            committed
+                .set_string(&block, 40, "committed", true)
+                .expect("failed to set string");
+            committed.commit().expect("failed to commit");
+
+            // Commit only forced the log, the block itself never made it to disk.
+            assert_eq!(
+                database
+                    .read_from_disk(&block)
+                    .read_int(80)
+                    .expect("failed to read int"),
+                0
+            );
+
+            // Different block, so flushing it doesn't drag the committed change to disk with it.
+            let mut uncommitted = transaction(&database, RecoveryMode::RedoUndo);
+            uncommitted.pin(&other_block).expect("failed to pin");
+            uncommitted
+                .set_int(&other_block, 120, 5, true)
+                .expect("failed to set int");
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .flush_all(uncommitted.transaction_number())
+                .expect("failed to flush");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::RedoUndo)
+            .recover()
+            .expect("failed to recover");
+
+        let page = database.read_from_disk(&block);
+        assert_eq!(page.read_int(80).expect("failed to read int"), 150);
+        assert_eq!(
+            page.read_string(40).expect("failed to read string"),
+            "committed"
+        );
+        assert_eq!(
+            database
+                .read_from_disk(&other_block)
+                .read_int(120)
+                .expect("failed to read int"),
+            0
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_recovery_writes_checkpoint() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::UndoOnly)
+            .recover()
+            .expect("failed to recover");
+
+        let newest = database
+            .log_manager
+            .lock()
+            .expect("log manager mutex poisoned")
+            .iterator()
+            .expect("failed to create iterator")
+            .next()
+            .expect("log should not be empty")
+            .expect("failed to read record");
+        assert_eq!(
+            LogRecord::from_bytes(newest).expect("failed to read record"),
+            LogRecord::Checkpoint
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_recovery_stops_at_quiescent_checkpoint() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut committed = transaction(&database, RecoveryMode::UndoOnly);
+            committed.pin(&block).expect("failed to pin");
+            committed
+                .set_int(&block, 0, 5, true)
+                .expect("failed to set int");
+            committed.commit().expect("failed to commit");
+
+            // A change from a transaction that never finished. Recovery would undo it if it went past the checkpoint.
+            LogRecord::SetInt {
+                transaction_number: 999_999,
+                block: block.clone(),
+                offset: 0,
+                old_value: 42,
+                new_value: 5,
+            }
+            .write_to_log(
+                &mut database
+                    .log_manager
+                    .lock()
+                    .expect("log manager mutex poisoned"),
+            )
+            .expect("failed to write record");
+            checkpoint(&database.log_manager, &database.buffer_manager)
+                .expect("failed to checkpoint");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::UndoOnly)
+            .recover()
+            .expect("failed to recover");
+        assert_eq!(
+            database
+                .read_from_disk(&block)
+                .read_int(0)
+                .expect("failed to read int"),
+            5
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_recovery_goes_past_nonquiescent_checkpoint_to_oldest_start() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut committed = transaction(&database, RecoveryMode::UndoOnly);
+            committed.pin(&block).expect("failed to pin");
+            committed
+                .set_int(&block, 0, 5, true)
+                .expect("failed to set int");
+            committed.commit().expect("failed to commit");
+
+            // Older than the START of the active transaction, so recovery should stop before reaching it.
+            LogRecord::SetInt {
+                transaction_number: 999_998,
+                block: block.clone(),
+                offset: 0,
+                old_value: 42,
+                new_value: 5,
+            }
+            .write_to_log(
+                &mut database
+                    .log_manager
+                    .lock()
+                    .expect("log manager mutex poisoned"),
+            )
+            .expect("failed to write record");
+
+            let mut active = transaction(&database, RecoveryMode::UndoOnly);
+            active.pin(&block).expect("failed to pin");
+            active
+                .set_int(&block, 40, 1, true)
+                .expect("failed to set int");
+            checkpoint(&database.log_manager, &database.buffer_manager)
+                .expect("failed to checkpoint");
+
+            let newest = database
+                .log_manager
+                .lock()
+                .expect("log manager mutex poisoned")
+                .iterator()
+                .expect("failed to create iterator")
+                .next()
+                .expect("log should not be empty")
+                .expect("failed to read record");
+            assert_eq!(
+                LogRecord::from_bytes(newest).expect("failed to read record"),
+                LogRecord::NonQuiescentCheckpoint {
+                    active_transactions: vec![active.transaction_number()]
+                }
+            );
+
+            active
+                .set_int(&block, 80, 2, true)
+                .expect("failed to set int");
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .flush_all(active.transaction_number())
+                .expect("failed to flush");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::UndoOnly)
+            .recover()
+            .expect("failed to recover");
+
+        let page = database.read_from_disk(&block);
+        assert_eq!(page.read_int(0).expect("failed to read int"), 5);
+        assert_eq!(page.read_int(40).expect("failed to read int"), 0);
+        assert_eq!(page.read_int(80).expect("failed to read int"), 0);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_write_racing_a_checkpoint_is_redone() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            let mut writer = transaction(&database, RecoveryMode::RedoUndo);
+            writer.pin(&block).expect("failed to pin");
+
+            // Holding the log up keeps the checkpoint between flushing the buffers and writing its record while the write comes in.
+            let log_manager = database
+                .log_manager
+                .lock()
+                .expect("log manager mutex poisoned");
+            std::thread::scope(|scope| {
+                let checkpointer = scope.spawn(|| {
+                    checkpoint(&database.log_manager, &database.buffer_manager)
+                        .expect("failed to checkpoint")
+                });
+                std::thread::sleep(Duration::from_millis(50));
+                let write = scope.spawn(|| {
+                    writer
+                        .set_int(&block, 0, 7, true)
+                        .expect("failed to set int")
+                });
+                std::thread::sleep(Duration::from_millis(50));
+                drop(log_manager);
+                checkpointer.join().expect("checkpoint panicked");
+                write.join().expect("write panicked");
+            });
+            // The write had to wait for the checkpoint record, so its SETINT comes after it and redo can't skip it.
+            let records: Vec<LogRecord> = database
+                .log_manager
+                .lock()
+                .expect("log manager mutex poisoned")
+                .iterator()
+                .expect("failed to create iterator")
+                .take(2)
+                .map(|bytes| {
+                    LogRecord::from_bytes(bytes.expect("failed to read log"))
+                        .expect("failed to read record")
+                })
+                .collect();
+            assert!(matches!(records[0], LogRecord::SetInt { .. }));
+            assert!(matches!(
+                records[1],
+                LogRecord::NonQuiescentCheckpoint { .. }
+            ));
+            // Only the log is forced, the block stays in the buffer pool and is gone after the crash.
+            writer.commit().expect("failed to commit");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::RedoUndo)
+            .recover()
+            .expect("failed to recover");
+        assert_eq!(
+            database
+                .read_from_disk(&block)
+                .read_int(0)
+                .expect("failed to read int"),
+            7
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_truncate_log_keeps_what_recovery_needs() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        {
+            let database = Database::open(&tmp_dir);
+            // Enough committed transactions to spill the log over a few blocks.
+            for value in 0..20 {
+                let mut transaction = transaction(&database, RecoveryMode::UndoOnly);
+                transaction.pin(&block).expect("failed to pin");
+                transaction
+                    .set_int(&block, 0, value, true)
+                    .expect("failed to set int");
+                transaction.commit().expect("failed to commit");
+            }
+            let blocks_before = database
+                .file_manager
+                .last_block_index("log.wal")
+                .expect("failed to get last block")
+                .expect("log should not be empty");
+            assert!(blocks_before > 0);
+
+            let mut active = transaction(&database, RecoveryMode::UndoOnly);
+            active.pin(&block).expect("failed to pin");
+            active
+                .set_int(&block, 40, 1, true)
+                .expect("failed to set int");
+            checkpoint(&database.log_manager, &database.buffer_manager)
+                .expect("failed to checkpoint");
+
+            let dropped = truncate_log(&database.log_manager).expect("failed to truncate log");
+            assert!(dropped > 0);
+
+            // The START of the active transaction has to survive, recovery needs to get back to it.
+            let records: Vec<LogRecord> = database
+                .log_manager
+                .lock()
+                .expect("log manager mutex poisoned")
+                .iterator()
+                .expect("failed to create iterator")
+                .map(|bytes| {
+                    LogRecord::from_bytes(bytes.expect("failed to read log"))
+                        .expect("failed to read record")
+                })
+                .collect();
+            assert!(records.contains(&LogRecord::Start {
+                transaction_number: active.transaction_number()
+            }));
+
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .flush_all(active.transaction_number())
+                .expect("failed to flush");
+        }
+
+        let database = Database::open(&tmp_dir);
+        transaction(&database, RecoveryMode::UndoOnly)
+            .recover()
+            .expect("failed to recover");
+
+        let page = database.read_from_disk(&block);
+        assert_eq!(page.read_int(0).expect("failed to read int"), 19);
+        assert_eq!(page.read_int(40).expect("failed to read int"), 0);
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_truncate_log_without_checkpoint_keeps_everything() {
+        let tmp_dir = TempDir::new("test_recovery_manager").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let mut transaction = transaction(&database, RecoveryMode::UndoOnly);
+        transaction.commit().expect("failed to commit");
+
+        assert_eq!(
+            truncate_log(&database.log_manager).expect("failed to truncate log"),
+            0
+        );
+
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
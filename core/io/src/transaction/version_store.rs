@@ -0,0 +1,593 @@
+/*
+Not in the book, SimpleDB only does two phase locking.
+
+Snapshot isolation keeps the bytes every commit overwrote around, per block, stamped with the commit's timestamp. Every commit gets a
+timestamp from a logical clock and a transaction reads as of the clock when it started, its read timestamp. The pages only ever hold the
+latest committed bytes, snapshot transactions keep their writes to themselves until commit. To read as of a timestamp the block is copied
+and the commits after it are undone on the copy, newest first, then the transaction's own pending writes go on top. Working on bytes and
+not on (offset, value) pairs means writes of different widths that overlap (a string at 0 and an int at 4) still see each other.
+
+Old bytes no running snapshot needs anymore, i.e. from commits before the oldest read timestamp, get dropped whenever a snapshot commits
+or finishes.
+
+Conflicts are first committer wins: a transaction can't commit a write that overlaps bytes somebody else committed after its read
+timestamp. Commit is two steps, begin_commit checks for conflicts and keeps the store locked while the transaction writes the pages, and
+finish records what got overwritten. If writing the pages fails the Commit is just dropped and the store is as it was, so commits don't
+interleave and a failed one leaves no trace.
+*/
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{BlockMetadata, StormDbError, error::Result, page::Page};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Int(i32),
+    String(String),
+}
+
+impl Value {
+    pub(crate) fn into_int(self) -> Result<i32> {
+        match self {
+            Value::Int(value) => Ok(value),
+            Value::String(value) => Err(StormDbError::Corrupt {
+                block: None,
+                message: format!(
+                    "Expected an int but the version holds the string {:?}",
+                    value
+                ),
+            }),
+        }
+    }
+
+    pub(crate) fn into_string(self) -> Result<String> {
+        match self {
+            Value::String(value) => Ok(value),
+            Value::Int(value) => Err(StormDbError::Corrupt {
+                block: None,
+                message: format!("Expected a string but the version holds the int {}", value),
+            }),
+        }
+    }
+
+    /// Number of bytes the value takes up on the page.
+    pub(crate) fn stored_len(&self) -> usize {
+        match self {
+            Value::Int(_) => Page::I32_SIZE,
+            Value::String(value) => Page::max_bytes_len(value.len()),
+        }
+    }
+
+    pub(crate) fn write_to(&self, page: &mut Page, offset: usize) -> Result<()> {
+        match self {
+            Value::Int(value) => page.write_int(offset, *value),
+            Value::String(value) => page.write_string(offset, value.clone()),
+        }
+    }
+}
+
+/// A write a snapshot transaction holds on to until it commits.
+pub(crate) struct Write {
+    pub(crate) block: BlockMetadata,
+    pub(crate) offset: usize,
+    pub(crate) value: Value,
+    pub(crate) ok_to_log: bool,
+}
+
+impl Write {
+    pub(crate) fn end(&self) -> usize {
+        self.offset + self.value.stored_len()
+    }
+}
+
+// The bytes at offset that a commit overwrote.
+struct Version {
+    commit_timestamp: u64,
+    offset: usize,
+    before: Vec<u8>,
+}
+
+#[derive(Default)]
+struct VersionState {
+    // Timestamp of the latest commit.
+    clock: u64,
+    // In commit order.
+    versions: HashMap<BlockMetadata, Vec<Version>>,
+    // Read timestamps of the running snapshots, with how many of them share each one.
+    active: BTreeMap<u64, usize>,
+}
+
+impl VersionState {
+    // Undoing a commit is only ever needed by a snapshot that started before it.
+    fn prune(&mut self) {
+        let Some(&oldest) = self.active.keys().next() else {
+            self.versions.clear();
+            return;
+        };
+        self.versions.retain(|_, versions| {
+            versions.retain(|version| version.commit_timestamp > oldest);
+            !versions.is_empty()
+        });
+    }
+}
+
+/// Committed versions of the bytes written by snapshot isolation transactions, shared by every transaction of a database.
+#[derive(Default)]
+pub struct VersionStore {
+    state: Mutex<VersionState>,
+}
+
+impl VersionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamp of the latest commit, a snapshot starting now reads as of this.
+    pub fn clock(&self) -> u64 {
+        self.lock_state().clock
+    }
+
+    /// Number of overwritten byte ranges being kept around.
+    pub fn version_count(&self) -> usize {
+        self.lock_state().versions.values().map(Vec::len).sum()
+    }
+
+    fn begin(&self) -> u64 {
+        let mut state = self.lock_state();
+        let read_timestamp = state.clock;
+        *state.active.entry(read_timestamp).or_default() += 1;
+        read_timestamp
+    }
+
+    fn finish(&self, read_timestamp: u64) {
+        let mut state = self.lock_state();
+        if let Some(count) = state.active.get_mut(&read_timestamp) {
+            *count -= 1;
+            if *count == 0 {
+                state.active.remove(&read_timestamp);
+            }
+        }
+        state.prune();
+    }
+
+    // `copy_page` copies the block's page as it is now, under the lock so no commit sneaks in between.
+    fn read(
+        &self,
+        block: &BlockMetadata,
+        read_timestamp: u64,
+        copy_page: impl FnOnce() -> Result<Page>,
+    ) -> Result<Page> {
+        let state = self.lock_state();
+        let mut page = copy_page()?;
+        if let Some(versions) = state.versions.get(block) {
+            for version in versions
+                .iter()
+                .rev()
+                .take_while(|version| version.commit_timestamp > read_timestamp)
+            {
+                page.byte_buffer[version.offset..version.offset + version.before.len()]
+                    .copy_from_slice(&version.before);
+            }
+        }
+        Ok(page)
+    }
+
+    fn begin_commit(&self, read_timestamp: u64, writes: &[Write]) -> Result<Commit<'_>> {
+        let state = self.lock_state();
+        for write in writes {
+            let conflict = state.versions.get(&write.block).is_some_and(|versions| {
+                versions.iter().any(|version| {
+                    version.commit_timestamp > read_timestamp
+                        && version.offset < write.end()
+                        && write.offset < version.offset + version.before.len()
+                })
+            });
+            if conflict {
+                return Err(StormDbError::WriteConflict(format!(
+                    "Bytes {}..{} of {} were changed by a transaction that committed after timestamp {}",
+                    write.offset,
+                    write.end(),
+                    write.block,
+                    read_timestamp
+                )));
+            }
+        }
+        Ok(Commit {
+            state,
+            overwritten: Vec::new(),
+        })
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, VersionState> {
+        self.state.lock().expect("version store mutex poisoned")
+    }
+}
+
+/// A commit that passed the conflict check, holding the store's lock while the writes go to the pages.
+pub(crate) struct Commit<'a> {
+    state: MutexGuard<'a, VersionState>,
+    overwritten: Vec<(BlockMetadata, usize, Vec<u8>)>,
+}
+
+impl Commit<'_> {
+    /// Records the bytes a write replaced on its page.
+    pub(crate) fn overwrote(&mut self, write: &Write, before: Vec<u8>) {
+        self.overwritten
+            .push((write.block.clone(), write.offset, before));
+    }
+
+    /// Makes the writes visible to snapshots starting from now on, returns the commit timestamp.
+    pub(crate) fn finish(mut self) -> u64 {
+        // Read only, nothing new for anybody to see.
+        if self.overwritten.is_empty() {
+            return self.state.clock;
+        }
+        self.state.clock += 1;
+        let commit_timestamp = self.state.clock;
+        for (block, offset, before) in std::mem::take(&mut self.overwritten) {
+            self.state.versions.entry(block).or_default().push(Version {
+                commit_timestamp,
+                offset,
+                before,
+            });
+        }
+        self.state.prune();
+        commit_timestamp
+    }
+}
+
+/// The read timestamp and pending writes of a single snapshot isolation transaction. Dropping it ends the snapshot.
+pub(crate) struct Snapshot {
+    version_store: Arc<VersionStore>,
+    read_timestamp: u64,
+    writes: Vec<Write>,
+}
+
+impl Snapshot {
+    pub(crate) fn begin(version_store: Arc<VersionStore>) -> Self {
+        let read_timestamp = version_store.begin();
+        Self {
+            version_store,
+            read_timestamp,
+            writes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn read_timestamp(&self) -> u64 {
+        self.read_timestamp
+    }
+
+    pub(crate) fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+
+    /// The block as the transaction sees it: what was committed as of the read timestamp with its own writes on top.
+    pub(crate) fn read(
+        &self,
+        block: &BlockMetadata,
+        copy_page: impl FnOnce() -> Result<Page>,
+    ) -> Result<Page> {
+        let mut page = self
+            .version_store
+            .read(block, self.read_timestamp, copy_page)?;
+        for write in self.writes.iter().filter(|write| write.block == *block) {
+            write.value.write_to(&mut page, write.offset)?;
+        }
+        Ok(page)
+    }
+
+    pub(crate) fn write(
+        &mut self,
+        block: &BlockMetadata,
+        offset: usize,
+        value: Value,
+        ok_to_log: bool,
+    ) {
+        self.writes.push(Write {
+            block: block.clone(),
+            offset,
+            value,
+            ok_to_log,
+        });
+    }
+
+    /// Checks for conflicts. The writes go to the pages while the returned Commit is held, then it's finished.
+    pub(crate) fn begin_commit(&self) -> Result<Commit<'_>> {
+        self.version_store
+            .begin_commit(self.read_timestamp, &self.writes)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.version_store.finish(self.read_timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transaction;
+    use tempdir::TempDir;
+
+    use crate::transaction::test_database::{BLOCK_SIZE, Database};
+
+    fn snapshot_transaction(database: &Database, block: &BlockMetadata) -> Transaction {
+        let mut transaction = database
+            .builder()
+            .with_snapshot_isolation(database.version_store.clone())
+            .build()
+            .expect("failed to start transaction");
+        transaction.pin(block).expect("failed to pin");
+        transaction
+    }
+
+    #[test]
+    fn test_reader_keeps_its_snapshot() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut setup = snapshot_transaction(&database, &block);
+        setup
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        setup
+            .set_string(&block, 40, "one", true)
+            .expect("failed to set string");
+        setup.commit().expect("failed to commit");
+
+        let mut reader = snapshot_transaction(&database, &block);
+        assert_eq!(
+            reader.read_timestamp(),
+            Some(database.version_store.clock())
+        );
+
+        let mut writer = snapshot_transaction(&database, &block);
+        writer
+            .set_int(&block, 0, 2, true)
+            .expect("failed to set int");
+        writer
+            .set_string(&block, 40, "two", true)
+            .expect("failed to set string");
+        // Not committed yet, so nobody else sees it but the writer.
+        assert_eq!(writer.get_int(&block, 0).expect("failed to get int"), 2);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 1);
+        writer.commit().expect("failed to commit");
+
+        // Committed after the reader started, still invisible to it.
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 1);
+        assert_eq!(
+            reader.get_string(&block, 40).expect("failed to get string"),
+            "one"
+        );
+
+        let mut late_reader = snapshot_transaction(&database, &block);
+        assert_eq!(
+            late_reader.get_int(&block, 0).expect("failed to get int"),
+            2
+        );
+        assert_eq!(
+            late_reader
+                .get_string(&block, 40)
+                .expect("failed to get string"),
+            "two"
+        );
+
+        reader.commit().expect("failed to commit");
+        late_reader.commit().expect("failed to commit");
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_first_committer_wins() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut first = snapshot_transaction(&database, &block);
+        let mut second = snapshot_transaction(&database, &block);
+        first
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        second
+            .set_int(&block, 0, 2, true)
+            .expect("failed to set int");
+        second
+            .set_int(&block, 80, 2, true)
+            .expect("failed to set int");
+
+        first.commit().expect("failed to commit");
+        assert!(matches!(
+            second.commit(),
+            Err(StormDbError::WriteConflict(_))
+        ));
+
+        // None of the loser's writes made it, not even the one without a conflict.
+        let mut reader = snapshot_transaction(&database, &block);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 1);
+        assert_eq!(reader.get_int(&block, 80).expect("failed to get int"), 0);
+        reader.commit().expect("failed to commit");
+
+        assert_eq!(
+            database
+                .buffer_manager
+                .lock()
+                .expect("buffer manager mutex poisoned")
+                .available(),
+            8
+        );
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_disjoint_writes_both_commit() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut first = snapshot_transaction(&database, &block);
+        let mut second = snapshot_transaction(&database, &block);
+        first
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        second
+            .set_int(&block, 80, 2, true)
+            .expect("failed to set int");
+        first.commit().expect("failed to commit");
+        second.commit().expect("failed to commit");
+
+        let mut reader = snapshot_transaction(&database, &block);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 1);
+        assert_eq!(reader.get_int(&block, 80).expect("failed to get int"), 2);
+        reader.commit().expect("failed to commit");
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_rollback_discards_pending_writes() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut writer = snapshot_transaction(&database, &block);
+        writer
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        writer.rollback().expect("failed to rollback");
+
+        let mut reader = snapshot_transaction(&database, &block);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 0);
+        reader.commit().expect("failed to commit");
+        assert_eq!(database.version_store.clock(), 0);
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_old_versions_dropped_once_no_snapshot_needs_them() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut reader = snapshot_transaction(&database, &block);
+        for value in 1..=3 {
+            let mut writer = snapshot_transaction(&database, &block);
+            writer
+                .set_int(&block, 0, value, true)
+                .expect("failed to set int");
+            writer.commit().expect("failed to commit");
+        }
+        // The reader started before all three commits, it needs every one of them undone.
+        assert_eq!(database.version_store.version_count(), 3);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 0);
+
+        reader.commit().expect("failed to commit");
+        assert_eq!(database.version_store.version_count(), 0);
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_overlapping_writes_of_different_widths() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut setup = snapshot_transaction(&database, &block);
+        setup
+            .set_int(&block, 4, 7, true)
+            .expect("failed to set int");
+        setup.commit().expect("failed to commit");
+
+        let mut reader = snapshot_transaction(&database, &block);
+        let mut first = snapshot_transaction(&database, &block);
+        let mut second = snapshot_transaction(&database, &block);
+        // Bytes 0..9, right over the int at 4.
+        first
+            .set_string(&block, 0, "abcdefgh", true)
+            .expect("failed to set string");
+        second
+            .set_int(&block, 4, 8, true)
+            .expect("failed to set int");
+        first.commit().expect("failed to commit");
+        assert!(matches!(
+            second.commit(),
+            Err(StormDbError::WriteConflict(_))
+        ));
+
+        // The string isn't visible to a snapshot from before it, not even through the int in the middle of it.
+        assert_eq!(reader.get_int(&block, 4).expect("failed to get int"), 7);
+        reader.commit().expect("failed to commit");
+        let mut late_reader = snapshot_transaction(&database, &block);
+        assert_eq!(
+            late_reader
+                .get_string(&block, 0)
+                .expect("failed to get string"),
+            "abcdefgh"
+        );
+        late_reader.commit().expect("failed to commit");
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_failed_commit_changes_nothing() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        let mut reader = snapshot_transaction(&database, &block);
+        let mut writer = snapshot_transaction(&database, &block);
+        writer
+            .set_int(&block, 0, 1, true)
+            .expect("failed to set int");
+        // Logged writes get their old value read right away, so that one is turned down at once. Unlogged is only caught at commit,
+        // after the first write is already lined up.
+        assert!(matches!(
+            writer.set_int(&block, BLOCK_SIZE - 2, 2, true),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        writer
+            .set_int(&block, BLOCK_SIZE - 2, 2, false)
+            .expect("failed to set int");
+        assert!(matches!(writer.commit(), Err(StormDbError::OutOfBound(_))));
+
+        assert_eq!(database.version_store.clock(), 0);
+        assert_eq!(database.version_store.version_count(), 0);
+        assert_eq!(reader.get_int(&block, 0).expect("failed to get int"), 0);
+        reader.commit().expect("failed to commit");
+        let mut late_reader = snapshot_transaction(&database, &block);
+        assert_eq!(
+            late_reader.get_int(&block, 0).expect("failed to get int"),
+            0
+        );
+        late_reader.commit().expect("failed to commit");
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+
+    #[test]
+    fn test_write_too_big_for_the_log_is_refused() {
+        let tmp_dir = TempDir::new("test_version_store").expect("failed to create temp dir");
+        let database = Database::open(&tmp_dir);
+        let block = BlockMetadata::new("test.tbl", 0);
+
+        // Fits in the page, but its SETSTRING record doesn't fit in a log block.
+        let mut writer = snapshot_transaction(&database, &block);
+        assert!(matches!(
+            writer.set_string(&block, 0, &"x".repeat(BLOCK_SIZE - 20), true),
+            Err(StormDbError::OutOfBound(_))
+        ));
+        writer
+            .set_string(&block, 0, "fits", true)
+            .expect("failed to set string");
+        writer.commit().expect("failed to commit");
+
+        let mut reader = snapshot_transaction(&database, &block);
+        assert_eq!(
+            reader.get_string(&block, 0).expect("failed to get string"),
+            "fits"
+        );
+        reader.commit().expect("failed to commit");
+        tmp_dir.close().expect("failed to remove temp dir");
+    }
+}
@@ -53,7 +53,12 @@ pub fn read_varint(buffer: &[u8]) -> Result<(u64, usize)> {
                     return Ok((varint, i + 1));
                 }
             }
-            None => return Err(StormDbError::Corrupt("Invalid Varint.".to_string())),
+            None => {
+                return Err(StormDbError::Corrupt {
+                    block: None,
+                    message: "Invalid Varint.".to_string(),
+                });
+            }
         }
     }
 
@@ -61,7 +66,10 @@ pub fn read_varint(buffer: &[u8]) -> Result<(u64, usize)> {
         varint = (varint << 8) + (*last_byte as u64);
         Ok((varint, 9))
     } else {
-        return Err(StormDbError::Corrupt("Invalid Varint.".to_string()));
+        Err(StormDbError::Corrupt {
+            block: None,
+            message: "Invalid Varint.".to_string(),
+        })
     }
 }
 
@@ -315,9 +323,10 @@ pub fn read_varint_reversed(buffer: &[u8], end_offset: usize) -> Result<(u64, us
     // Read backwards from end_offset
     for i in 0..9 {
         if end_offset < i {
-            return Err(StormDbError::Corrupt(
-                "Invalid reversed varint.".to_string(),
-            ));
+            return Err(StormDbError::Corrupt {
+                block: None,
+                message: "Invalid reversed varint.".to_string(),
+            });
         }
 
         let pos = end_offset - i;
@@ -364,8 +373,8 @@ pub fn get_varint_reversed(value: u64) -> (Vec<u8>, usize) {
         }
 
         // Reverse bytes and bits
-        for i in 0..9 {
-            buffer[i] = buffer[i].reverse_bits();
+        for byte in buffer.iter_mut() {
+            *byte = byte.reverse_bits();
         }
 
         return (buffer, 9);
@@ -430,7 +439,7 @@ mod test {
         let mut buffer_sqlite_fun = vec![0u8; 10];
         let sqlite_varint_size = write_varint_sqlite(&mut buffer_sqlite_fun, value);
 
-        let (varint_read, varint_size) = read_varint(&mut buffer_sqlite_fun)?;
+        let (varint_read, varint_size) = read_varint(&buffer_sqlite_fun)?;
         assert_eq!(varint_read, value);
         assert_eq!(varint_size, sqlite_varint_size);
         Ok(())
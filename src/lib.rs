@@ -0,0 +1,11 @@
+/*
+The database side of StormDB, everything that sits on top of the file_manager library in core/io. For now that's just the SQL parser,
+the planner and the query processing from the book come later.
+*/
+
+mod parse;
+
+pub use parse::{
+    Constant, CreateIndex, CreateTable, CreateView, Delete, Expression, Insert, Predicate, Query,
+    Statement, Term, Update, parse,
+};
@@ -1,3 +1,21 @@
+use std::io::{self, BufRead, Write};
+
+// No planner yet, so for now all this does is show what the parser makes of each line.
 fn main() {
-    println!("Hello, world!");
+    let prompt = || {
+        print!("stormdb> ");
+        io::stdout().flush().expect("failed to flush stdout");
+    };
+
+    prompt();
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("failed to read stdin");
+        if !line.trim().is_empty() {
+            match stormdb::parse(&line) {
+                Ok(statement) => println!("{:#?}", statement),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+        prompt();
+    }
 }
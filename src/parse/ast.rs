@@ -0,0 +1,147 @@
+/*
+What the parser produces. The book has a class per statement (QueryData, InsertData, ModifyData, ...), here they're structs held by a
+Statement enum. No WHERE clause is an empty predicate, same as the book.
+
+Queries print back as SQL, that's what a view keeps in the catalog as its definition.
+*/
+
+use std::fmt::{Display, Formatter};
+
+use file_manager::Schema;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constant {
+    Int(i32),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Field(String),
+    Constant(Constant),
+}
+
+/// Two expressions that have to be equal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub lhs: Expression,
+    pub rhs: Expression,
+}
+
+/// Terms that all have to hold, no terms holds for every record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Predicate {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub fields: Vec<String>,
+    pub tables: Vec<String>,
+    pub predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Insert {
+    pub table: String,
+    pub fields: Vec<String>,
+    pub values: Vec<Constant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delete {
+    pub table: String,
+    pub predicate: Predicate,
+}
+
+/// Sets one field of every record matching the predicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub table: String,
+    pub field: String,
+    pub value: Expression,
+    pub predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateTable {
+    pub table: String,
+    pub schema: Schema,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateView {
+    pub view: String,
+    pub query: Query,
+}
+
+impl CreateView {
+    /// The definition to keep in the catalog.
+    pub fn view_def(&self) -> String {
+        self.query.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateIndex {
+    pub index: String,
+    pub table: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Query(Query),
+    Insert(Insert),
+    Delete(Delete),
+    Update(Update),
+    CreateTable(CreateTable),
+    CreateView(CreateView),
+    CreateIndex(CreateIndex),
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Constant::Int(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Expression::Field(field_name) => write!(f, "{}", field_name),
+            Expression::Constant(constant) => write!(f, "{}", constant),
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{} = {}", self.lhs, self.rhs)
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(Term::to_string).collect();
+        write!(f, "{}", terms.join(" and "))
+    }
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "select {} from {}",
+            self.fields.join(", "),
+            self.tables.join(", ")
+        )?;
+        if !self.predicate.terms.is_empty() {
+            write!(f, " where {}", self.predicate)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+/*
+Lexer API as per the book:
+  public boolean matchDelim(char d);
+  public boolean matchIntConstant();
+  public boolean matchStringConstant();
+  public boolean matchKeyword(String w);
+  public boolean matchId();
+  public void eatDelim(char d);
+  public int eatIntConstant();
+  public String eatStringConstant();
+  public void eatKeyword(String w);
+  public String eatId();
+
+The book wraps Java's StreamTokenizer and the parser matches and eats tokens straight off the lexer. Here the whole statement is split
+into tokens up front and the match/eat part is in the parser, which keeps the lexer a plain function. Every token remembers the line and
+column it starts at for the error messages.
+*/
+
+use std::{
+    fmt::{Display, Formatter},
+    iter::Peekable,
+    str::Chars,
+};
+
+use file_manager::{Result, StormDbError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Keyword {
+    Select,
+    From,
+    Where,
+    And,
+    Insert,
+    Into,
+    Values,
+    Delete,
+    Update,
+    Set,
+    Create,
+    Table,
+    View,
+    As,
+    Index,
+    On,
+    Int,
+    Varchar,
+    Bytes,
+}
+
+impl Keyword {
+    fn from_word(word: &str) -> Option<Self> {
+        let keyword = match word {
+            "select" => Keyword::Select,
+            "from" => Keyword::From,
+            "where" => Keyword::Where,
+            "and" => Keyword::And,
+            "insert" => Keyword::Insert,
+            "into" => Keyword::Into,
+            "values" => Keyword::Values,
+            "delete" => Keyword::Delete,
+            "update" => Keyword::Update,
+            "set" => Keyword::Set,
+            "create" => Keyword::Create,
+            "table" => Keyword::Table,
+            "view" => Keyword::View,
+            "as" => Keyword::As,
+            "index" => Keyword::Index,
+            "on" => Keyword::On,
+            "int" => Keyword::Int,
+            "varchar" => Keyword::Varchar,
+            "bytes" => Keyword::Bytes,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(&format!("{:?}", self).to_uppercase())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    // One of , ( ) = ;
+    Delimiter(char),
+    Int(i32),
+    String(String),
+    Keyword(Keyword),
+    Identifier(String),
+    End,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Token::Delimiter(delimiter) => write!(f, "'{}'", delimiter),
+            Token::Int(value) => write!(f, "{}", value),
+            Token::String(value) => write!(f, "string '{}'", value),
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// A token and where it starts, both counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Spanned {
+    pub(crate) token: Token,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+/// Splits the statement into tokens, the last one is always Token::End.
+pub(crate) fn tokenize(sql: &str) -> Result<Vec<Spanned>> {
+    let mut lexer = Lexer {
+        chars: sql.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+    let mut tokens = Vec::new();
+    loop {
+        let spanned = lexer.next_token()?;
+        let end = spanned.token == Token::End;
+        tokens.push(spanned);
+        if end {
+            return Ok(tokens);
+        }
+    }
+}
+
+impl Lexer<'_> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '-' && self.chars.clone().nth(1) == Some('-') {
+                while self.chars.peek().is_some_and(|&c| c != '\n') {
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Spanned> {
+        self.skip_whitespace_and_comments();
+        let (line, column) = (self.line, self.column);
+        let error = |message: String| StormDbError::Syntax {
+            line,
+            column,
+            message,
+        };
+
+        let Some(&c) = self.chars.peek() else {
+            return Ok(Spanned {
+                token: Token::End,
+                line,
+                column,
+            });
+        };
+        let token = match c {
+            ',' | '(' | ')' | '=' | ';' => {
+                self.bump();
+                Token::Delimiter(c)
+            }
+            '\'' => {
+                self.bump();
+                let mut value = String::new();
+                loop {
+                    match self.bump() {
+                        Some('\'') if self.chars.peek() == Some(&'\'') => {
+                            self.bump();
+                            value.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(error("Unterminated string".to_string())),
+                    }
+                }
+                Token::String(value)
+            }
+            '-' | '0'..='9' => {
+                let mut digits = String::new();
+                if c == '-' {
+                    self.bump();
+                    digits.push('-');
+                }
+                while let Some(&c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+                    self.bump();
+                    digits.push(c);
+                }
+                let value = digits
+                    .parse()
+                    .map_err(|_| error(format!("Invalid integer {}", digits)))?;
+                Token::Int(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&c) = self
+                    .chars
+                    .peek()
+                    .filter(|c| c.is_alphanumeric() || **c == '_')
+                {
+                    self.bump();
+                    word.push(c);
+                }
+                let word = word.to_lowercase();
+                match Keyword::from_word(&word) {
+                    Some(keyword) => Token::Keyword(keyword),
+                    None => Token::Identifier(word),
+                }
+            }
+            _ => return Err(error(format!("Unexpected character '{}'", c))),
+        };
+        Ok(Spanned {
+            token,
+            line,
+            column,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn tokens(sql: &str) -> Vec<Token> {
+        tokenize(sql)
+            .expect("failed to tokenize")
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens("SELECT Name, id FROM student WHERE id = -12 AND name = 'it''s'; -- done"),
+            vec![
+                Token::Keyword(Keyword::Select),
+                Token::Identifier("name".to_string()),
+                Token::Delimiter(','),
+                Token::Identifier("id".to_string()),
+                Token::Keyword(Keyword::From),
+                Token::Identifier("student".to_string()),
+                Token::Keyword(Keyword::Where),
+                Token::Identifier("id".to_string()),
+                Token::Delimiter('='),
+                Token::Int(-12),
+                Token::Keyword(Keyword::And),
+                Token::Identifier("name".to_string()),
+                Token::Delimiter('='),
+                Token::String("it's".to_string()),
+                Token::Delimiter(';'),
+                Token::End,
+            ]
+        );
+        assert_eq!(tokens("  -- nothing here"), vec![Token::End]);
+    }
+
+    #[test]
+    fn test_positions() {
+        let positions: Vec<(usize, usize)> = tokenize("select a\n  from t")
+            .expect("failed to tokenize")
+            .into_iter()
+            .map(|spanned| (spanned.line, spanned.column))
+            .collect();
+        assert_eq!(positions, vec![(1, 1), (1, 8), (2, 3), (2, 8), (2, 9)]);
+    }
+
+    #[rstest]
+    #[case::unterminated_string("select a from t where b = 'abc", 1, 27)]
+    #[case::unexpected_character("select a\nfrom t where a > 1", 2, 16)]
+    #[case::int_overflow("insert into t (a) values (2147483648)", 1, 27)]
+    #[case::lone_minus("select - from t", 1, 8)]
+    fn test_errors(#[case] sql: &str, #[case] line: usize, #[case] column: usize) {
+        match tokenize(sql) {
+            Err(StormDbError::Syntax {
+                line: error_line,
+                column: error_column,
+                ..
+            }) => assert_eq!((error_line, error_column), (line, column)),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+}
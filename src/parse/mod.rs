@@ -0,0 +1,42 @@
+/*
+SQL parsing as per the book, the SimpleDB subset of SQL:
+  <Field>       := IdTok
+  <Constant>    := StrTok | IntTok
+  <Expression>  := <Field> | <Constant>
+  <Term>        := <Expression> = <Expression>
+  <Predicate>   := <Term> [ AND <Predicate> ]
+
+  <Query>       := SELECT <SelectList> FROM <TableList> [ WHERE <Predicate> ]
+  <SelectList>  := <Field> [ , <SelectList> ]
+  <TableList>   := IdTok [ , <TableList> ]
+
+  <UpdateCmd>   := <Insert> | <Delete> | <Modify> | <Create>
+  <Create>      := <CreateTable> | <CreateView> | <CreateIndex>
+  <Insert>      := INSERT INTO IdTok ( <FieldList> ) VALUES ( <ConstList> )
+  <FieldList>   := <Field> [ , <FieldList> ]
+  <ConstList>   := <Constant> [ , <ConstList> ]
+  <Delete>      := DELETE FROM IdTok [ WHERE <Predicate> ]
+  <Modify>      := UPDATE IdTok SET <Field> = <Expression> [ WHERE <Predicate> ]
+  <CreateTable> := CREATE TABLE IdTok ( <FieldDefs> )
+  <FieldDefs>   := <FieldDef> [ , <FieldDefs> ]
+  <FieldDef>    := IdTok <TypeDef>
+  <TypeDef>     := INT | VARCHAR ( IntTok )
+  <CreateView>  := CREATE VIEW IdTok AS <Query>
+  <CreateIndex> := CREATE INDEX IdTok ON IdTok ( <Field> )
+
+On top of the book there's BYTES ( IntTok ) for the bytes fields the record layer has, -- comments, and a statement can end with a ;.
+Keywords and identifiers are case insensitive, identifiers come out lower cased. String constants are in single quotes, '' is a quote.
+
+The lexer turns the text into tokens with the line and column they start at, the parser is recursive descent over those, a function
+per rule. Anything that doesn't fit comes back as StormDbError::Syntax pointing at the token it choked on.
+*/
+
+mod ast;
+mod lexer;
+mod parser;
+
+pub use ast::{
+    Constant, CreateIndex, CreateTable, CreateView, Delete, Expression, Insert, Predicate, Query,
+    Statement, Term, Update,
+};
+pub use parser::parse;
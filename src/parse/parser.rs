@@ -0,0 +1,541 @@
+/*
+Parser API as per the book:
+  public Parser(String s);
+  public String field();
+  public Constant constant();
+  public Expression expression();
+  public Term term();
+  public Predicate predicate();
+  public QueryData query();
+  public Object updateCmd();
+  public DeleteData delete();
+  public InsertData insert();
+  public ModifyData modify();
+  public CreateTableData createTable();
+  public CreateViewData createView();
+  public CreateIndexData createIndex();
+
+One function per grammar rule in mod.rs, same as the book. Only parse is public, it takes one statement and fails on anything left
+after it.
+*/
+
+use file_manager::{Result, Schema, StormDbError};
+
+use super::{
+    ast::{
+        Constant, CreateIndex, CreateTable, CreateView, Delete, Expression, Insert, Predicate,
+        Query, Statement, Term, Update,
+    },
+    lexer::{Keyword, Spanned, Token, tokenize},
+};
+
+/// Parses a single SQL statement, optionally ended by a ;.
+pub fn parse(sql: &str) -> Result<Statement> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        position: 0,
+    };
+    let statement = parser.statement()?;
+    parser.match_delimiter(';');
+    if parser.peek() != &Token::End {
+        return Err(parser.error("end of statement"));
+    }
+    Ok(statement)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].token
+    }
+
+    // The tokens end with Token::End, the position never goes past it.
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].token.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, expected: &str) -> StormDbError {
+        let spanned = &self.tokens[self.position];
+        StormDbError::Syntax {
+            line: spanned.line,
+            column: spanned.column,
+            message: format!("Expected {}, found {}", expected, spanned.token),
+        }
+    }
+
+    fn match_delimiter(&mut self, delimiter: char) -> bool {
+        let matched = self.peek() == &Token::Delimiter(delimiter);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn match_keyword(&mut self, keyword: Keyword) -> bool {
+        let matched = self.peek() == &Token::Keyword(keyword);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn eat_delimiter(&mut self, delimiter: char) -> Result<()> {
+        if !self.match_delimiter(delimiter) {
+            return Err(self.error(&format!("'{}'", delimiter)));
+        }
+        Ok(())
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> Result<()> {
+        if !self.match_keyword(keyword) {
+            return Err(self.error(&keyword.to_string()));
+        }
+        Ok(())
+    }
+
+    fn eat_identifier(&mut self, what: &str) -> Result<String> {
+        match self.peek() {
+            Token::Identifier(_) => match self.advance() {
+                Token::Identifier(name) => Ok(name),
+                _ => unreachable!("peeked an identifier"),
+            },
+            _ => Err(self.error(what)),
+        }
+    }
+
+    // Lengths of VARCHAR and BYTES.
+    fn eat_length(&mut self) -> Result<usize> {
+        self.eat_delimiter('(')?;
+        let length = match self.peek() {
+            Token::Int(length) if *length > 0 => *length as usize,
+            _ => return Err(self.error("a positive length")),
+        };
+        self.advance();
+        self.eat_delimiter(')')?;
+        Ok(length)
+    }
+
+    fn field(&mut self) -> Result<String> {
+        self.eat_identifier("field name")
+    }
+
+    fn constant(&mut self) -> Result<Constant> {
+        let constant = match self.peek() {
+            Token::Int(value) => Constant::Int(*value),
+            Token::String(value) => Constant::String(value.clone()),
+            _ => return Err(self.error("a constant")),
+        };
+        self.advance();
+        Ok(constant)
+    }
+
+    fn expression(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Token::Identifier(_) => Ok(Expression::Field(self.field()?)),
+            Token::Int(_) | Token::String(_) => Ok(Expression::Constant(self.constant()?)),
+            _ => Err(self.error("a field name or a constant")),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term> {
+        let lhs = self.expression()?;
+        self.eat_delimiter('=')?;
+        let rhs = self.expression()?;
+        Ok(Term { lhs, rhs })
+    }
+
+    fn predicate(&mut self) -> Result<Predicate> {
+        let mut terms = vec![self.term()?];
+        while self.match_keyword(Keyword::And) {
+            terms.push(self.term()?);
+        }
+        Ok(Predicate { terms })
+    }
+
+    fn where_clause(&mut self) -> Result<Predicate> {
+        if self.match_keyword(Keyword::Where) {
+            self.predicate()
+        } else {
+            Ok(Predicate::default())
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        match self.peek() {
+            Token::Keyword(Keyword::Select) => Ok(Statement::Query(self.query()?)),
+            Token::Keyword(Keyword::Insert) => Ok(Statement::Insert(self.insert()?)),
+            Token::Keyword(Keyword::Delete) => Ok(Statement::Delete(self.delete()?)),
+            Token::Keyword(Keyword::Update) => Ok(Statement::Update(self.modify()?)),
+            Token::Keyword(Keyword::Create) => self.create(),
+            _ => Err(self.error("SELECT, INSERT, DELETE, UPDATE or CREATE")),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        self.eat_keyword(Keyword::Select)?;
+        let mut fields = vec![self.field()?];
+        while self.match_delimiter(',') {
+            fields.push(self.field()?);
+        }
+        self.eat_keyword(Keyword::From)?;
+        let mut tables = vec![self.eat_identifier("table name")?];
+        while self.match_delimiter(',') {
+            tables.push(self.eat_identifier("table name")?);
+        }
+        let predicate = self.where_clause()?;
+        Ok(Query {
+            fields,
+            tables,
+            predicate,
+        })
+    }
+
+    fn insert(&mut self) -> Result<Insert> {
+        self.eat_keyword(Keyword::Insert)?;
+        self.eat_keyword(Keyword::Into)?;
+        let table = self.eat_identifier("table name")?;
+        self.eat_delimiter('(')?;
+        let mut fields = vec![self.field()?];
+        while self.match_delimiter(',') {
+            fields.push(self.field()?);
+        }
+        self.eat_delimiter(')')?;
+        self.eat_keyword(Keyword::Values)?;
+        self.eat_delimiter('(')?;
+        let mut values = vec![self.constant()?];
+        while values.len() < fields.len() && self.match_delimiter(',') {
+            values.push(self.constant()?);
+        }
+        // Points at the , before an extra value or at the early ), whichever is there.
+        if values.len() != fields.len() || self.peek() == &Token::Delimiter(',') {
+            return Err(self.error(&format!("{} values", fields.len())));
+        }
+        self.eat_delimiter(')')?;
+        Ok(Insert {
+            table,
+            fields,
+            values,
+        })
+    }
+
+    fn delete(&mut self) -> Result<Delete> {
+        self.eat_keyword(Keyword::Delete)?;
+        self.eat_keyword(Keyword::From)?;
+        let table = self.eat_identifier("table name")?;
+        let predicate = self.where_clause()?;
+        Ok(Delete { table, predicate })
+    }
+
+    fn modify(&mut self) -> Result<Update> {
+        self.eat_keyword(Keyword::Update)?;
+        let table = self.eat_identifier("table name")?;
+        self.eat_keyword(Keyword::Set)?;
+        let field = self.field()?;
+        self.eat_delimiter('=')?;
+        let value = self.expression()?;
+        let predicate = self.where_clause()?;
+        Ok(Update {
+            table,
+            field,
+            value,
+            predicate,
+        })
+    }
+
+    fn create(&mut self) -> Result<Statement> {
+        self.eat_keyword(Keyword::Create)?;
+        match self.peek() {
+            Token::Keyword(Keyword::Table) => Ok(Statement::CreateTable(self.create_table()?)),
+            Token::Keyword(Keyword::View) => Ok(Statement::CreateView(self.create_view()?)),
+            Token::Keyword(Keyword::Index) => Ok(Statement::CreateIndex(self.create_index()?)),
+            _ => Err(self.error("TABLE, VIEW or INDEX")),
+        }
+    }
+
+    fn create_table(&mut self) -> Result<CreateTable> {
+        self.eat_keyword(Keyword::Table)?;
+        let table = self.eat_identifier("table name")?;
+        self.eat_delimiter('(')?;
+        let mut schema = Schema::new();
+        loop {
+            self.field_def(&mut schema)?;
+            if !self.match_delimiter(',') {
+                break;
+            }
+        }
+        self.eat_delimiter(')')?;
+        Ok(CreateTable { table, schema })
+    }
+
+    fn field_def(&mut self, schema: &mut Schema) -> Result<()> {
+        let field_position = self.position;
+        let field_name = self.field()?;
+        if schema.has_field(&field_name) {
+            // Point back at the name, not at the type after it.
+            self.position = field_position;
+            return Err(self.error("a field name that isn't taken"));
+        }
+        if self.match_keyword(Keyword::Int) {
+            schema.add_int_field(&field_name);
+        } else if self.match_keyword(Keyword::Varchar) {
+            schema.add_string_field(&field_name, self.eat_length()?);
+        } else if self.match_keyword(Keyword::Bytes) {
+            schema.add_bytes_field(&field_name, self.eat_length()?);
+        } else {
+            return Err(self.error("INT, VARCHAR or BYTES"));
+        }
+        Ok(())
+    }
+
+    fn create_view(&mut self) -> Result<CreateView> {
+        self.eat_keyword(Keyword::View)?;
+        let view = self.eat_identifier("view name")?;
+        self.eat_keyword(Keyword::As)?;
+        let query = self.query()?;
+        Ok(CreateView { view, query })
+    }
+
+    fn create_index(&mut self) -> Result<CreateIndex> {
+        self.eat_keyword(Keyword::Index)?;
+        let index = self.eat_identifier("index name")?;
+        self.eat_keyword(Keyword::On)?;
+        let table = self.eat_identifier("table name")?;
+        self.eat_delimiter('(')?;
+        let field = self.field()?;
+        self.eat_delimiter(')')?;
+        Ok(CreateIndex {
+            index,
+            table,
+            field,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn field(name: &str) -> Expression {
+        Expression::Field(name.to_string())
+    }
+
+    fn int(value: i32) -> Expression {
+        Expression::Constant(Constant::Int(value))
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let statement = parse(
+            "SELECT sname, dname\nFROM student, dept\nWHERE majorid = did AND gradyear = 2020;",
+        )
+        .expect("failed to parse");
+        let Statement::Query(query) = statement else {
+            panic!("expected a query, got {:?}", statement);
+        };
+        assert_eq!(
+            query,
+            Query {
+                fields: strings(&["sname", "dname"]),
+                tables: strings(&["student", "dept"]),
+                predicate: Predicate {
+                    terms: vec![
+                        Term {
+                            lhs: field("majorid"),
+                            rhs: field("did"),
+                        },
+                        Term {
+                            lhs: field("gradyear"),
+                            rhs: int(2020),
+                        },
+                    ],
+                },
+            }
+        );
+        assert_eq!(
+            query.to_string(),
+            "select sname, dname from student, dept where majorid = did and gradyear = 2020"
+        );
+        assert_eq!(
+            parse("select a from t").expect("failed to parse"),
+            Statement::Query(Query {
+                fields: strings(&["a"]),
+                tables: strings(&["t"]),
+                predicate: Predicate::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_update_commands() {
+        assert_eq!(
+            parse("insert into student (sid, sname) values (1, 'joe')").expect("failed to parse"),
+            Statement::Insert(Insert {
+                table: "student".to_string(),
+                fields: strings(&["sid", "sname"]),
+                values: vec![Constant::Int(1), Constant::String("joe".to_string())],
+            })
+        );
+        assert_eq!(
+            parse("delete from student where sid = 1").expect("failed to parse"),
+            Statement::Delete(Delete {
+                table: "student".to_string(),
+                predicate: Predicate {
+                    terms: vec![Term {
+                        lhs: field("sid"),
+                        rhs: int(1),
+                    }],
+                },
+            })
+        );
+        assert_eq!(
+            parse("delete from student").expect("failed to parse"),
+            Statement::Delete(Delete {
+                table: "student".to_string(),
+                predicate: Predicate::default(),
+            })
+        );
+        assert_eq!(
+            parse("update student set majorid = 20 where majorid = 30").expect("failed to parse"),
+            Statement::Update(Update {
+                table: "student".to_string(),
+                field: "majorid".to_string(),
+                value: int(20),
+                predicate: Predicate {
+                    terms: vec![Term {
+                        lhs: field("majorid"),
+                        rhs: int(30),
+                    }],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_create() {
+        let mut schema = Schema::new();
+        schema
+            .add_int_field("sid")
+            .add_string_field("sname", 10)
+            .add_bytes_field("photo", 64);
+        assert_eq!(
+            parse("create table student (sid int, sname varchar(10), photo bytes(64))")
+                .expect("failed to parse"),
+            Statement::CreateTable(CreateTable {
+                table: "student".to_string(),
+                schema,
+            })
+        );
+
+        let statement = parse("create view honors as select sname from student where gpa = 4")
+            .expect("failed to parse");
+        let Statement::CreateView(create_view) = statement else {
+            panic!("expected a view, got {:?}", statement);
+        };
+        assert_eq!(create_view.view, "honors");
+        assert_eq!(
+            create_view.view_def(),
+            "select sname from student where gpa = 4"
+        );
+        // The definition parses back to the same query.
+        assert_eq!(
+            parse(&create_view.view_def()).expect("failed to parse"),
+            Statement::Query(create_view.query)
+        );
+
+        assert_eq!(
+            parse("CREATE INDEX sid_idx ON student (sid)").expect("failed to parse"),
+            Statement::CreateIndex(CreateIndex {
+                index: "sid_idx".to_string(),
+                table: "student".to_string(),
+                field: "sid".to_string(),
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::empty(
+        "",
+        1,
+        1,
+        "Expected SELECT, INSERT, DELETE, UPDATE or CREATE, found end of input"
+    )]
+    #[case::missing_from("select a, b where a = 1", 1, 13, "Expected FROM, found WHERE")]
+    #[case::keyword_as_table("select a from select", 1, 15, "Expected table name, found SELECT")]
+    #[case::dangling_and(
+        "select a from t\nwhere a = 1 and",
+        2,
+        16,
+        "Expected a field name or a constant, found end of input"
+    )]
+    #[case::trailing_tokens(
+        "delete from t; delete from u",
+        1,
+        16,
+        "Expected end of statement, found DELETE"
+    )]
+    #[case::too_few_values(
+        "insert into t (a, b) values (1)",
+        1,
+        31,
+        "Expected 2 values, found ')'"
+    )]
+    #[case::too_many_values(
+        "insert into t (a) values (1, 2)",
+        1,
+        28,
+        "Expected 1 values, found ','"
+    )]
+    #[case::bad_type(
+        "create table t (a float)",
+        1,
+        19,
+        "Expected INT, VARCHAR or BYTES, found float"
+    )]
+    #[case::zero_length(
+        "create table t (a varchar(0))",
+        1,
+        27,
+        "Expected a positive length, found 0"
+    )]
+    #[case::duplicate_field(
+        "create table t (a int, a int)",
+        1,
+        24,
+        "Expected a field name that isn't taken, found a"
+    )]
+    #[case::create_what(
+        "create database d",
+        1,
+        8,
+        "Expected TABLE, VIEW or INDEX, found database"
+    )]
+    #[case::lexer_error("select a from t where a = 'x", 1, 27, "Unterminated string")]
+    fn test_syntax_errors(
+        #[case] sql: &str,
+        #[case] line: usize,
+        #[case] column: usize,
+        #[case] message: &str,
+    ) {
+        assert_eq!(
+            parse(sql),
+            Err(StormDbError::Syntax {
+                line,
+                column,
+                message: message.to_string(),
+            })
+        );
+    }
+}